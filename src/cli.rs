@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
 /// emx-note - A Zettelkasten-style note management tool
 ///
@@ -10,8 +10,25 @@ use clap::{Parser, Subcommand};
 /// emx-note note "My Idea"           # Create permanent note
 /// emx-note note                    # Create with timestamp title
 /// emx-note note "Idea" -s "book" # Create in note/{hash}/
+/// emx-note note "Idea" --on-conflict suffix   # Title taken → note/idea-2.md
+/// emx-note note "Sync" -t meeting   # From .template/meeting.md ({{title}}, {{date}}, {{content}}...)
+/// emx-note note "Idea" --tag rust --meta status=draft --meta review.by=bob   # One write, tags indexed
+/// echo '{"title":"Idea","tags":["rust"],"meta":{"status":"draft"},"content":"..."}' | emx-note note --envelope
 /// emx-note print "Idea"           # Print note content
+/// emx-note edit "Idea"            # Open in $VISUAL/$EDITOR
+/// emx-note edit "Idea" --ops < ops.json   # Apply [{"op":"replace","old":..,"new":..}]
+/// emx-note patch "Idea" --dry-run < fix.diff   # Check a unified diff, then apply without --dry-run
 /// emx-note resolve "Idea"          # Get file path
+/// emx-note link backlinks "Idea"  # Notes, tags and tasks linking here
+/// emx-note link fix --apply        # Repair broken links with a clear candidate (-i to choose)
+/// emx-note link orphans -a          # Unlinked notes and attachments (images, PDFs, ...)
+/// emx-note link graph --note "Idea" -d 2 | dot -Tsvg > idea.svg   # Also -f graphml|json, --tag
+/// emx-note mv "Idea" "Better Idea"  # Rename, rewriting links/tags/tasks
+/// emx-note mv "Idea" note/archive/ --dry-run   # Preview move as a diff
+/// emx-note rm "Idea"                # Move to .trash/, drop tag/daily entries
+/// emx-note trash list               # Deleted notes: id, path, time, agent
+/// emx-note trash restore 20260214100000
+/// emx-note trash empty --older-than 30
 ///
 /// # From stdin/heredoc:
 /// emx-note note "Idea" <<EOF
@@ -26,6 +43,7 @@ use clap::{Parser, Subcommand};
 /// ```bash
 /// emx-note daily                   # Today's daily note
 /// emx-note daily "Meeting Notes"    # With title
+/// emx-note daily --no-template     # Skip the daily_template setting
 /// emx-note list "#daily"           # List all dates
 ///
 /// # From stdin/heredoc:
@@ -45,6 +63,10 @@ use clap::{Parser, Subcommand};
 /// emx-note tag add "Idea" rust programming
 /// emx-note tag remove "Idea" rust
 /// emx-note tag add "Note" tag --force    # Force: apply to all matches
+/// emx-note tag sync --dry-run          # Tag files vs frontmatter tags (exit 1 on drift)
+/// emx-note tag sync --from-index       # Tag notes in their frontmatter from existing tag files
+/// emx-note tag rebuild                 # Regenerate tag files from frontmatter tags
+/// emx-note config set inline_tags true # Also tag notes with the #hashtags of their body
 /// emx-note list "#rust"                # List notes with #rust tag
 /// emx-note list "#rust" --json         # JSON output for scripting
 /// ```
@@ -59,6 +81,15 @@ use clap::{Parser, Subcommand};
 /// emx-note list "20250113"               # List files in daily/YYYYMMDD/
 /// ```
 ///
+/// ## Search
+///
+/// ```bash
+/// emx-note search "rust async"                 # Ranked full-text search (BM25)
+/// emx-note search '"error handling" rust'      # Quoted words match as a phrase
+/// emx-note search rust --area daily --from 2025-01-01
+/// emx-note search rust --tag programming --json
+/// ```
+///
 /// ## Metadata
 ///
 /// ```bash
@@ -78,6 +109,17 @@ use clap::{Parser, Subcommand};
 /// emx-note default                        # View current default
 /// ```
 ///
+/// ## Settings
+///
+/// ```bash
+/// emx-note config list                     # Every setting, its value and source
+/// emx-note config get task_prefix
+/// emx-note config set task_prefix T-       # In the capsa's .emx-note.yaml
+/// emx-note config set backup true --defaults   # Home-level default for all capsae
+/// emx-note config set extensions .md .txt  # Lists take several values
+/// emx-note config set daily_dir journal    # Daily notes in journal/YYYYMMDD/
+/// ```
+///
 /// ## Global Options
 ///
 /// ```bash
@@ -91,6 +133,7 @@ use clap::{Parser, Subcommand};
 /// - `EMX_NOTE_HOME`: Base directory for all capsae (default: ~/.emx-notes)
 /// - `EMX_NOTE_DEFAULT`: Default capsa name (overrides .default)
 /// - `EMX_AGENT_NAME`: Agent name for prefixing (e.g., "agent1" → "agent1-work")
+/// - `EMX_TASKFILE`: Task file of `task` commands (overrides the `task_file` setting)
 ///
 /// ## Agent Prefixing
 ///
@@ -151,6 +194,9 @@ pub enum Command {
     Daily {
         /// Optional title for daily note
         title: Option<String>,
+
+        #[command(flatten)]
+        args: CreateArgs,
     },
 
     /// Create a permanent note (in note/ directory)
@@ -161,6 +207,9 @@ pub enum Command {
         /// Source of the note (creates in note/{hash}/ subdirectory if provided)
         #[arg(short = 's', long)]
         source: Option<String>,
+
+        #[command(flatten)]
+        args: CreateArgs,
     },
 
     /// Resolve note reference to file path
@@ -184,6 +233,60 @@ pub enum Command {
         note_name: String,
     },
 
+    /// Open a note in $VISUAL/$EDITOR, then update tag indexes, `modified` and the search index
+    #[command(alias = "e")]
+    Edit {
+        /// Note reference (supports resolve/print format)
+        note_ref: String,
+
+        /// Apply a JSON list of edit ops read from stdin instead of opening an editor
+        #[arg(long)]
+        ops: bool,
+    },
+
+    /// Apply a unified diff from stdin to a note
+    Patch {
+        /// Note reference (supports resolve/print format)
+        note_ref: String,
+
+        /// Context lines a hunk may ignore at each end to apply
+        #[arg(short = 'F', long, default_value_t = crate::constants::PATCH_DEFAULT_FUZZ)]
+        fuzz: usize,
+
+        /// Report where hunks would apply without changing the note
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Move or rename a note, rewriting links that point to it
+    #[command(alias = "rename")]
+    Mv {
+        /// Note reference (supports resolve/print format)
+        note_ref: String,
+
+        /// New title (renamed in place) or path relative to the capsa root
+        new_name: String,
+
+        /// Show the rename and link rewrites as a diff without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Delete a note (moved to the capsa trash, removed from tag and daily indexes)
+    #[command(alias = "delete")]
+    Rm {
+        /// Note reference (supports resolve/print format)
+        note_ref: String,
+
+        /// Delete even if tasks reference the note
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Full-text search across notes (BM25 ranked, persistent index)
+    #[command(alias = "s")]
+    Search(SearchArgs),
+
     /// Manage note metadata (YAML frontmatter)
     #[command(alias = "m")]
     Meta {
@@ -211,6 +314,10 @@ pub enum Command {
         caps: Option<String>,
     },
 
+    /// Show or change settings (`.emx-note.yaml` of the capsa or of the notes home)
+    #[command(subcommand)]
+    Config(ConfigCommand),
+
     /// Find and manage orphaned notes (no incoming links)
     /// NOTE: This command is not yet fully implemented
     #[command(hide = true)]
@@ -219,7 +326,7 @@ pub enum Command {
         #[arg(short, long, default_value = "7")]
         days: u32,
 
-        /// Actually delete the orphaned notes (default is dry-run)
+        /// Actually delete the orphaned notes, moving them to the trash (default is dry-run)
         #[arg(short, long)]
         execute: bool,
 
@@ -236,6 +343,10 @@ pub enum Command {
     #[command(subcommand)]
     Tag(TagCommand),
 
+    /// List, restore or purge deleted notes (.trash/)
+    #[command(subcommand)]
+    Trash(TrashCommand),
+
     /// Check and manage links between notes
     #[command(subcommand)]
     Link(LinkCommand),
@@ -245,6 +356,64 @@ pub enum Command {
     Task(TaskCommand),
 }
 
+/// Options shared by `note` and `daily`
+#[derive(Args, Debug, Default)]
+pub struct CreateArgs {
+    /// If the filename is taken: error, suffix, timestamp or append (default from capsa settings)
+    #[arg(long, value_name = "POLICY")]
+    pub on_conflict: Option<String>,
+
+    /// Template from .template/ (default: the note_template or daily_template setting)
+    #[arg(short = 't', long, value_name = "NAME", conflicts_with = "no_template")]
+    pub template: Option<String>,
+
+    /// Ignore the note_template or daily_template setting
+    #[arg(long)]
+    pub no_template: bool,
+
+    /// Tag the note, in its frontmatter and tag index (repeatable)
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Set a frontmatter key, nested keys as in `meta` (repeatable; a repeated key makes a list)
+    #[arg(long = "meta", value_name = "KEY=VALUE")]
+    pub meta: Vec<String>,
+
+    /// Read a JSON envelope {"title", "tags", "meta", "content"} from stdin; the result is reported as JSON
+    #[arg(long)]
+    pub envelope: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// Query terms; wrap words in double quotes to match a phrase
+    pub query: String,
+
+    /// Only notes in this area: daily, permanent, literature
+    #[arg(long)]
+    pub area: Option<String>,
+
+    /// Only notes with this tag
+    #[arg(short, long)]
+    pub tag: Option<String>,
+
+    /// Only notes dated on or after this date (YYYY-MM-DD or YYYYMMDD)
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Only notes dated on or before this date (YYYY-MM-DD or YYYYMMDD)
+    #[arg(long)]
+    pub to: Option<String>,
+
+    /// Maximum number of results
+    #[arg(short = 'n', long, default_value_t = crate::constants::DEFAULT_SEARCH_LIMIT)]
+    pub limit: usize,
+
+    /// Rebuild the index from scratch before searching
+    #[arg(long)]
+    pub rebuild: bool,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum LinkCommand {
     /// Check for broken local links
@@ -266,6 +435,50 @@ pub enum LinkCommand {
         /// Path to scan (default: current capsa root)
         #[arg(short, long)]
         path: Option<String>,
+
+        /// Also report attachments (images, PDFs, ...) no note links to
+        #[arg(short, long)]
+        attachments: bool,
+    },
+
+    /// List the files that link to a note, with context
+    Backlinks {
+        /// Note reference (supports resolve/print format)
+        note_ref: String,
+    },
+
+    /// Propose repairs for broken links, and apply them
+    Fix {
+        /// Path to scan (default: current capsa root)
+        #[arg(short, long)]
+        path: Option<String>,
+
+        /// Rewrite every link that has a single best candidate
+        #[arg(long, conflicts_with = "interactive")]
+        apply: bool,
+
+        /// Choose a candidate for each broken link
+        #[arg(short, long)]
+        interactive: bool,
+    },
+
+    /// Export the note graph as Graphviz DOT, GraphML or node-link JSON
+    Graph {
+        /// Output format: dot, graphml or json (default: dot, or json with --json)
+        #[arg(short, long, value_name = "FORMAT")]
+        format: Option<String>,
+
+        /// Only notes with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// Only notes within --depth links of this note (either direction)
+        #[arg(short, long, value_name = "NOTE_REF")]
+        note: Option<String>,
+
+        /// Number of links to follow from --note
+        #[arg(short, long, default_value_t = crate::constants::LINK_GRAPH_DEFAULT_DEPTH)]
+        depth: usize,
     },
 }
 
@@ -298,6 +511,55 @@ pub enum TagCommand {
         #[arg(short, long)]
         force: bool,
     },
+
+    /// Update tag files to match the notes' frontmatter tags
+    Sync {
+        /// Only report the differences (exit status 1 if any)
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Remove stale entries (notes not tagged in their frontmatter)
+        #[arg(long)]
+        prune: bool,
+
+        /// First tag the notes listed in tag files in their frontmatter
+        #[arg(long)]
+        from_index: bool,
+    },
+
+    /// Regenerate every tag file from the notes' frontmatter tags
+    Rebuild {
+        /// Only report the files that would change
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Drop stale entries (notes not tagged in their frontmatter)
+        #[arg(long)]
+        prune: bool,
+
+        /// First tag the notes listed in tag files in their frontmatter
+        #[arg(long)]
+        from_index: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommand {
+    /// List deleted notes
+    List,
+
+    /// Restore a deleted note to its original path (and its tag/daily index entries)
+    Restore {
+        /// Trash entry id (from `trash list`)
+        id: String,
+    },
+
+    /// Permanently delete trashed notes
+    Empty {
+        /// Only entries deleted at least this many days ago (default: all)
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u32>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -321,6 +583,43 @@ pub enum CapsaCommand {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// List every setting with its value and where it comes from
+    List {
+        /// Home-level defaults instead of the capsa settings
+        #[arg(long)]
+        defaults: bool,
+    },
+
+    /// Print the value of a setting
+    Get {
+        /// Setting name (e.g. task_prefix)
+        key: String,
+
+        /// Home-level defaults instead of the capsa settings
+        #[arg(long)]
+        defaults: bool,
+    },
+
+    /// Set a setting in the capsa settings file
+    Set {
+        /// Setting name (e.g. task_prefix)
+        key: String,
+
+        /// Value (several values for a list, e.g. `extensions .md .txt`)
+        value: Vec<String>,
+
+        /// Remove the setting from the file instead (back to the next layer)
+        #[arg(long)]
+        delete: bool,
+
+        /// Write the home-level defaults instead of the capsa settings
+        #[arg(long)]
+        defaults: bool,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum TaskCommand {
     /// Add a new task (auto-increment ID)
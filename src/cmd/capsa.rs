@@ -64,7 +64,7 @@ fn create_capsa(ctx: &ResolveContext, name: &str, path: Option<String>) -> io::R
 
         // Create link file with INI-style content
         let link_content = format!("[link]\ntarget = {}", util::display_path(&absolute));
-        fs::write(&capsa_path, link_content)?;
+        util::atomic_write(&capsa_path, &link_content)?;
 
         // Output the link file path
         println!("{}", util::display_path(&capsa_path));
@@ -73,8 +73,8 @@ fn create_capsa(ctx: &ResolveContext, name: &str, path: Option<String>) -> io::R
         // Create the directory
         fs::create_dir_all(&capsa_path)?;
 
-        // Create default subdirectories
-        fs::create_dir_all(capsa_path.join("#daily"))?;
+        // Create default subdirectories (the home-level settings may move the daily directory)
+        fs::create_dir_all(capsa_path.join(&emx_note::CapsaConfig::load(&capsa_path)?.daily_dir))?;
 
         // Output the created path
         println!("{}", util::display_path(&capsa_path));
@@ -0,0 +1,87 @@
+//! Settings command module
+//!
+//! Shows and changes the settings of `emx_note::config`: the capsa's
+//! `.emx-note.yaml`, or with `--defaults` the one in the notes home, which
+//! gives the defaults of every capsa.
+
+use std::fs;
+use std::io;
+
+use serde_json::json;
+use emx_note::config::render_value;
+use emx_note::{util, CapsaConfig, CapsaLock, ConfigCommand, Setting};
+
+pub fn run(
+    ctx: &emx_note::ResolveContext,
+    caps: Option<&str>,
+    cmd: ConfigCommand,
+) -> io::Result<()> {
+    match cmd {
+        ConfigCommand::List { defaults } => {
+            let settings = settings(ctx, caps, defaults)?;
+            if ctx.json {
+                let map: serde_json::Map<String, serde_json::Value> = settings.iter()
+                    .map(|s| (s.key.clone(), json!({ "value": s.value, "source": s.source })))
+                    .collect();
+                println!("{}", serde_json::Value::Object(map));
+            } else {
+                for s in &settings {
+                    println!("{}: {} ({})", s.key, render_value(&s.value), s.source);
+                }
+            }
+            Ok(())
+        }
+        ConfigCommand::Get { key, defaults } => {
+            CapsaConfig::check_key(&key)?;
+            let settings = settings(ctx, caps, defaults)?;
+            if let Some(setting) = settings.iter().find(|s| s.key == key) {
+                if ctx.json {
+                    println!("{}", json!({ "key": setting.key, "value": setting.value, "source": setting.source }));
+                } else {
+                    println!("{}", render_value(&setting.value));
+                }
+            }
+            Ok(())
+        }
+        ConfigCommand::Set { key, value, delete, defaults } => {
+            let new_value = if delete {
+                CapsaConfig::check_key(&key)?;
+                if !value.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "No value expected with --delete",
+                    ));
+                }
+                None
+            } else {
+                Some(CapsaConfig::parse_value(&key, &value)?)
+            };
+
+            let file = if defaults {
+                fs::create_dir_all(&ctx.home)?;
+                let file = CapsaConfig::home_file(&ctx.home);
+                CapsaConfig::write_setting(&file, &key, new_value.as_ref())?;
+                file
+            } else {
+                let capsa_ref = super::resolve::resolve_capsa(ctx, caps)?;
+                let _lock = CapsaLock::acquire(&capsa_ref.path)?;
+                let file = CapsaConfig::file(&capsa_ref.path);
+                CapsaConfig::write_setting(&file, &key, new_value.as_ref())?;
+                file
+            };
+
+            println!("{}", util::display_path(&file));
+            Ok(())
+        }
+    }
+}
+
+/// Settings of the capsa, or the home-level defaults with `defaults`
+fn settings(ctx: &emx_note::ResolveContext, caps: Option<&str>, defaults: bool) -> io::Result<Vec<Setting>> {
+    if defaults {
+        CapsaConfig::settings(None)
+    } else {
+        let capsa_ref = super::resolve::resolve_capsa(ctx, caps)?;
+        CapsaConfig::settings(Some(&capsa_ref.path))
+    }
+}
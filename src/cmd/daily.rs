@@ -1,19 +1,43 @@
 //! Daily note command module
 
 use std::io;
-use emx_note::{CapsaEngine, util, read_stdin_content};
+use emx_note::{frontmatter, CapsaConfig, CapsaEngine, CreateArgs, TemplateVars};
 
-pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, title: Option<String>) -> io::Result<()> {
-    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+pub fn run(
+    ctx: &emx_note::ResolveContext,
+    caps: Option<&str>,
+    title: Option<String>,
+    args: CreateArgs,
+) -> io::Result<()> {
+    let capsa_ref = super::resolve::resolve_capsa(ctx, caps)?;
 
-    // Read content from stdin (empty if no data)
-    let content = read_stdin_content()?;
+    // Read content from stdin (empty if no data), rendered with the template if any
+    let input = super::note::read_input(title, &args)?;
+    let config = CapsaConfig::load(&capsa_ref.path)?;
+    let vars = TemplateVars::now(
+        input.title.as_deref().unwrap_or(&config.daily_title),
+        &capsa_ref.name,
+        None,
+        ctx.agent_name.as_deref(),
+        &input.content,
+    );
+    let (content, template) = super::note::apply_template(
+        &capsa_ref,
+        args.template.clone(),
+        &config.daily_template,
+        args.no_template,
+        &vars,
+    )?;
+    let content = frontmatter::merge(&content, &input.fields)?;
+    let tags = super::note::indexed_tags(&config, &input.tags, &content);
 
-    // Create the daily note
-    let note_path = capsa.create_daily_note(title.as_deref(), &content)?;
+    let capsa = CapsaEngine::new(capsa_ref);
+    let policy = super::note::conflict_policy(&capsa, args.on_conflict.as_deref())?;
 
-    // Output full path for shell pipeline compatibility
-    println!("{}", util::display_path(&note_path));
+    // Create the daily note and index its tags
+    let created = capsa.create_tagged(&tags, |capsa| {
+        capsa.create_daily_note_with(input.title.as_deref(), &content, policy)
+    })?;
 
-    Ok(())
+    super::note::report(ctx, &args, &created, template, &tags)
 }
@@ -1,7 +1,6 @@
 //! Default capsa management command module
 
 use std::io;
-use std::fs;
 
 pub fn run(
     ctx: &emx_note::ResolveContext,
@@ -12,7 +11,7 @@ pub fn run(
         let _capsa_ref = super::resolve::resolve_capsa(ctx, Some(&caps_name))?;
         let default_file = ctx.home.join(".default");
 
-        fs::write(&default_file, &caps_name)?;
+        emx_note::util::atomic_write(&default_file, &caps_name)?;
         eprintln!("Default capsa set to '{}'", caps_name);
         Ok(())
     } else {
@@ -0,0 +1,172 @@
+//! Edit note command module
+//!
+//! Opens a resolved note in `$VISUAL` / `$EDITOR`, then post-processes the
+//! saved file: tag indexes follow frontmatter `tags`, `modified` is bumped
+//! and the search index is updated.
+//!
+//! With `--ops`, a JSON list of [`EditOp`]s is read from stdin instead and
+//! applied atomically: either every op validates and the note is written
+//! once, or nothing changes and the failing op is reported.
+
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde_json::json;
+use emx_note::{CapsaConfig, CapsaEngine, EditOp, ResolvedNote, ValidationError, util};
+
+pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, note_ref: &str, ops: bool) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+    if ops {
+        return run_ops(ctx, &capsa, note_ref);
+    }
+    let note_path = resolve_interactive(&capsa.path, note_ref)?;
+
+    let before = std::fs::read_to_string(&note_path)?;
+    open_in_editor(&note_path)?;
+    let edited = capsa.finish_edit(&note_path, &before)?;
+
+    if ctx.json {
+        println!("{}", json!({
+            "path": util::display_path(&note_path),
+            "changed": edited.changed,
+            "tags_added": edited.tags_added,
+            "tags_removed": edited.tags_removed,
+        }));
+    } else {
+        println!("{}", util::display_path(&note_path));
+        if !edited.tags_added.is_empty() {
+            eprintln!("Tagged: {}", edited.tags_added.join(", "));
+        }
+        if !edited.tags_removed.is_empty() {
+            eprintln!("Untagged: {}", edited.tags_removed.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply JSON edit ops from stdin to a note
+fn run_ops(ctx: &emx_note::ResolveContext, capsa: &CapsaEngine, note_ref: &str) -> io::Result<()> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let ops = EditOp::parse_list(&input)?;
+
+    let note_path = emx_note::resolve_note_or_error(&capsa.path, note_ref, &capsa.config()?.extensions())?;
+    let backup = capsa.config()?.backup;
+
+    // Validate every op against the freshly read content before writing;
+    // the first failing op is kept for the report
+    let mut before = String::new();
+    let mut failed: Option<(usize, ValidationError)> = None;
+    let result = emx_note::update_file(&note_path, backup, |file| {
+        before = file.content().to_string();
+        let mut content = before.clone();
+        for (index, op) in ops.iter().enumerate() {
+            match emx_note::apply_edits(&content, vec![op.clone()]) {
+                Ok(new_content) => content = new_content,
+                Err(e) => {
+                    let message = format!("Edit op {} ({}) failed: {}", index, op.name(), e);
+                    failed = Some((index, e));
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            }
+        }
+        Ok(Some(content))
+    });
+
+    let written = match result {
+        Ok(written) => written,
+        Err(e) => {
+            if let (true, Some((index, error))) = (ctx.json, &failed) {
+                // {"op": 1, "type": "replace", "kind": "MultipleMatches", "pattern": .., "count": 3, ..}
+                let mut report = json!({ "op": index, "type": ops[*index].name() });
+                if let (Some(report), Ok(serde_json::Value::Object(detail))) =
+                    (report.as_object_mut(), serde_json::to_value(error))
+                {
+                    report.extend(detail);
+                    report.insert("message".to_string(), json!(error.to_string()));
+                }
+                println!("{}", json!({
+                    "path": util::display_path(&note_path),
+                    "applied": false,
+                    "error": report,
+                }));
+            }
+            return Err(e);
+        }
+    };
+
+    let edited = match written {
+        Some(_) => capsa.finish_edit(&note_path, &before)?,
+        None => Default::default(),
+    };
+
+    if ctx.json {
+        let content = std::fs::read_to_string(&note_path)?;
+        println!("{}", json!({
+            "path": util::display_path(&note_path),
+            "applied": true,
+            "ops": ops.len(),
+            "changed": edited.changed,
+            "version": emx_note::edit::content_version(&content),
+            "tags_added": edited.tags_added,
+            "tags_removed": edited.tags_removed,
+        }));
+    } else {
+        println!("{}", util::display_path(&note_path));
+    }
+
+    Ok(())
+}
+
+/// Resolve a note reference, letting the user pick one if it is ambiguous
+/// and stdin is a terminal
+fn resolve_interactive(capsa_path: &Path, note_ref: &str) -> io::Result<PathBuf> {
+    let config = CapsaConfig::load(capsa_path)?;
+    let extensions = config.extensions();
+    match emx_note::resolve_note(capsa_path, note_ref, &extensions)? {
+        ResolvedNote::Ambiguous(candidates) if io::stdin().is_terminal() => {
+            choose(capsa_path, note_ref, &candidates)
+        }
+        _ => emx_note::resolve_note_or_error(capsa_path, note_ref, &extensions),
+    }
+}
+
+/// Numbered chooser on stderr/stdin
+fn choose(capsa_path: &Path, note_ref: &str, candidates: &[PathBuf]) -> io::Result<PathBuf> {
+    eprintln!("Multiple notes match '{}':", note_ref);
+    for (i, path) in candidates.iter().enumerate() {
+        eprintln!("  {}. {}", i + 1, util::relative_path(capsa_path, path));
+    }
+    eprint!("Select [1-{}]: ", candidates.len());
+    io::stderr().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    input.trim().parse::<usize>().ok()
+        .filter(|n| (1..=candidates.len()).contains(n))
+        .map(|n| candidates[n - 1].clone())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No note selected"))
+}
+
+/// Run the user's editor on a file and wait for it to exit
+fn open_in_editor(path: &Path) -> io::Result<()> {
+    let editor = ["VISUAL", "EDITOR"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+
+    // Allow editors with arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to launch editor '{}': {}", editor, e)))?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!("Editor '{}' exited with {}", editor, status)));
+    }
+    Ok(())
+}
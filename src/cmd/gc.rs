@@ -4,16 +4,14 @@
 //! 1. Are older than N days
 //! 2. Have no incoming links from other notes
 //!
-//! Default mode is dry-run (list only), use --execute to actually delete.
+//! Default mode is dry-run (list only), use --execute to move them to the trash.
 //! Use --force to skip confirmation prompt.
 
-use std::collections::HashSet;
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 use chrono::{DateTime, Local};
-use pulldown_cmark::{Parser, Event, Tag};
 
 pub fn run(
     ctx: &emx_note::ResolveContext,
@@ -30,7 +28,8 @@ pub fn run(
     println!();
 
     // Find all markdown files
-    let all_notes = find_all_notes(&capsa_ref.path)?;
+    let config = emx_note::CapsaConfig::load(&capsa_ref.path)?;
+    let all_notes = find_all_notes(&capsa_ref.path, &config.extensions())?;
 
     if verbose {
         println!("Found {} notes total", all_notes.len());
@@ -52,7 +51,8 @@ pub fn run(
             .replace('\\', "/");
 
         // Skip files in special directories and root tag files
-        if relative.starts_with(".template/") || relative.starts_with("#daily/") || relative.starts_with("note/") {
+        let in_daily = relative.strip_prefix(config.daily_dir.as_str()).is_some_and(|rest| rest.starts_with('/'));
+        if relative.starts_with(".template/") || in_daily || relative.starts_with("note/") {
             continue;
         }
         // Skip root directory tag files (#*.md)
@@ -119,12 +119,14 @@ pub fn run(
             }
         }
 
-        println!("Deleting {} orphaned note(s)...", orphaned.len());
+        println!("Moving {} orphaned note(s) to the trash...", orphaned.len());
+        let capsa = emx_note::CapsaEngine::new(capsa_ref.clone());
+        let _lock = capsa.lock()?;
         for (path, relative) in &orphaned {
-            fs::remove_file(path)?;
-            println!("  Deleted: {}", relative);
+            let deleted = capsa.delete_note(path, true)?;
+            println!("  Deleted: {} (trash id {})", relative, deleted.id);
         }
-        println!("Done.");
+        println!("Done. Use `emx-note trash restore <id>` to undo.");
     } else {
         println!("Dry-run mode. Run with --execute to actually delete these notes.");
         println!("Tip: Use --execute --force to skip confirmation prompt.");
@@ -134,13 +136,13 @@ pub fn run(
 }
 
 /// Find all markdown files in the capsa
-fn find_all_notes(capsa_path: &PathBuf) -> io::Result<Vec<PathBuf>> {
+fn find_all_notes(capsa_path: &PathBuf, extensions: &[&str]) -> io::Result<Vec<PathBuf>> {
     let mut notes = Vec::new();
-    find_notes_recursive(capsa_path, &capsa_path, &mut notes)?;
+    find_notes_recursive(capsa_path, &capsa_path, extensions, &mut notes)?;
     Ok(notes)
 }
 
-fn find_notes_recursive(base: &PathBuf, current: &PathBuf, notes: &mut Vec<PathBuf>) -> io::Result<()> {
+fn find_notes_recursive(base: &PathBuf, current: &PathBuf, extensions: &[&str], notes: &mut Vec<PathBuf>) -> io::Result<()> {
     if !current.is_dir() {
         return Ok(());
     }
@@ -153,10 +155,10 @@ fn find_notes_recursive(base: &PathBuf, current: &PathBuf, notes: &mut Vec<PathB
             // Skip hidden directories
             if let Some(name) = path.file_name() {
                 if !name.to_string_lossy().starts_with('.') {
-                    find_notes_recursive(base, &path, notes)?;
+                    find_notes_recursive(base, &path, extensions, notes)?;
                 }
             }
-        } else if path.extension().map(|e| e == "md").unwrap_or(false) {
+        } else if extensions.iter().any(|ext| path.to_string_lossy().ends_with(ext)) {
             notes.push(path);
         }
     }
@@ -164,34 +166,37 @@ fn find_notes_recursive(base: &PathBuf, current: &PathBuf, notes: &mut Vec<PathB
     Ok(())
 }
 
-/// Build a map of all links (target -> list of source files) using pulldown-cmark
+/// Build a map of all links (target -> list of source files), both markdown
+/// and wiki links, keyed by the capsa-relative path of the linked note
 fn build_link_map(
     capsa_path: &PathBuf,
     notes: &[PathBuf],
     verbose: bool,
 ) -> io::Result<std::collections::HashMap<String, Vec<String>>> {
     let mut link_map: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
-
-    for note_path in notes {
-        let source_relative = note_path.strip_prefix(capsa_path)
-            .unwrap_or(note_path)
+    let relative = |path: &Path| {
+        path.strip_prefix(capsa_path)
+            .unwrap_or(path)
             .to_string_lossy()
-            .replace('\\', "/");
+            .replace('\\', "/")
+    };
 
-        let content = match fs::read_to_string(note_path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
+    // Parsed links come from the link cache; only resolution is done here
+    let cache = emx_note::LinkCache::open(capsa_path)?;
+    let resolver = emx_note::NoteResolver::from_cache(capsa_path, &cache)?;
 
-        // Extract links using pulldown-cmark (for standard markdown links)
-        let mut links = extract_links(&content);
+    for note_path in notes {
+        let source_relative = relative(note_path);
 
-        // Also extract wiki links from raw content (pulldown-cmark may not handle them as text)
-        extract_wiki_links(&content, &mut links);
+        // Files missing from the cache could not be read (e.g. not UTF-8)
+        let Some(cached) = cache.get(&source_relative) else { continue };
 
-        for target in links.iter() {
-            let normalized = normalize_link(target);
-            link_map.entry(normalized).or_default().push(source_relative.clone());
+        for link in &cached.links {
+            if let emx_note::ResolvedNote::Found(target) = emx_note::links::resolve(&resolver, note_path, link)? {
+                if target != *note_path {
+                    link_map.entry(relative(&target)).or_default().push(source_relative.clone());
+                }
+            }
         }
     }
 
@@ -202,100 +207,3 @@ fn build_link_map(
 
     Ok(link_map)
 }
-
-/// Extract all links from markdown content using pulldown-cmark
-fn extract_links(content: &str) -> HashSet<String> {
-    let mut links = HashSet::new();
-    let parser = Parser::new(content);
-
-    for event in parser {
-        match event {
-            // Standard markdown links [text](url)
-            Event::Start(Tag::Link { dest_url, .. }) => {
-                // Only track internal .md links
-                let url_str = dest_url.to_string();
-                if is_internal_link(&url_str) {
-                    links.insert(url_str);
-                }
-            }
-            // Wiki-style links [[note]] are not directly supported by pulldown-cmark
-            // but we can check for them in text events
-            Event::Text(text) => {
-                extract_wiki_links(&text, &mut links);
-            }
-            Event::InlineHtml(html) | Event::Html(html) => {
-                // Also check HTML blocks for wiki links (some parsers handle them this way)
-                extract_wiki_links(&html, &mut links);
-            }
-            _ => {}
-        }
-    }
-
-    links
-}
-
-/// Check if a link is an internal markdown link
-fn is_internal_link(url: &str) -> bool {
-    // Skip external URLs
-    if url.starts_with("http://") || url.starts_with("https://") ||
-       url.starts_with("ftp://") || url.starts_with("mailto:") ||
-       url.starts_with("tel:") {
-        return false;
-    }
-
-    // Check if it's a markdown file or has no extension (wiki-style)
-    url.ends_with(".md") || !url.contains('.')
-}
-
-/// Extract wiki-style [[links]] from text
-fn extract_wiki_links(text: &str, links: &mut HashSet<String>) {
-    let mut chars = text.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if c == '[' && chars.peek() == Some(&'[') {
-            chars.next(); // consume second '['
-
-            let mut link = String::new();
-            let mut closed = false;
-
-            while let Some(c) = chars.next() {
-                if c == ']' && chars.peek() == Some(&']') {
-                    chars.next(); // consume second ']'
-                    closed = true;
-                    break;
-                }
-                // Handle display text separator
-                if c == '|' {
-                    break;
-                }
-                // Handle anchor
-                if c == '#' && !link.is_empty() {
-                    // Keep anchor as part of link for now, normalize_link will strip it
-                }
-                link.push(c);
-            }
-
-            if closed && !link.is_empty() {
-                links.insert(link);
-            }
-        }
-    }
-}
-
-/// Normalize a link target to a consistent format
-fn normalize_link(link: &str) -> String {
-    let mut normalized = link.to_string();
-
-    // Remove anchor part
-    if let Some(idx) = normalized.find('#') {
-        normalized.truncate(idx);
-    }
-
-    // Ensure .md extension
-    if !normalized.ends_with(".md") {
-        normalized.push_str(".md");
-    }
-
-    // Normalize path separators
-    normalized.replace('\\', "/")
-}
@@ -1,32 +1,25 @@
 //! Link check command module
 //!
 //! This module provides functionality to check and manage links between notes.
-//! Uses pulldown-cmark for markdown parsing to detect and validate local links.
+//! Links are collected by `emx_note::links`: CommonMark links (parsed with
+//! pulldown-cmark) and wiki links (`[[Title]]`, `[[note#heading|alias]]`),
+//! resolved like the rest of emx-note resolves notes. `link fix` rewrites
+//! broken links with the candidates proposed by `emx_note::link_fix`.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
-use pulldown_cmark::{Event, Parser, Tag, TagEnd};
-
-/// Information about a link found in a markdown file
-#[derive(Debug, Clone)]
-pub struct LinkInfo {
-    /// Source file path (relative to scan root)
-    pub source: PathBuf,
-    /// Line number where the link appears
-    pub line: usize,
-    /// Link target (as it appears in markdown)
-    pub target: String,
-    /// Whether this link is broken (target doesn't exist)
-    pub broken: bool,
-}
+use serde_json::json;
+use emx_note::link_fix::{self, BrokenLink, FixCandidate};
+use emx_note::{links, util, CapsaConfig, CapsaEngine, GraphFormat, LinkGraph, NoteLink, ResolvedNote};
+use emx_note::constants as C;
 
 /// Result of link scanning operation
 pub struct ScanResult {
     /// All links found (both valid and broken)
-    pub links: Vec<LinkInfo>,
+    pub links: Vec<NoteLink>,
     /// Source files that were scanned
     pub sources: Vec<PathBuf>,
 }
@@ -53,51 +46,100 @@ pub fn run(
             };
             list_links(&capsa_ref.path, scan_path)
         }
-        emx_note::cli::LinkCommand::Orphans { ref path } => {
+        emx_note::cli::LinkCommand::Orphans { ref path, attachments } => {
+            let scan_path = match path {
+                Some(ref p) => Path::new(p),
+                None => &capsa_ref.path,
+            };
+            find_orphans(&capsa_ref.path, scan_path, *attachments)
+        }
+        emx_note::cli::LinkCommand::Backlinks { ref note_ref } => {
+            backlinks(ctx, &capsa_ref.path, note_ref)
+        }
+        emx_note::cli::LinkCommand::Fix { ref path, apply, interactive } => {
             let scan_path = match path {
                 Some(ref p) => Path::new(p),
                 None => &capsa_ref.path,
             };
-            find_orphans(&capsa_ref.path, scan_path)
+            let capsa = CapsaEngine::new(capsa_ref.clone());
+            fix(ctx, &capsa, scan_path, *apply, *interactive)
+        }
+        emx_note::cli::LinkCommand::Graph { ref format, ref tag, ref note, depth } => {
+            graph(ctx, &capsa_ref.path, format.as_deref(), tag.as_deref(), note.as_deref(), *depth)
         }
     }
 }
 
-/// Check for broken links in a directory
+/// Check for broken links and anchors in a directory
 fn check(root: &Path, scan_path: &Path) -> io::Result<()> {
-    let result = scan_dir(scan_path)?;
+    let result = scan_dir(root, scan_path)?;
+    let anchors = links::broken_anchors(root, &result.sources)?;
 
-    let broken_count = result.links.iter().filter(|l| l.broken).count();
+    let broken_count = result.links.iter().filter(|l| l.is_broken()).count();
 
     if broken_count > 0 {
         eprintln!("Found {} broken link(s):", broken_count);
-        for link in &result.links {
-            if link.broken {
-                let source_display = link.source.strip_prefix(root).unwrap_or(&link.source);
-                eprintln!("  {}:{}: broken link -> {}",
-                    source_display.display(),
-                    link.line,
-                    link.target
-                );
-            }
+        for link in result.links.iter().filter(|l| l.is_broken()) {
+            let problem = match &link.resolution {
+                ResolvedNote::Ambiguous(candidates) => {
+                    format!("ambiguous link ({} candidates)", candidates.len())
+                }
+                _ => "broken link".to_string(),
+            };
+            eprintln!("  {}:{}: {} -> {}",
+                util::relative_path(root, &link.source),
+                link.line,
+                problem,
+                link.target
+            );
         }
-        Err(io::Error::other(format!("Found {} broken link(s)", broken_count)))
-    } else {
+    }
+
+    if !anchors.is_empty() {
+        eprintln!("Found {} broken anchor(s):", anchors.len());
+        for anchor in &anchors {
+            let hint = match &anchor.suggestion {
+                Some(suggestion) => format!(" (did you mean #{}?)", suggestion),
+                None => String::new(),
+            };
+            eprintln!("  {}:{}: broken anchor -> {}{}",
+                util::relative_path(root, &anchor.source),
+                anchor.line,
+                anchor.target,
+                hint
+            );
+        }
+    }
+
+    let mut problems = Vec::new();
+    if broken_count > 0 {
+        problems.push(format!("{} broken link(s)", broken_count));
+    }
+    if !anchors.is_empty() {
+        problems.push(format!("{} broken anchor(s)", anchors.len()));
+    }
+
+    if problems.is_empty() {
         println!("No broken links found. OK.");
         Ok(())
+    } else {
+        Err(io::Error::other(format!("Found {}", problems.join(" and "))))
     }
 }
 
 /// List all local links found in directory
 fn list_links(root: &Path, scan_path: &Path) -> io::Result<()> {
-    let result = scan_dir(scan_path)?;
+    let result = scan_dir(root, scan_path)?;
 
     for link in &result.links {
-        let source_display = link.source.strip_prefix(root).unwrap_or(&link.source);
-        let status = if link.broken { "BROKEN" } else { "OK" };
+        let status = match link.resolution {
+            ResolvedNote::Found(_) => "OK",
+            ResolvedNote::Ambiguous(_) => "AMBIGUOUS",
+            ResolvedNote::NotFound => "BROKEN",
+        };
 
         println!("{}:{}: {} -> {}",
-            source_display.display(),
+            util::relative_path(root, &link.source),
             link.line,
             status,
             link.target
@@ -107,37 +149,43 @@ fn list_links(root: &Path, scan_path: &Path) -> io::Result<()> {
     Ok(())
 }
 
-/// Find files that are not linked by any other file
-fn find_orphans(root: &Path, scan_path: &Path) -> io::Result<()> {
-    let result = scan_dir(scan_path)?;
-
-    // Build a set of all linked targets
+/// Find files that are not linked by any other file, including attachments
+/// with `attachments`
+///
+/// Index files (`#tag.md`, `note/#daily.md`, the task file) are not notes and
+/// are never reported.
+fn find_orphans(root: &Path, scan_path: &Path, attachments: bool) -> io::Result<()> {
+    let config = emx_note::CapsaConfig::load(root)?;
+    let task_file = root.join(&config.task_file);
+    let result = scan_dir(root, scan_path)?;
+
+    // Build a set of all linked targets (links from a file to itself don't count)
     let mut linked_targets: HashSet<PathBuf> = HashSet::new();
-    for link in result.links.iter().filter(|l| !l.broken) {
-        let target = scan_path.join(&link.target);
+    for link in &result.links {
+        let Some(target) = link.resolved() else { continue };
         if target.is_dir() {
-            // Add all .md files in directory
-            if let Ok(entries) = fs::read_dir(&target) {
+            // Add all files in directory (notes and attachments)
+            if let Ok(entries) = fs::read_dir(target) {
                 for entry in entries.flatten() {
-                    let path = entry.path();
-                    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                        if ext == "md" || ext == "mx" || ext == "emx" {
-                            linked_targets.insert(path);
-                        }
-                    }
+                    linked_targets.insert(entry.path());
                 }
             }
-        } else {
-            linked_targets.insert(target);
+        } else if target != link.source {
+            linked_targets.insert(target.to_path_buf());
         }
     }
 
     // Find orphans (files that exist but are not in linked_targets)
-    let mut orphans: Vec<_> = result.sources.iter()
-        .filter(|source| !linked_targets.contains(*source))
+    let mut orphans: Vec<PathBuf> = result.sources.iter()
+        .filter(|source| !linked_targets.contains(*source) && !is_index_file(&task_file, source))
+        .cloned()
         .collect();
+    if attachments {
+        orphans.extend(links::attachment_files(scan_path, &config.extensions())?.into_iter()
+            .filter(|file| !linked_targets.contains(file)));
+    }
 
-    orphans.sort_by(|a, b| a.cmp(b));
+    orphans.sort();
 
     if orphans.is_empty() {
         println!("No orphaned files found.");
@@ -145,92 +193,311 @@ fn find_orphans(root: &Path, scan_path: &Path) -> io::Result<()> {
     } else {
         eprintln!("Found {} orphaned file(s):", orphans.len());
         for orphan in &orphans {
-            let display = orphan.strip_prefix(root).unwrap_or(orphan);
-            eprintln!("  {}", display.display());
+            eprintln!("  {}", util::relative_path(root, orphan));
         }
         Err(io::Error::other(format!("Found {} orphaned file(s)", orphans.len())))
     }
 }
 
-/// Scan a directory for markdown files and extract all links
-fn scan_dir(scan_path: &Path) -> io::Result<ScanResult> {
-    let mut links = Vec::new();
-    let mut sources = Vec::new();
-
-    // Walk through directory looking for .md, .mx, .emx files
-    for entry in fs::read_dir(scan_path).map_err(|e| {
-        io::Error::new(io::ErrorKind::NotFound, format!("Failed to read directory '{}': {}", scan_path.display(), e))
-    })? {
-        let entry = entry?;
-        let entry_path = entry.path();
-        let ext = entry_path.extension().and_then(|s| s.to_str());
-
-        // Only process markdown files
-        if !ext.map(|e| e == "md" || e == "mx" || e == "emx").unwrap_or(false) {
-            continue;
+/// List the links pointing at a note, with the lines around each
+fn backlinks(ctx: &emx_note::ResolveContext, root: &Path, note_ref: &str) -> io::Result<()> {
+    let config = CapsaConfig::load(root)?;
+    let task_file = root.join(&config.task_file);
+    let note_path = emx_note::resolve_note_or_error(root, note_ref, &config.extensions())?;
+    let found = links::backlinks(root, &note_path)?;
+
+    // Several links on one line are one backlink
+    let mut seen = HashSet::new();
+    let found: Vec<&NoteLink> = found.iter()
+        .filter(|link| seen.insert((link.source.clone(), link.line)))
+        .collect();
+
+    let mut contents: HashMap<&Path, String> = HashMap::new();
+    for link in &found {
+        if !contents.contains_key(link.source.as_path()) {
+            contents.insert(&link.source, fs::read_to_string(&link.source)?);
         }
+    }
+    let snippet_of = |link: &NoteLink| snippet(&contents[link.source.as_path()], link.line);
+
+    if ctx.json {
+        let backlinks: Vec<_> = found.iter().map(|link| json!({
+            "source": util::relative_path(root, &link.source),
+            "line": link.line,
+            "via": via(root, &task_file, &link.source),
+            "kind": link.kind,
+            "target": link.target,
+            "snippet": snippet_of(link),
+        })).collect();
+        println!("{}", json!({
+            "note": util::relative_path(root, &note_path),
+            "backlinks": backlinks,
+        }));
+        return Ok(());
+    }
 
-        // Skip directories and hidden files
-        if entry_path.is_dir() || entry.file_name().to_string_lossy().starts_with('.') {
-            continue;
+    if found.is_empty() {
+        println!("No backlinks to {}.", util::relative_path(root, &note_path));
+        return Ok(());
+    }
+    for link in &found {
+        println!("{}:{}: ({}) {}", util::relative_path(root, &link.source), link.line, via(root, &task_file, &link.source), link.target);
+        for line in snippet_of(link).lines() {
+            println!("    {}", line);
         }
+    }
+
+    Ok(())
+}
+
+/// Propose candidates for broken links; with `apply` or `interactive`,
+/// rewrite the links with the best or the chosen candidate
+fn fix(
+    ctx: &emx_note::ResolveContext,
+    capsa: &CapsaEngine,
+    scan_path: &Path,
+    apply: bool,
+    interactive: bool,
+) -> io::Result<()> {
+    let root = capsa.path.as_path();
+    let result = scan_dir(root, scan_path)?;
+    let broken = link_fix::broken_links(root, &result.sources)?;
+
+    if broken.is_empty() {
+        if ctx.json {
+            println!("{}", json!({ "applied": false, "fixes": [], "unfixed": [], "files": [] }));
+        } else {
+            println!("No broken links found. OK.");
+        }
+        return Ok(());
+    }
 
-        sources.push(entry_path.clone());
+    let chosen: Vec<(&BrokenLink, &FixCandidate)> = if interactive {
+        choose(root, &broken)?
+    } else {
+        broken.iter().filter_map(|b| b.best().map(|c| (b, c))).collect()
+    };
+    let applied = apply || interactive;
+
+    // Rewrite each file once, with one edit op per changed line
+    let mut files = Vec::new();
+    if applied {
+        let backup = capsa.config()?.backup;
+        let mut by_source: BTreeMap<&Path, Vec<(&BrokenLink, &FixCandidate)>> = BTreeMap::new();
+        for &(b, c) in &chosen {
+            by_source.entry(b.source.as_path()).or_default().push((b, c));
+        }
+        for (source, fixes) in by_source {
+            let mut ops = Vec::new();
+            emx_note::update_file(source, backup, |file| {
+                ops = link_fix::fix_ops(file.content(), &fixes);
+                if ops.is_empty() {
+                    return Ok(None);
+                }
+                emx_note::apply_edits(file.content(), ops.clone())
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })?;
+            files.push((source, ops));
+        }
+    }
 
-        // Parse markdown content to extract links
-        let content = fs::read_to_string(&entry_path).map_err(|e| {
-            io::Error::new(io::ErrorKind::Other, format!("Failed to read '{}': {}", entry_path.display(), e))
-        })?;
+    let unfixed: Vec<&BrokenLink> = broken.iter()
+        .filter(|b| !chosen.iter().any(|(c, _)| std::ptr::eq(*c, *b)))
+        .collect();
 
-        let root_relative = entry_path.strip_prefix(scan_path).unwrap_or(&entry_path);
+    if ctx.json {
+        let fixes: Vec<_> = chosen.iter().map(|(b, c)| json!({
+            "source": util::relative_path(root, &b.source),
+            "line": b.line,
+            "old": b.target,
+            "new": c.replacement,
+            "path": util::relative_path(root, &c.path),
+            "reason": c.reason,
+        })).collect();
+        let unfixed: Vec<_> = unfixed.iter().map(|b| json!({
+            "source": util::relative_path(root, &b.source),
+            "line": b.line,
+            "target": b.target,
+            "candidates": b.candidates.iter().map(|c| json!({
+                "path": util::relative_path(root, &c.path),
+                "reason": c.reason,
+                "replacement": c.replacement,
+            })).collect::<Vec<_>>(),
+        })).collect();
+        let files: Vec<_> = files.iter().map(|(path, ops)| json!({
+            "path": util::relative_path(root, path),
+            "ops": ops,
+        })).collect();
+        println!("{}", json!({ "applied": applied, "fixes": fixes, "unfixed": unfixed, "files": files }));
+        return Ok(());
+    }
 
-        for link in extract_links(&content) {
-            links.push(LinkInfo {
-                source: root_relative.to_path_buf(),
-                line: link.line,
-                target: link.target.clone(),
-                broken: false, // Will be validated later
-            });
+    if !applied {
+        for b in &broken {
+            print_candidates(root, b);
         }
+        println!("{} of {} broken link(s) have a single best candidate (*); use --apply to fix them or -i to choose.",
+            chosen.len(), broken.len());
+        return Ok(());
     }
 
-    Ok(ScanResult { links, sources })
+    for (b, c) in &chosen {
+        println!("Fixed {}:{}: {} -> {}", util::relative_path(root, &b.source), b.line, b.target, c.replacement);
+    }
+    for b in &unfixed {
+        let why = match b.candidates.len() {
+            0 => "no candidates".to_string(),
+            n => format!("{} candidates", n),
+        };
+        eprintln!("Skipped {}:{}: {} ({})", util::relative_path(root, &b.source), b.line, b.target, why);
+    }
+    println!("Fixed {} link(s) in {} file(s).", chosen.len(), files.len());
+    Ok(())
 }
 
-/// Extract all local links from markdown content using pulldown-cmark
-fn extract_links(content: &str) -> Vec<LinkInfo> {
-    let mut links = Vec::new();
-    let mut current_line = 1;
-
-    let parser = Parser::new(content);
-
-    for event in parser {
-        match event {
-            // Track line numbers
-            Event::End(TagEnd::Paragraph)
-            | Event::End(TagEnd::Heading(_))
-            | Event::End(TagEnd::Item)
-            | Event::End(TagEnd::CodeBlock)
-            => {
-                current_line += 1;
-            }
-            Event::Start(Tag::Link { dest_url, .. }) => {
-                // Inline link [text](url)
-                let url = dest_url.to_string();
-
-                // Only include local file links
-                if !url.contains("://") && !url.starts_with("mailto:") && !url.starts_with('#') {
-                    links.push(LinkInfo {
-                        source: PathBuf::new(), // Will be set by caller
-                        line: current_line,
-                        target: url,
-                        broken: false,
-                    });
+/// Ask which candidate to use for each broken link
+fn choose<'a>(root: &Path, broken: &'a [BrokenLink]) -> io::Result<Vec<(&'a BrokenLink, &'a FixCandidate)>> {
+    let mut chosen = Vec::new();
+    for b in broken {
+        print_candidates(root, b);
+        if b.candidates.is_empty() {
+            continue;
+        }
+
+        print!("Replace with [1-{}], s to skip, q to quit: ", b.candidates.len());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            break;
+        }
+        match input.trim() {
+            "q" => break,
+            answer => {
+                if let Some(c) = answer.parse::<usize>().ok().and_then(|n| b.candidates.get(n.wrapping_sub(1))) {
+                    chosen.push((b, c));
                 }
             }
-            _ => {}
         }
     }
+    Ok(chosen)
+}
+
+/// A broken link and its numbered candidates; `*` marks the one `--apply` uses
+fn print_candidates(root: &Path, broken: &BrokenLink) {
+    println!("{}:{}: {}", util::relative_path(root, &broken.source), broken.line, broken.target);
+    if broken.candidates.is_empty() {
+        println!("    (no candidates)");
+    }
+    let best = broken.best();
+    for (i, c) in broken.candidates.iter().enumerate() {
+        let mark = if best.is_some_and(|b| std::ptr::eq(b, c)) { '*' } else { ' ' };
+        println!("  {}{}. {} ({}) -> {}", mark, i + 1, util::relative_path(root, &c.path), c.reason.describe(), c.replacement);
+    }
+}
+
+/// Print the note graph, optionally cut down to a tag and/or the
+/// neighbourhood of a note
+fn graph(
+    ctx: &emx_note::ResolveContext,
+    root: &Path,
+    format: Option<&str>,
+    tag: Option<&str>,
+    note_ref: Option<&str>,
+    depth: usize,
+) -> io::Result<()> {
+    let format = match format {
+        Some(f) => f.parse::<GraphFormat>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        None if ctx.json => GraphFormat::Json,
+        None => GraphFormat::default(),
+    };
+
+    let mut graph = LinkGraph::build(root)?;
+
+    if let Some(note_ref) = note_ref {
+        let note_path = emx_note::resolve_note_or_error(root, note_ref, &CapsaConfig::load(root)?.extensions())?;
+        let center = util::relative_path(root, &note_path);
+        if !graph.contains(&center) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' is not a note (tag indexes and TASK.md are not in the graph)", center),
+            ));
+        }
+        graph.retain_neighbourhood(&center, depth);
+    }
+
+    if let Some(tag) = tag {
+        let tag = tag.trim_start_matches('#');
+        if !graph.nodes.iter().any(|node| node.tags.iter().any(|t| t == tag)) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No note tagged '{}'", tag),
+            ));
+        }
+        graph.retain_tag(tag);
+    }
+
+    print!("{}", graph.render(format));
+    if format == GraphFormat::Json {
+        println!();
+    }
+    Ok(())
+}
+
+/// Non-blank lines around line `line` (1-based), each cut to
+/// `BACKLINK_SNIPPET_WIDTH` characters
+fn snippet(content: &str, line: usize) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let index = line.saturating_sub(1);
+    let start = index.saturating_sub(C::BACKLINK_CONTEXT_LINES);
+    let end = (index + C::BACKLINK_CONTEXT_LINES + 1).min(lines.len());
+
+    lines[start.min(end)..end].iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let l = l.trim_end();
+            match l.char_indices().nth(C::BACKLINK_SNIPPET_WIDTH) {
+                Some((cut, _)) => format!("{}…", &l[..cut]),
+                None => l.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// What kind of file a backlink comes from: a note, a tag index, the daily
+/// index or the task file
+fn via(root: &Path, task_file: &Path, source: &Path) -> &'static str {
+    let name = source.file_name().unwrap_or_default().to_string_lossy();
+    if source == task_file {
+        "task"
+    } else if source == root.join(C::NOTE_SUBDIR).join(C::DAILY_LINK_FILENAME) {
+        "daily"
+    } else if name.starts_with('#') && source.parent() == Some(root) {
+        "tag"
+    } else {
+        "note"
+    }
+}
+
+/// Scan a directory tree for markdown files and extract all local links
+fn scan_dir(root: &Path, scan_path: &Path) -> io::Result<ScanResult> {
+    if !scan_path.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Failed to read directory '{}'", scan_path.display()),
+        ));
+    }
+
+    let config = emx_note::CapsaConfig::load(root)?;
+    let sources = links::markdown_files(scan_path, &config.extensions())?;
+    let links = links::scan(root, &sources)?;
+    Ok(ScanResult { links, sources })
+}
 
-    links
+/// Tag indexes, the daily index and the task file
+fn is_index_file(task_file: &Path, path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.starts_with('#') || path == task_file
 }
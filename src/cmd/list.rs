@@ -17,20 +17,20 @@ pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, filter: Option<St
         } else {
             // Try as source hash in note/ (12 chars) or date in #daily/ (8 chars YYYYMMDD)
             // First, hash the input to get the directory name
-            use emx_note::{hash_source, abbreviate_hash};
-            let hash = abbreviate_hash(&hash_source(&f));
+            let config = emx_note::CapsaConfig::load(&capsa_ref.path)?;
+            let hash = config.abbreviate_hash(&emx_note::hash_source(&f));
             let note_hash_dir = capsa_ref.path.join("note").join(&hash);
             if note_hash_dir.is_dir() {
                 list_directory(&note_hash_dir)?;
             } else {
                 // Try as date in #daily/
-                let daily_dir = capsa_ref.path.join("#daily").join(&f);
+                let daily_dir = capsa_ref.path.join(&config.daily_dir).join(&f);
                 if daily_dir.is_dir() {
                     list_directory(&daily_dir)?;
                 } else {
                     return Err(io::Error::new(
                         io::ErrorKind::NotFound,
-                        format!("Not found: note/{}/ or #daily/{}", hash, f),
+                        format!("Not found: note/{}/ or {}/{}", hash, config.daily_dir, f),
                     ));
                 }
             }
@@ -75,9 +75,9 @@ pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, filter: Option<St
 fn list_tag(capsa_ref: &emx_note::CapsaRef, tag: &str, json: bool) -> io::Result<()> {
     let tag_name = tag.trim_start_matches('#');
 
-    // Special case: #daily lists only the subdirectory names (dates) in #daily/
+    // Special case: #daily lists only the subdirectory names (dates) of the daily directory
     if tag_name == "daily" {
-        let daily_dir = capsa_ref.path.join("#daily");
+        let daily_dir = capsa_ref.path.join(&emx_note::CapsaConfig::load(&capsa_ref.path)?.daily_dir);
         if !daily_dir.exists() {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -89,14 +89,18 @@ fn list_tag(capsa_ref: &emx_note::CapsaRef, tag: &str, json: bool) -> io::Result
 
     let tag_file = capsa_ref.path.join(format!("#{}.md", tag_name));
 
-    if !tag_file.exists() {
+    // Notes tagged in their frontmatter (or inline) that the tag file doesn't list yet
+    let config = emx_note::CapsaConfig::load(&capsa_ref.path)?;
+    let unindexed = emx_note::TagIndex::load(&capsa_ref.path, &config)?.unindexed(tag_name);
+
+    if !tag_file.exists() && unindexed.is_empty() {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
             format!("Tag '{}' not found", tag),
         ));
     }
 
-    let content = fs::read_to_string(&tag_file)?;
+    let content = if tag_file.exists() { fs::read_to_string(&tag_file)? } else { String::new() };
 
     // Strip YAML frontmatter if present
     let content = strip_yaml_frontmatter(&content);
@@ -149,6 +153,9 @@ fn list_tag(capsa_ref: &emx_note::CapsaRef, tag: &str, json: bool) -> io::Result
             _ => {}
         }
     }
+    for path in &unindexed {
+        grouped_links.entry("_uncategorized".to_string()).or_default().push(format_link_target(path));
+    }
 
     // Output
     if json {
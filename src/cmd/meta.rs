@@ -2,7 +2,7 @@
 
 use std::fs;
 use std::io;
-use emx_note::{EditOp, apply_edits};
+use emx_note::{EditOp, apply_edits, update_file};
 
 pub fn run(
     ctx: &emx_note::ResolveContext,
@@ -18,24 +18,29 @@ pub fn run(
     let note_path = emx_note::resolve_note_or_error(
         &capsa_ref.path,
         &note_ref,
-        emx_note::DEFAULT_EXTENSIONS
+        &emx_note::CapsaConfig::load(&capsa_ref.path)?.extensions()
     )?;
 
-    // Read note content
+    // Read note content (the capsa lock serializes other emx-note writers;
+    // the versioned write below also catches edits made outside emx-note)
+    let _lock = emx_note::CapsaLock::acquire(&capsa_ref.path)?;
+    let backup = emx_note::CapsaConfig::load(&capsa_ref.path)?.backup;
     let content = fs::read_to_string(&note_path)?;
 
     // Parse or modify frontmatter
     if delete {
         if let Some(k) = key {
-            let (old_fm, new_fm) = delete_key_edit(&content, &k)?;
-            let edits = if new_fm.is_empty() {
-                vec![EditOp::replace(&old_fm, "")]
-            } else {
-                vec![EditOp::replace(&old_fm, &new_fm)]
-            };
-            let updated = apply_edits(&content, edits)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            fs::write(&note_path, updated)?;
+            update_file(&note_path, backup, |file| {
+                let (old_fm, new_fm) = delete_key_edit(file.content(), &k)?;
+                let edits = if new_fm.is_empty() {
+                    vec![EditOp::replace(&old_fm, "")]
+                } else {
+                    vec![EditOp::replace(&old_fm, &new_fm)]
+                };
+                apply_edits(file.content(), edits)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            })?;
             eprintln!("Deleted key '{}'", k);
         } else {
             return Err(io::Error::new(
@@ -61,16 +66,18 @@ pub fn run(
             } else {
                 serde_yaml::Value::Sequence(value.into_iter().map(serde_yaml::Value::String).collect())
             };
-            let (old_fm, new_fm) = set_key_edit(&content, &k, yaml_value.clone())?;
-            let edits = if old_fm.is_empty() {
-                // No existing frontmatter - insert at start
-                vec![EditOp::insert_at_line(0, &new_fm)]
-            } else {
-                vec![EditOp::replace(&old_fm, &new_fm)]
-            };
-            let updated = apply_edits(&content, edits)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            fs::write(&note_path, updated)?;
+            update_file(&note_path, backup, |file| {
+                let (old_fm, new_fm) = set_key_edit(file.content(), &k, yaml_value.clone())?;
+                let edits = if old_fm.is_empty() {
+                    // No existing frontmatter - insert at start
+                    vec![EditOp::insert_at_line(0, &new_fm)]
+                } else {
+                    vec![EditOp::replace(&old_fm, &new_fm)]
+                };
+                apply_edits(file.content(), edits)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+            })?;
 
             // Output the value that was set (for confirmation)
             println!("{}", to_yaml_string(&yaml_value));
@@ -94,7 +101,10 @@ fn extract_frontmatter(content: &str) -> String {
         return String::new();
     }
 
+    // Skip the newline after the opening `---`, so that the block rebuilt as
+    // "---\n{frontmatter}\n---" matches the content
     let rest = &content[3..];
+    let rest = rest.strip_prefix('\n').unwrap_or(rest);
     if let Some(end_pos) = rest.find("\n---") {
         let frontmatter = &rest[..end_pos];
 
@@ -166,7 +176,7 @@ fn set_key_edit(content: &str, key: &str, value: serde_yaml::Value) -> io::Resul
         })?
     };
 
-    let updated = set_nested_value(yaml, key, value);
+    let updated = emx_note::frontmatter::set_nested(yaml, key, value);
     let new_frontmatter = serde_yaml::to_string(&updated).unwrap_or_default();
 
     // Build the new frontmatter block
@@ -217,62 +227,6 @@ fn delete_key_edit(content: &str, key: &str) -> io::Result<(String, String)> {
     Ok((old_block, new_block))
 }
 
-/// Set nested value in YAML
-fn set_nested_value(yaml: serde_yaml::Value, key: &str, value: serde_yaml::Value) -> serde_yaml::Value {
-    let parts: Vec<&str> = key.split('.').collect();
-
-    if parts.len() == 1 {
-        if let serde_yaml::Value::Mapping(mut map) = yaml {
-            map.insert(
-                serde_yaml::Value::String(parts[0].to_string()),
-                value
-            );
-            return serde_yaml::Value::Mapping(map);
-        }
-        return yaml;
-    }
-
-    // Nested path - need to insert into a mapping
-    let current_key = serde_yaml::Value::String(parts[0].to_string());
-    let rest_key = parts[1..].join(".");
-
-    let nested_value = if let serde_yaml::Value::Mapping(ref map) = yaml {
-        if let Some(existing) = map.get(&current_key) {
-            // If existing value is not a mapping, we need to replace it
-            if !matches!(existing, serde_yaml::Value::Mapping(_)) {
-                // Create fresh nested structure, discarding the non-mapping value
-                let new_map = serde_yaml::mapping::Mapping::new();
-                let empty = serde_yaml::Value::Mapping(new_map);
-                set_nested_value(empty, &rest_key, value)
-            } else {
-                // Recurse into existing mapping
-                set_nested_value(existing.clone(), &rest_key, value)
-            }
-        } else {
-            // Create new nested structure
-            let new_map = serde_yaml::mapping::Mapping::new();
-            let empty = serde_yaml::Value::Mapping(new_map);
-            set_nested_value(empty, &rest_key, value)
-        }
-    } else {
-        // yaml is not a mapping, create new nested structure from scratch
-        let new_map = serde_yaml::mapping::Mapping::new();
-        let empty = serde_yaml::Value::Mapping(new_map);
-        set_nested_value(empty, &rest_key, value)
-    };
-
-    // Insert into mapping, creating one if yaml isn't a mapping
-    if let serde_yaml::Value::Mapping(mut map) = yaml {
-        map.insert(current_key, nested_value);
-        serde_yaml::Value::Mapping(map)
-    } else {
-        // Convert non-mapping yaml to a new mapping with the nested value
-        let mut new_map = serde_yaml::mapping::Mapping::new();
-        new_map.insert(current_key, nested_value);
-        serde_yaml::Value::Mapping(new_map)
-    }
-}
-
 /// Delete nested value from YAML
 fn delete_nested_value(yaml: serde_yaml::Value, key: &str) -> serde_yaml::Value {
     let parts: Vec<&str> = key.split('.').collect();
@@ -0,0 +1,119 @@
+//! Move/rename note command module
+//!
+//! Moves a note and rewrites every link, tag index entry and task reference
+//! pointing at it. `--dry-run` prints the changes as a diff instead.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use serde_json::json;
+use emx_note::{CapsaConfig, CapsaEngine, util, relink};
+use emx_note::constants as C;
+
+pub fn run(
+    ctx: &emx_note::ResolveContext,
+    caps: Option<&str>,
+    note_ref: &str,
+    new_name: &str,
+    dry_run: bool,
+) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+
+    let config = capsa.config()?;
+    let from = emx_note::resolve_note_or_error(&capsa.path, note_ref, &config.extensions())?;
+    let to = target_path(&capsa, &from, new_name)?;
+    let title = Some(new_name.trim()).filter(|name| !is_path(&config, name));
+
+    if to == from {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Note is already at {}", util::display_path(&from)),
+        ));
+    }
+    if to.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Target already exists: {}", util::display_path(&to)),
+        ));
+    }
+
+    let changes = if dry_run {
+        relink::plan_move(&capsa.path, &from, &to, title)?
+    } else {
+        capsa.move_note(&from, &to, title)?
+    };
+
+    let relative = |path: &Path| {
+        path.strip_prefix(&capsa.path).unwrap_or(path).to_string_lossy().replace('\\', "/")
+    };
+
+    if ctx.json {
+        println!("{}", json!({
+            "from": relative(&from),
+            "to": relative(&to),
+            "updated": changes.iter().map(|c| relative(&c.path)).collect::<Vec<_>>(),
+            "dry_run": dry_run,
+        }));
+    } else if dry_run {
+        println!("rename from {}", relative(&from));
+        println!("rename to {}", relative(&to));
+        for change in &changes {
+            print!("{}", change.diff(&capsa.path));
+        }
+    } else {
+        println!("{}", util::display_path(&to));
+        let rewritten = changes.iter().filter(|c| c.path != to).count();
+        if rewritten > 0 {
+            eprintln!("Updated links in {} file(s)", rewritten);
+        }
+    }
+
+    Ok(())
+}
+
+/// Work out the new location from a title or a capsa-relative path
+///
+/// - A title (no `/`, no note extension) renames the note in place, keeping
+///   the `HHMMSS-` prefix of daily notes
+/// - A path is taken relative to the capsa root; a trailing `/` or an existing
+///   directory keeps the file name, a missing extension keeps the current one
+fn target_path(capsa: &CapsaEngine, from: &Path, new_name: &str) -> io::Result<PathBuf> {
+    let name = new_name.trim();
+    let config = capsa.config()?;
+    let ext = from.extension().map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| C::MARKDOWN_EXTENSION.trim_start_matches('.').to_string());
+
+    if is_path(&config, name) {
+        let mut path = util::secure_path(&capsa.path, name)?;
+        if name.ends_with('/') || name.ends_with('\\') || path.is_dir() {
+            path = path.join(from.file_name().unwrap_or_default());
+        } else if path.extension().is_none() {
+            path.set_extension(&ext);
+        }
+        return Ok(path);
+    }
+
+    let slug = config.slugify(name);
+    if slug.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid note name '{}'", new_name),
+        ));
+    }
+
+    // Daily notes keep their time prefix: 143022-standup.md → 143022-{slug}.md
+    let stem = from.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let time: String = stem.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let in_daily = from.starts_with(capsa.path.join(&config.daily_dir));
+    let new_stem = if in_daily && time.len() == 6 {
+        format!("{}-{}", time, slug)
+    } else {
+        slug
+    };
+
+    Ok(from.with_file_name(format!("{}.{}", new_stem, ext)))
+}
+
+/// Whether a new name is a capsa path rather than a title
+fn is_path(config: &CapsaConfig, name: &str) -> bool {
+    name.contains('/') || name.contains('\\') || config.extensions().iter().any(|e| name.ends_with(e))
+}
@@ -1,28 +1,204 @@
 //! Permanent note command module
 
+use std::fs;
 use std::io;
-use emx_note::{CapsaEngine, util, read_stdin_content};
+use serde::Deserialize;
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+use emx_note::{
+    constants as C, frontmatter, markdown, CapsaConfig, CapsaEngine, CapsaRef, ConflictPolicy, CreateArgs,
+    CreatedNote, Tags, Template, TemplateVars, util, read_stdin_content,
+};
 
 pub fn run(
     ctx: &emx_note::ResolveContext,
     caps: Option<&str>,
     title: Option<String>,
     source: Option<String>,
+    args: CreateArgs,
 ) -> io::Result<()> {
-    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+    let capsa_ref = super::resolve::resolve_capsa(ctx, caps)?;
 
-    // Read content from stdin (empty if no data)
-    let content = read_stdin_content()?;
-
-    // Create the permanent note
-    let note_path = capsa.create_permanent_note(
-        title.as_deref(),
+    // Read content from stdin (empty if no data), rendered with the template if any
+    let input = read_input(title, &args)?;
+    let config = CapsaConfig::load(&capsa_ref.path)?;
+    let vars = TemplateVars::now(
+        input.title.as_deref().unwrap_or(C::UNTITLED_NOTE_TITLE),
+        &capsa_ref.name,
         source.as_deref(),
-        &content,
-    )?;
+        ctx.agent_name.as_deref(),
+        &input.content,
+    );
+    let (content, template) = apply_template(&capsa_ref, args.template.clone(), &config.note_template, args.no_template, &vars)?;
+    let content = frontmatter::merge(&content, &input.fields)?;
+    let tags = indexed_tags(&config, &input.tags, &content);
+
+    let capsa = CapsaEngine::new(capsa_ref);
+    let policy = conflict_policy(&capsa, args.on_conflict.as_deref())?;
+
+    // Create the permanent note and index its tags
+    let created = capsa.create_tagged(&tags, |capsa| {
+        capsa.create_permanent_note_with(input.title.as_deref(), source.as_deref(), &content, policy)
+    })?;
+
+    report(ctx, &args, &created, template, &tags)
+}
+
+/// Conflict policy from `--on-conflict`, falling back to the capsa settings
+pub fn conflict_policy(capsa: &CapsaEngine, flag: Option<&str>) -> io::Result<ConflictPolicy> {
+    match flag {
+        Some(value) => value.parse()
+            .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e)),
+        None => Ok(capsa.config()?.on_conflict),
+    }
+}
+
+/// Render the stdin content with `--template`, or the capsa's default template
+/// for the note kind unless `--no-template`. Returns the content and the name
+/// of the template used.
+pub fn apply_template(
+    capsa_ref: &CapsaRef,
+    flag: Option<String>,
+    default: &str,
+    no_template: bool,
+    vars: &TemplateVars,
+) -> io::Result<(String, Option<String>)> {
+    let name = match flag {
+        Some(name) => name,
+        None if no_template || default.is_empty() => return Ok((vars.content.clone(), None)),
+        None => default.to_string(),
+    };
+    let template = Template::load(&capsa_ref.path, &name)?;
+    Ok((template.render(vars), Some(template.name)))
+}
+
+/// Title, content, tags and frontmatter fields of a new note
+pub struct NoteInput {
+    pub title: Option<String>,
+    pub content: String,
+    /// Tags without `#`, for the frontmatter and the tag indexes
+    pub tags: Vec<String>,
+    /// Frontmatter fields from `--meta`, the envelope and the tags
+    pub fields: Mapping,
+}
+
+/// JSON envelope read from stdin with `--envelope`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Envelope {
+    title: Option<String>,
+    tags: Vec<String>,
+    meta: serde_json::Map<String, serde_json::Value>,
+    content: String,
+}
+
+/// Read the input of a new note: stdin content and the `--tag`/`--meta`
+/// options, or a JSON envelope on stdin with `--envelope`
+pub fn read_input(title: Option<String>, args: &CreateArgs) -> io::Result<NoteInput> {
+    let stdin = read_stdin_content()?;
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    let mut fields = Value::Mapping(Mapping::new());
+    let (title, content, mut tags) = if args.envelope {
+        let envelope: Envelope = serde_json::from_str(&stdin)
+            .map_err(|e| invalid(format!("Invalid envelope: {}", e)))?;
+        if title.is_some() && envelope.title.is_some() {
+            return Err(invalid("Title given both as argument and in the envelope".to_string()));
+        }
+        for (key, value) in envelope.meta {
+            let value = serde_yaml::to_value(value).map_err(|e| invalid(e.to_string()))?;
+            fields = frontmatter::set_nested(fields, &key, value);
+        }
+        (title.or(envelope.title), envelope.content, envelope.tags)
+    } else {
+        (title, stdin, Vec::new())
+    };
+    tags.extend(args.tags.iter().cloned());
+
+    // A repeated --meta key makes a list, as several values do in `meta`
+    let mut meta: Vec<(&str, Vec<String>)> = Vec::new();
+    for item in &args.meta {
+        let (key, value) = item.split_once('=')
+            .filter(|(key, _)| !key.trim().is_empty())
+            .ok_or_else(|| invalid(format!("Invalid --meta '{}' (expected KEY=VALUE)", item)))?;
+        match meta.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value.to_string()),
+            None => meta.push((key, vec![value.to_string()])),
+        }
+    }
+    for (key, mut values) in meta {
+        let value = if values.len() == 1 {
+            Value::String(values.remove(0))
+        } else {
+            Value::Sequence(values.into_iter().map(Value::String).collect())
+        };
+        fields = frontmatter::set_nested(fields, key, value);
+    }
+
+    let mut fields = match fields {
+        Value::Mapping(fields) => fields,
+        _ => Mapping::new(),
+    };
 
-    // Output full path for shell pipeline compatibility
-    println!("{}", util::display_path(&note_path));
+    // Tags set as a frontmatter field are indexed too
+    if let Some(value) = fields.remove(C::TAGS_KEY) {
+        tags.splice(0..0, frontmatter::tag_values(&value));
+    }
+    let mut unique: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').to_string();
+        Tags::check_name(&tag)?;
+        if !unique.contains(&tag) {
+            unique.push(tag);
+        }
+    }
+    if !unique.is_empty() {
+        fields.insert(
+            Value::from(C::TAGS_KEY),
+            Value::Sequence(unique.iter().cloned().map(Value::String).collect()),
+        );
+    }
 
+    Ok(NoteInput { title, content, tags: unique, fields })
+}
+
+/// Tags to index for a new note: the given ones, then with the
+/// `inline_tags` setting the hashtags of its content
+pub fn indexed_tags(config: &CapsaConfig, tags: &[String], content: &str) -> Vec<String> {
+    let mut tags = tags.to_vec();
+    if config.inline_tags {
+        for tag in markdown::inline_tags(content) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// Print the created note's path, or with `--json`/`--envelope` the result
+/// with the tags indexed and the note's frontmatter
+pub fn report(
+    ctx: &emx_note::ResolveContext,
+    args: &CreateArgs,
+    created: &CreatedNote,
+    template: Option<String>,
+    tags: &[String],
+) -> io::Result<()> {
+    if ctx.json || args.envelope {
+        let meta = frontmatter::parse(&fs::read_to_string(&created.path)?)
+            .and_then(|yaml| serde_json::to_value(yaml).ok())
+            .unwrap_or(serde_json::Value::Null);
+        println!("{}", json!({
+            "path": util::display_path(&created.path),
+            "action": created.action,
+            "template": template,
+            "tags": tags,
+            "meta": meta,
+        }));
+    } else {
+        // Output full path for shell pipeline compatibility
+        println!("{}", util::display_path(&created.path));
+    }
     Ok(())
 }
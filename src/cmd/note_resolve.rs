@@ -6,7 +6,11 @@ pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, note_name: String
     let capsa_ref = super::resolve::resolve_capsa(ctx, caps)?;
 
     // Resolve note using resolver
-    let resolved = emx_note::resolve_note(&capsa_ref.path, &note_name, emx_note::DEFAULT_EXTENSIONS)?;
+    let resolved = emx_note::resolve_note(
+        &capsa_ref.path,
+        &note_name,
+        &emx_note::CapsaConfig::load(&capsa_ref.path)?.extensions(),
+    )?;
 
     match resolved {
         emx_note::ResolvedNote::Found(path) => {
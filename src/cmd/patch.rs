@@ -0,0 +1,129 @@
+//! Patch note command module
+//!
+//! Applies a unified diff read from stdin to a resolved note. All hunks must
+//! apply or the note is left unchanged; `--dry-run` only reports where each
+//! hunk would go.
+
+use std::io::{self, Read};
+use serde_json::json;
+use emx_note::{CapsaEngine, Patch, PatchError, AppliedHunk, util};
+
+pub fn run(
+    ctx: &emx_note::ResolveContext,
+    caps: Option<&str>,
+    note_ref: &str,
+    fuzz: usize,
+    dry_run: bool,
+) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+    let note_path = emx_note::resolve_note_or_error(&capsa.path, note_ref, &capsa.config()?.extensions())?;
+
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    // A parse or apply error is kept for the report
+    let mut failed: Option<PatchError> = None;
+    let mut applied: Vec<AppliedHunk> = Vec::new();
+    let mut before = String::new();
+    let result = match Patch::parse(&input) {
+        Err(e) => {
+            let err = io::Error::new(io::ErrorKind::InvalidInput, e.to_string());
+            failed = Some(e);
+            Err(err)
+        }
+        Ok(patch) => {
+            let mut apply = |content: &str| match patch.apply(content, fuzz) {
+                Ok(result) => {
+                    applied = result.hunks;
+                    Ok(result.content)
+                }
+                Err(e) => {
+                    let err = io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+                    failed = Some(e);
+                    Err(err)
+                }
+            };
+
+            if dry_run {
+                std::fs::read_to_string(&note_path).and_then(|content| apply(&content)).map(|_| ())
+            } else {
+                let backup = capsa.config()?.backup;
+                emx_note::update_file(&note_path, backup, |file| {
+                    before = file.content().to_string();
+                    apply(file.content()).map(Some)
+                })
+                .map(|_| ())
+            }
+        }
+    };
+
+    if let Err(e) = result {
+        if let Some(error) = &failed {
+            if ctx.json {
+                println!("{}", json!({
+                    "path": util::display_path(&note_path),
+                    "applied": false,
+                    "dry_run": dry_run,
+                    "error": error,
+                }));
+            } else if let PatchError::Rejected { hunks } = error {
+                for hunk in hunks {
+                    eprintln!("Hunk #{} FAILED at {}, expected:", hunk.hunk, hunk.old_start);
+                    for line in &hunk.expected {
+                        eprintln!("  {}", line);
+                    }
+                }
+            }
+        }
+        return Err(e);
+    }
+
+    let edited = if dry_run {
+        Default::default()
+    } else {
+        capsa.finish_edit(&note_path, &before)?
+    };
+
+    if ctx.json {
+        println!("{}", json!({
+            "path": util::display_path(&note_path),
+            "applied": true,
+            "dry_run": dry_run,
+            "hunks": applied,
+            "changed": edited.changed,
+            "tags_added": edited.tags_added,
+            "tags_removed": edited.tags_removed,
+        }));
+        return Ok(());
+    }
+
+    if dry_run {
+        for hunk in &applied {
+            println!("{}", describe(hunk));
+        }
+    } else {
+        println!("{}", util::display_path(&note_path));
+        for hunk in applied.iter().filter(|h| h.offset != 0 || h.fuzz > 0) {
+            eprintln!("{}", describe(hunk));
+        }
+    }
+
+    Ok(())
+}
+
+/// "Hunk #2 applies at line 9 (offset 3 lines, fuzz 1)"
+fn describe(hunk: &AppliedHunk) -> String {
+    let mut notes = Vec::new();
+    if hunk.offset != 0 {
+        notes.push(format!("offset {} line{}", hunk.offset, if hunk.offset.abs() == 1 { "" } else { "s" }));
+    }
+    if hunk.fuzz > 0 {
+        notes.push(format!("fuzz {}", hunk.fuzz));
+    }
+
+    let mut text = format!("Hunk #{} applies at line {}", hunk.hunk, hunk.line);
+    if !notes.is_empty() {
+        text.push_str(&format!(" ({})", notes.join(", ")));
+    }
+    text
+}
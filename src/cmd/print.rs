@@ -10,7 +10,7 @@ pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, note_name: String
     let note_path = emx_note::resolve_note_or_error(
         &capsa_ref.path,
         &note_name,
-        emx_note::DEFAULT_EXTENSIONS
+        &emx_note::CapsaConfig::load(&capsa_ref.path)?.extensions()
     )?;
 
     // Read and print note content
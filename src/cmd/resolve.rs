@@ -31,7 +31,7 @@ fn create_default_capsa(ctx: &emx_note::ResolveContext, name: &str) -> io::Resul
 
     // Create the directory and subdirectories
     fs::create_dir_all(&capsa_path)?;
-    fs::create_dir_all(capsa_path.join("#daily"))?;
+    fs::create_dir_all(capsa_path.join(&emx_note::CapsaConfig::load(&capsa_path)?.daily_dir))?;
 
     // Display the capsa name (not the internal hierarchical name)
     let display_name = if name.ends_with("/.") {
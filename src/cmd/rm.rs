@@ -0,0 +1,43 @@
+//! Delete note command module
+//!
+//! Moves the note to the capsa trash and removes it from tag indexes and
+//! `note/#daily.md`. Notes referenced by tasks are only deleted with `--force`.
+
+use std::io;
+use std::path::Path;
+use serde_json::json;
+use emx_note::{CapsaEngine, util};
+
+pub fn run(
+    ctx: &emx_note::ResolveContext,
+    caps: Option<&str>,
+    note_ref: &str,
+    force: bool,
+) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+
+    let note_path = emx_note::resolve_note_or_error(&capsa.path, note_ref, &capsa.config()?.extensions())?;
+    let deleted = capsa.delete_note(&note_path, force)?;
+
+    let relative = |path: &Path| {
+        path.strip_prefix(&capsa.path).unwrap_or(path).to_string_lossy().replace('\\', "/")
+    };
+
+    if ctx.json {
+        println!("{}", json!({
+            "path": relative(&deleted.path),
+            "id": deleted.id,
+            "trash": relative(&deleted.trash_path),
+            "tags": deleted.tags,
+            "tasks": deleted.tasks,
+        }));
+    } else {
+        println!("{}", util::display_path(&deleted.trash_path));
+        if !deleted.tasks.is_empty() {
+            eprintln!("Warning: still referenced by {}", deleted.tasks.join(", "));
+        }
+        eprintln!("Restore with: emx-note trash restore {}", deleted.id);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Full-text search command module
+//!
+//! Searches the capsa's persistent inverted index (`.index/search.json`),
+//! updating it incrementally before each query.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use serde_json::json;
+use emx_note::{CapsaEngine, NoteArea, Query, SearchArgs, SearchFilter, SearchIndex, TagIndex};
+use emx_note::search::parse_date_filter;
+
+pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, args: &SearchArgs) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+
+    let query = Query::parse(&args.query);
+    if query.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Search query contains no searchable words",
+        ));
+    }
+
+    let filter = build_filter(&capsa, args)?;
+
+    if args.rebuild {
+        let index_file = SearchIndex::file(&capsa.path);
+        if index_file.exists() {
+            fs::remove_file(&index_file)?;
+        }
+    }
+    let index = SearchIndex::open(&capsa.path)?;
+
+    let hits = index.search(&query, &filter, args.limit);
+
+    if ctx.json {
+        println!("{}", json!(hits));
+    } else {
+        for hit in &hits {
+            println!("{:>7.3}  {}  {}", hit.score, hit.path, hit.title);
+        }
+    }
+
+    Ok(())
+}
+
+/// Build result filters from command-line options
+fn build_filter(capsa: &CapsaEngine, args: &SearchArgs) -> io::Result<SearchFilter> {
+    let area = args.area.as_deref()
+        .map(|a| a.parse::<NoteArea>())
+        .transpose()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    // Members of a tag: listed in its tag file, or tagged in their
+    // frontmatter (or inline) but not indexed yet
+    let paths = match args.tag {
+        Some(ref tag_name) => {
+            let members: HashSet<String> = TagIndex::load(&capsa.path, &capsa.config()?)?
+                .notes(tag_name.trim_start_matches('#'))
+                .into_iter()
+                .collect();
+            if members.is_empty() && !capsa.tags().get(tag_name).file().exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Tag '{}' not found", tag_name),
+                ));
+            }
+            Some(members)
+        }
+        None => None,
+    };
+
+    Ok(SearchFilter {
+        area,
+        paths,
+        from: date_option(args.from.as_deref(), "--from")?,
+        to: date_option(args.to.as_deref(), "--to")?,
+    })
+}
+
+/// Parse an optional date filter, rejecting malformed dates
+fn date_option(value: Option<&str>, flag: &str) -> io::Result<Option<String>> {
+    match value {
+        Some(v) => parse_date_filter(v).map(Some).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid date for {}: '{}' (expected YYYY-MM-DD or YYYYMMDD)", flag, v),
+            )
+        }),
+        None => Ok(None),
+    }
+}
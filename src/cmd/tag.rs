@@ -1,10 +1,15 @@
 //! Tag management module
 //!
-//! Tags are stored as #xxxx.md files in the capsa root directory.
-//! Notes added to tags are grouped by date.
+//! A note's tags are the `tags` of its frontmatter. They are indexed in
+//! #xxxx.md files in the capsa root directory, where notes added to tags are
+//! grouped by date; `tag sync` and `tag rebuild` bring those back in line
+//! with the frontmatter. Entries of notes not tagged in their frontmatter
+//! (e.g. tagged before tags were kept there) are only removed with
+//! `--prune`; `--from-index` copies them into the frontmatter instead.
 
 use std::io;
-use emx_note::{CapsaEngine, TagCommand};
+use serde_json::json;
+use emx_note::{util, CapsaEngine, DriftKind, SyncOptions, TagCommand};
 
 pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, cmd: TagCommand) -> io::Result<()> {
     match cmd {
@@ -14,6 +19,12 @@ pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, cmd: TagCommand)
         TagCommand::Remove { note_ref, tags, force } => {
             remove_tags(ctx, caps, &note_ref, &tags, force)
         }
+        TagCommand::Sync { dry_run, prune, from_index } => {
+            sync(ctx, caps, false, SyncOptions { apply: !dry_run, prune, from_index })
+        }
+        TagCommand::Rebuild { dry_run, prune, from_index } => {
+            sync(ctx, caps, true, SyncOptions { apply: !dry_run, prune, from_index })
+        }
     }
 }
 
@@ -30,10 +41,10 @@ fn add_tags(
     // Resolve note reference with force support
     let note_paths = capsa.resolve_note(note_ref, force)?;
 
-    // Add each tag to each note
+    // Add each tag to each note's frontmatter and to the tag indexes
     for note_path in &note_paths {
-        for tag_name in tags {
-            capsa.tags().get(tag_name).add_note(note_path)?;
+        for tag_file in capsa.tag_note(note_path, tags)? {
+            println!("{}", util::display_path(&tag_file));
         }
     }
 
@@ -53,17 +64,79 @@ fn remove_tags(
     // Resolve note reference with force support
     let note_paths = capsa.resolve_note(note_ref, force)?;
 
-    // Remove each tag from each note
+    // Remove each tag from each note's frontmatter and from the tag indexes
     for note_path in &note_paths {
-        let relative = note_path.strip_prefix(&capsa.path)
-            .unwrap_or(note_path)
-            .to_string_lossy()
-            .replace('\\', "/");
+        for tag_file in capsa.untag_note(note_path, tags)? {
+            println!("{}", util::display_path(&tag_file));
+        }
+    }
+
+    Ok(())
+}
 
-        for tag_name in tags {
-            capsa.tags().get(tag_name).remove_note(&relative)?;
+/// Report the differences between frontmatter tags and tag files, and fix
+/// them (only the entries that differ, or every tag file with `rebuild`)
+fn sync(ctx: &emx_note::ResolveContext, caps: Option<&str>, rebuild: bool, options: SyncOptions) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+    let result = if rebuild {
+        capsa.rebuild_tags(options)?
+    } else {
+        capsa.sync_tags(options)?
+    };
+    let dry_run = !options.apply;
+    let kept = result.drift.iter().filter(|d| d.kind == DriftKind::Stale).count();
+
+    if ctx.json {
+        let notes: Vec<String> = result.notes.iter().map(|f| util::display_path(f)).collect();
+        let files: Vec<String> = result.files.iter().map(|f| util::display_path(f)).collect();
+        println!("{}", json!({
+            "drift": result.drift,
+            "backfilled": result.backfilled,
+            "notes": notes,
+            "files": files,
+            "applied": options.apply,
+        }));
+    } else {
+        for item in &result.backfilled {
+            eprintln!("  #{}: {} tagged from the tag file", item.tag, item.path);
+        }
+        if !result.drift.is_empty() {
+            eprintln!("Found {} difference(s) between frontmatter tags and tag files:", result.drift.len());
+            for drift in &result.drift {
+                let problem = match drift.kind {
+                    DriftKind::Missing => "missing (tagged in frontmatter)",
+                    DriftKind::Stale => "stale (not tagged in frontmatter)",
+                };
+                eprintln!("  #{}: {} {}", drift.tag, drift.path, problem);
+            }
+        }
+        if kept > 0 && !options.prune {
+            if options.from_index {
+                eprintln!("Kept {} stale entry(ies) of deleted notes: remove them with --prune", kept);
+            } else {
+                eprintln!("Kept {} stale entry(ies): tag the notes with --from-index, or remove the entries with --prune", kept);
+            }
+        }
+        for file in result.notes.iter().chain(&result.files) {
+            println!("{}", util::display_path(file));
+        }
+        if result.drift.is_empty() && result.notes.is_empty() && result.files.is_empty() {
+            println!("Tag files match frontmatter tags. OK.");
         }
     }
 
+    // A dry run fails when there is something to fix, like `link check`
+    let pending = if rebuild {
+        result.notes.len() + result.files.len()
+    } else {
+        result.backfilled.len() + result.drift.len()
+    };
+    if dry_run && pending > 0 {
+        return Err(io::Error::other(if rebuild {
+            format!("{} file(s) would change", pending)
+        } else {
+            format!("Found {} difference(s) between frontmatter tags and tag files", pending)
+        }));
+    }
     Ok(())
 }
@@ -8,30 +8,15 @@ use super::TaskFileReader;
 /// Add a new task
 pub fn run(capsa: &CapsaEngine, node_ref: &str) -> io::Result<()> {
     // Validate that node_ref resolves to an existing note
-    let extensions = ["md", "txt"];
-    note_resolver::resolve_note_or_error(&capsa.path, node_ref, &extensions)?;
+    let config = capsa.config()?;
+    note_resolver::resolve_note_or_error(&capsa.path, node_ref, &config.extensions())?;
 
     let task_file = capsa.task_file();
-    let path = task_file.file();
-
-    let content = if path.exists() {
-        task_file.load()?
-    } else {
-        // Default empty TASK.md content
-        r#"---
-PREFIX: TASK-
----
-
----
-
-"#.to_string()
-    };
-
-    let reader = if path.exists() {
-        TaskFileReader::load(&path)?
-    } else {
-        TaskFileReader::new(content.clone())
-    };
+    let _lock = capsa.lock()?;
+
+    // Default TASK.md content (with the task_prefix setting) if missing
+    let content = task_file.load()?;
+    let reader = TaskFileReader::with_default_prefix(content.clone(), &config.task_prefix);
 
     // Check if node_ref already exists
     if let Some(existing_id) = reader.find_by_node_ref(node_ref) {
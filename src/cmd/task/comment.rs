@@ -14,21 +14,6 @@ pub fn run(
     dry_run: bool,
 ) -> io::Result<()> {
     let task_file = capsa.task_file();
-    let content = task_file.load()?;
-    let reader = TaskFileReader::new(content.clone());
-
-    // Find task
-    let task = reader.get_task(task_id).ok_or_else(|| {
-        io::Error::new(io::ErrorKind::NotFound, format!("Task '{}' not found", task_id))
-    })?;
-
-    // Check task is in body (taken)
-    if task.status == TaskStatus::Backlog {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            format!("Task '{}' not in body section\nHint: Use 'task take {}' first", task_id, task_id)
-        ));
-    }
 
     // Format comment
     let timestamp = task_file.get_timestamp();
@@ -39,6 +24,7 @@ pub fn run(
     };
 
     if dry_run {
+        check_task_taken(&TaskFileReader::new(task_file.load()?), task_id)?;
         println!("--- TASK.md (append) ---");
         println!("{}", comment);
         println!("---");
@@ -46,30 +32,59 @@ pub fn run(
         return Ok(());
     }
 
+    task_file.update(|content| add_comment(content, task_id, &comment))?;
+
+    // Show log after adding comment
+    log::run(capsa, task_id)
+}
+
+/// Fail unless the task exists and is in the body (taken)
+fn check_task_taken(reader: &TaskFileReader, task_id: &str) -> io::Result<()> {
+    // Find task
+    let task = reader.get_task(task_id).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("Task '{}' not found", task_id))
+    })?;
+
+    // Check task is in body (taken)
+    if task.status == TaskStatus::Backlog {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Task '{}' not in body section\nHint: Use 'task take {}' first", task_id, task_id)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Compute the new TASK.md content with the comment appended to the task
+fn add_comment(content: &str, task_id: &str, comment: &str) -> io::Result<Option<String>> {
+    let reader = TaskFileReader::new(content.to_string());
+    check_task_taken(&reader, task_id)?;
+
     // Find task line and append comment
-    if let Some((task_line_num, _task_line)) = reader.find_task_entry_line(task_id) {
-        // Find where to insert the comment (after task line and any existing comments)
-        let lines: Vec<&str> = content.lines().collect();
-        let mut insert_at = task_line_num + 1;
+    let (task_line_num, _task_line) = match reader.find_task_entry_line(task_id) {
+        Some(found) => found,
+        None => return Ok(None),
+    };
 
-        for (i, line) in lines.iter().enumerate().skip(task_line_num + 1) {
-            if line.trim().starts_with("  - ") {
-                insert_at = i + 1;
-            } else if line.trim().starts_with("- [") {
-                // Next task starts
-                break;
-            } else if !line.trim().is_empty() && !line.trim().starts_with("  ") {
-                // Non-comment content
-                break;
-            }
-        }
+    // Find where to insert the comment (after task line and any existing comments)
+    let lines: Vec<&str> = content.lines().collect();
+    let mut insert_at = task_line_num + 1;
 
-        let edits = vec![EditOp::insert_at_line(insert_at, comment)];
-        let new_content = apply_edits(&content, edits)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-        task_file.save(&new_content)?;
+    for (i, line) in lines.iter().enumerate().skip(task_line_num + 1) {
+        if line.trim().starts_with("  - ") {
+            insert_at = i + 1;
+        } else if line.trim().starts_with("- [") {
+            // Next task starts
+            break;
+        } else if !line.trim().is_empty() && !line.trim().starts_with("  ") {
+            // Non-comment content
+            break;
+        }
     }
 
-    // Show log after adding comment
-    log::run(capsa, task_id)
+    let edits = vec![EditOp::insert_at_line(insert_at, comment)];
+    let new_content = apply_edits(content, edits)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    Ok(Some(new_content))
 }
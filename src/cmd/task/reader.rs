@@ -42,6 +42,14 @@ impl TaskFileReader {
         Ok(TaskFileReader { content, prefix })
     }
 
+    /// Create reader with given content, using the `PREFIX` of its frontmatter
+    /// or else `default_prefix`
+    pub fn with_default_prefix(content: String, default_prefix: &str) -> Self {
+        let prefix = extract_frontmatter_prefix(&content)
+            .unwrap_or_else(|| default_prefix.to_string());
+        TaskFileReader { content, prefix }
+    }
+
     /// Create reader with given content and default prefix
     pub fn new(content: String) -> Self {
         TaskFileReader {
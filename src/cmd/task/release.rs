@@ -29,10 +29,10 @@ pub fn run(
         ));
     }
 
-    let content = task_file.load()?;
-    let reader = TaskFileReader::new(content.clone());
+    let timestamp = task_file.get_timestamp();
 
     if dry_run {
+        let reader = TaskFileReader::new(task_file.load()?);
         println!("--- TASK.md changes ---");
         for task_id in task_ids {
             let task = reader.get_task(task_id).ok_or_else(|| {
@@ -48,7 +48,32 @@ pub fn run(
         return Ok(());
     }
 
-    let mut current_content = content;
+    task_file.update(|content| {
+        release_tasks(content, task_ids, done, force, agent_marker.as_deref(), &timestamp)
+    })?;
+
+    // Show log after marking done for single task
+    if done && task_ids.len() == 1 {
+        return log::run(capsa, &task_ids[0]);
+    }
+
+    for task_id in task_ids {
+        println!("{}", task_id);
+    }
+    Ok(())
+}
+
+/// Compute the new TASK.md content after releasing tasks (None if unchanged)
+fn release_tasks(
+    content: &str,
+    task_ids: &[String],
+    done: bool,
+    force: bool,
+    agent_marker: Option<&str>,
+    timestamp: &str,
+) -> io::Result<Option<String>> {
+    let reader = TaskFileReader::new(content.to_string());
+    let mut current_content = content.to_string();
     let mut released_count = 0;
 
     for task_id in task_ids {
@@ -100,7 +125,6 @@ pub fn run(
 
             // Add completion comment if --done and no agent
             if done && agent_marker.is_none() {
-                let timestamp = task_file.get_timestamp();
                 let comment = format!("  - {} Completed by @anonymous", timestamp);
                 // Find insert point after the task line
                 let lines: Vec<&str> = current_content.lines().collect();
@@ -141,7 +165,6 @@ pub fn run(
 
             // Add completion comment if no agent
             if agent_marker.is_none() {
-                let timestamp = task_file.get_timestamp();
                 let comment = format!("  - {} Completed by @anonymous", timestamp);
                 edits.push(EditOp::insert_at_line(insert_point + lines_inserted + 1, comment));
             }
@@ -152,17 +175,8 @@ pub fn run(
         }
     }
 
-    if released_count > 0 {
-        task_file.save(&current_content)?;
-    }
-
-    // Show log after marking done for single task
-    if done && task_ids.len() == 1 {
-        return log::run(capsa, &task_ids[0]);
-    }
-
-    for task_id in task_ids {
-        println!("{}", task_id);
+    if released_count == 0 {
+        return Ok(None);
     }
-    Ok(())
+    Ok(Some(current_content))
 }
@@ -13,17 +13,42 @@ pub fn run(
     dry_run: bool,
 ) -> io::Result<()> {
     let task_file = capsa.task_file();
-    let content = task_file.load()?;
-    let reader = TaskFileReader::new(content.clone());
 
+    // Get agent name first (needed for ownership check)
+    let agent_marker = task_file.get_agent_name();
+
+    if dry_run {
+        let reader = TaskFileReader::new(task_file.load()?);
+        let task_entry = new_task_entry(&reader, task_id, title, agent_marker.as_deref())?;
+        println!("--- TASK.md (new entry) ---");
+        println!("{}", task_entry);
+        println!("---");
+        if let Some(ref h) = header {
+            println!("Would insert under header: {}", h);
+        } else {
+            println!("Would insert before first header or reference section");
+        }
+        return Ok(());
+    }
+
+    task_file.update(|content| take_task(content, task_id, title, header, agent_marker.as_deref()))?;
+
+    println!("{}", task_id);
+    Ok(())
+}
+
+/// Build the body entry for a task, failing if it is missing or owned by someone
+fn new_task_entry(
+    reader: &TaskFileReader,
+    task_id: &str,
+    title: Option<&str>,
+    agent_marker: Option<&str>,
+) -> io::Result<String> {
     // Find task in references
     let task = reader.get_task(task_id).ok_or_else(|| {
         io::Error::new(io::ErrorKind::NotFound, format!("Task '{}' not found", task_id))
     })?;
 
-    // Get agent name first (needed for ownership check)
-    let agent_marker = task_file.get_agent_name();
-
     // Check if already taken (only when agent name is set)
     if agent_marker.is_some() {
         if let Some(ref owner) = task.owner {
@@ -42,29 +67,29 @@ pub fn run(
 
     // Create task entry
     let checkbox = if task.status == TaskStatus::Done { "[x]" } else { "[ ]" };
-    let owner_str = agent_marker.as_ref()
+    let owner_str = agent_marker
         .map(|a| format!(" {}", a))
         .unwrap_or_default();
-    let task_entry = format!("- {} [{}][{}]{}", checkbox, task_title, task_id, owner_str);
+    Ok(format!("- {} [{}][{}]{}", checkbox, task_title, task_id, owner_str))
+}
 
-    if dry_run {
-        println!("--- TASK.md (new entry) ---");
-        println!("{}", task_entry);
-        println!("---");
-        if let Some(ref h) = header {
-            println!("Would insert under header: {}", h);
-        } else {
-            println!("Would insert before first header or reference section");
-        }
-        return Ok(());
-    }
+/// Compute the new TASK.md content after taking a task (None if unchanged)
+fn take_task(
+    content: &str,
+    task_id: &str,
+    title: Option<&str>,
+    header: Option<&str>,
+    agent_marker: Option<&str>,
+) -> io::Result<Option<String>> {
+    let reader = TaskFileReader::new(content.to_string());
+    let task_entry = new_task_entry(&reader, task_id, title, agent_marker)?;
 
     // Check if task already in body
     if let Some((_line_num, existing_line)) = reader.find_task_entry_line(task_id) {
         // Update existing entry based on agent_marker
         let updated_line = if let Some(at_pos) = existing_line.find('@') {
             // Has owner marker
-            if let Some(ref a) = agent_marker {
+            if let Some(a) = agent_marker {
                 // Replace with new owner
                 format!("{} {}", existing_line[..at_pos].trim_end(), a)
             } else {
@@ -73,7 +98,7 @@ pub fn run(
             }
         } else {
             // No owner marker
-            if let Some(ref a) = agent_marker {
+            if let Some(a) = agent_marker {
                 // Add owner
                 format!("{} {}", existing_line.trim_end(), a)
             } else {
@@ -82,41 +107,40 @@ pub fn run(
             }
         };
 
-        if updated_line != existing_line {
-            let edits = vec![EditOp::replace(&existing_line, &updated_line)];
-            let new_content = apply_edits(&content, edits)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            task_file.save(&new_content)?;
+        if updated_line == existing_line {
+            return Ok(None);
         }
-    } else {
-        // Insert new task entry
-        let (insert_point, needs_header) = reader.find_body_insert_point(header)
-            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
-
-        let mut edits = Vec::new();
-
-        let lines_inserted = if needs_header {
-            let header_text = match header {
-                Some(h) if h.starts_with("##") => format!("{}\n\n", h),
-                Some(h) => format!("## {}\n\n", h),
-                None => String::new(),
-            };
-            // Insert header + blank line + task entry
-            edits.push(EditOp::insert_at_line(insert_point, format!("{}{}", header_text, task_entry)));
-            3
-        } else {
-            edits.push(EditOp::insert_at_line(insert_point, task_entry.clone()));
-            1
-        };
 
-        // Add blank line after task entry
-        edits.push(EditOp::insert_at_line(insert_point + lines_inserted, String::new()));
-
-        let new_content = apply_edits(&content, edits)
+        let edits = vec![EditOp::replace(&existing_line, &updated_line)];
+        let new_content = apply_edits(content, edits)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-        task_file.save(&new_content)?;
+        return Ok(Some(new_content));
     }
 
-    println!("{}", task_id);
-    Ok(())
+    // Insert new task entry
+    let (insert_point, needs_header) = reader.find_body_insert_point(header)
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+
+    let mut edits = Vec::new();
+
+    let lines_inserted = if needs_header {
+        let header_text = match header {
+            Some(h) if h.starts_with("##") => format!("{}\n\n", h),
+            Some(h) => format!("## {}\n\n", h),
+            None => String::new(),
+        };
+        // Insert header + blank line + task entry
+        edits.push(EditOp::insert_at_line(insert_point, format!("{}{}", header_text, task_entry)));
+        3
+    } else {
+        edits.push(EditOp::insert_at_line(insert_point, task_entry));
+        1
+    };
+
+    // Add blank line after task entry
+    edits.push(EditOp::insert_at_line(insert_point + lines_inserted, String::new()));
+
+    let new_content = apply_edits(content, edits)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    Ok(Some(new_content))
 }
@@ -0,0 +1,60 @@
+//! Trash bin command module
+//!
+//! Deleted notes live in `.trash/` until restored or purged.
+
+use std::io;
+use std::time::Duration;
+use serde_json::json;
+use emx_note::{CapsaEngine, TrashCommand, util};
+
+pub fn run(ctx: &emx_note::ResolveContext, caps: Option<&str>, cmd: TrashCommand) -> io::Result<()> {
+    let capsa = CapsaEngine::new(super::resolve::resolve_capsa(ctx, caps)?);
+
+    match cmd {
+        TrashCommand::List => list(ctx, &capsa),
+        TrashCommand::Restore { id } => {
+            let path = capsa.restore_note(&id)?;
+            if ctx.json {
+                println!("{}", json!({ "id": id, "path": util::display_path(&path) }));
+            } else {
+                println!("{}", util::display_path(&path));
+            }
+            Ok(())
+        }
+        TrashCommand::Empty { older_than } => {
+            let min_age = older_than.map(|days| Duration::from_secs(days as u64 * 24 * 60 * 60));
+            let removed = {
+                let _lock = capsa.lock()?;
+                capsa.trash().empty(min_age)?
+            };
+            if ctx.json {
+                let ids: Vec<&str> = removed.iter().map(|e| e.id.as_str()).collect();
+                println!("{}", json!(ids));
+            } else {
+                for entry in &removed {
+                    println!("Deleted: {} ({})", entry.path, entry.id);
+                }
+                eprintln!("Removed {} trash entr{}", removed.len(), if removed.len() == 1 { "y" } else { "ies" });
+            }
+            Ok(())
+        }
+    }
+}
+
+/// List trash entries, oldest first
+fn list(ctx: &emx_note::ResolveContext, capsa: &CapsaEngine) -> io::Result<()> {
+    let entries = capsa.trash().list()?;
+
+    if ctx.json {
+        println!("{}", serde_json::to_string(&entries).map_err(io::Error::other)?);
+        return Ok(());
+    }
+
+    for entry in &entries {
+        match &entry.agent {
+            Some(agent) => println!("{}  {}  (deleted {} by @{})", entry.id, entry.path, entry.deleted, agent),
+            None => println!("{}  {}  (deleted {})", entry.id, entry.path, entry.deleted),
+        }
+    }
+    Ok(())
+}
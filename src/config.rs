@@ -0,0 +1,594 @@
+//! Per-capsa settings
+//!
+//! Settings are read from optional `.emx-note.yaml` files. A missing file (or
+//! missing key) means the next layer applies, from highest to lowest precedence:
+//!
+//! 1. command-line flags (`--on-conflict`)
+//! 2. environment variables (`EMX_TASKFILE` for `task_file`)
+//! 3. `.emx-note.yaml` in the capsa root
+//! 4. `.emx-note.yaml` in the notes home (defaults for every capsa)
+//! 5. built-in defaults
+//!
+//! ```yaml
+//! slug: pinyin          # unicode (default) | pinyin
+//! on_conflict: suffix   # error (default) | suffix | timestamp | append
+//! backup: true          # keep `{file}.bak` before rewriting a file (default: false)
+//! task_prefix: T-       # prefix of new task ids (default: TASK-)
+//! task_file: TODO.md    # task file of `task` commands (default: TASK.md)
+//! daily_title: Log      # title of untitled daily notes (default: Daily Note)
+//! daily_dir: journal    # directory of daily notes, `{daily_dir}/YYYYMMDD/` (default: #daily)
+//! extensions: [.md]     # extensions tried when resolving a note reference
+//! tag_link: "- [{title}]({path})"   # tag index entry
+//! hash_length: 8        # length of `note/{hash}/` source directories (default: 12)
+//! note_template: idea   # template of `note` from `.template/` (default: none)
+//! daily_template: day   # template of `daily` (default: none)
+//! inline_tags: true     # index `#hashtags` of note bodies as tags (default: false)
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::constants as C;
+use crate::{frontmatter, util};
+
+/// Environment variable overriding the `task_file` setting
+pub const ENV_TASKFILE: &str = "EMX_TASKFILE";
+
+/// Notes home whose `.emx-note.yaml` holds the defaults for every capsa
+static HOME_DIR: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+/// How note titles are turned into filenames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStyle {
+    /// Keep letters of any script (CJK titles stay CJK)
+    #[default]
+    Unicode,
+    /// Transliterate non-Latin scripts to ASCII (Chinese → pinyin)
+    Pinyin,
+}
+
+/// What to do when a new note's filename is already taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Refuse to create the note
+    #[default]
+    Error,
+    /// Add a numeric suffix: `idea-2.md`, `idea-3.md`, ...
+    Suffix,
+    /// Add a timestamp suffix: `idea-20260214100000.md`
+    Timestamp,
+    /// Append the content to the existing note
+    Append,
+}
+
+impl ConflictPolicy {
+    /// Get the policy name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Error => "error",
+            ConflictPolicy::Suffix => "suffix",
+            ConflictPolicy::Timestamp => "timestamp",
+            ConflictPolicy::Append => "append",
+        }
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(ConflictPolicy::Error),
+            "suffix" => Ok(ConflictPolicy::Suffix),
+            "timestamp" => Ok(ConflictPolicy::Timestamp),
+            "append" => Ok(ConflictPolicy::Append),
+            _ => Err(format!(
+                "Unknown conflict policy '{}' (expected error, suffix, timestamp or append)",
+                s
+            )),
+        }
+    }
+}
+
+/// Where the effective value of a setting comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// `.emx-note.yaml` in the notes home
+    Home,
+    /// `.emx-note.yaml` in the capsa root
+    Capsa,
+    /// Environment variable
+    Env,
+}
+
+impl ConfigSource {
+    /// Get the source name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigSource::Default => "default",
+            ConfigSource::Home => "home",
+            ConfigSource::Capsa => "capsa",
+            ConfigSource::Env => "env",
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A setting with its effective value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub key: String,
+    pub value: Value,
+    pub source: ConfigSource,
+}
+
+/// Settings for a single capsa
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapsaConfig {
+    /// Slug style for note filenames
+    pub slug: SlugStyle,
+    /// Policy when a new note's filename already exists
+    pub on_conflict: ConflictPolicy,
+    /// Keep the previous version of rewritten files as `{file}.bak`
+    pub backup: bool,
+    /// Prefix of new task ids, when the task file sets no `PREFIX`
+    pub task_prefix: String,
+    /// Task file of the `task` commands, relative to the capsa root
+    pub task_file: String,
+    /// Title of daily notes created without one
+    pub daily_title: String,
+    /// Directory of daily notes (`{daily_dir}/YYYYMMDD/HHmmSS-title.md`)
+    pub daily_dir: String,
+    /// Extensions tried, in order, when resolving a note reference
+    pub extensions: Vec<String>,
+    /// Tag index entry; `{title}` and `{path}` are replaced
+    pub tag_link: String,
+    /// Length of the abbreviated source hash (`note/{hash}/`)
+    pub hash_length: usize,
+    /// Template of new permanent notes (empty for none)
+    pub note_template: String,
+    /// Template of new daily notes (empty for none)
+    pub daily_template: String,
+    /// Index the inline `#hashtags` of note bodies like frontmatter tags
+    pub inline_tags: bool,
+}
+
+impl Default for CapsaConfig {
+    fn default() -> Self {
+        CapsaConfig {
+            slug: SlugStyle::default(),
+            on_conflict: ConflictPolicy::default(),
+            backup: false,
+            task_prefix: C::DEFAULT_TASK_PREFIX.to_string(),
+            task_file: C::TASK_FILENAME.to_string(),
+            daily_title: C::DEFAULT_DAILY_TITLE.to_string(),
+            daily_dir: C::DAILY_SUBDIR.to_string(),
+            extensions: crate::DEFAULT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            tag_link: C::TAG_LINK_FORMAT.to_string(),
+            hash_length: C::HASH_ABBREVIATION_LENGTH,
+            note_template: String::new(),
+            daily_template: String::new(),
+            inline_tags: false,
+        }
+    }
+}
+
+impl CapsaConfig {
+    /// Path of the settings file for a capsa
+    pub fn file(capsa_path: &Path) -> PathBuf {
+        capsa_path.join(C::CONFIG_FILENAME)
+    }
+
+    /// Path of the home-level settings file (defaults for every capsa)
+    pub fn home_file(home: &Path) -> PathBuf {
+        home.join(C::CONFIG_FILENAME)
+    }
+
+    /// Use the settings file of the notes home as defaults from now on
+    pub fn set_home(home: &Path) {
+        if let Ok(mut dir) = HOME_DIR.write() {
+            *dir = Some(home.to_path_buf());
+        }
+    }
+
+    /// Load settings for a capsa: its settings file over the home-level one,
+    /// then environment variables (defaults for missing files and keys)
+    pub fn load(capsa_path: &Path) -> io::Result<Self> {
+        Self::resolve(Some(capsa_path)).map(|(config, _)| config)
+    }
+
+    /// Load the defaults every capsa starts from: the home-level settings
+    /// file, then environment variables
+    pub fn load_defaults() -> io::Result<Self> {
+        Self::resolve(None).map(|(config, _)| config)
+    }
+
+    /// Every setting with its effective value and where it comes from, for a
+    /// capsa (or for the home-level defaults)
+    pub fn settings(capsa_path: Option<&Path>) -> io::Result<Vec<Setting>> {
+        let (config, sources) = Self::resolve(capsa_path)?;
+        let Value::Mapping(values) = to_value(&config)? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(values.into_iter()
+            .filter_map(|(key, value)| {
+                let key = key.as_str()?.to_string();
+                let source = sources.iter().rev()
+                    .find(|(k, _)| *k == key)
+                    .map_or(ConfigSource::Default, |(_, source)| *source);
+                Some(Setting { key, value, source })
+            })
+            .collect())
+    }
+
+    /// Names of all settings
+    pub fn keys() -> Vec<String> {
+        match to_value(&Self::default()) {
+            Ok(Value::Mapping(values)) => values.keys().filter_map(|k| k.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Fail unless `key` names a setting
+    pub fn check_key(key: &str) -> io::Result<()> {
+        let keys = Self::keys();
+        if keys.iter().any(|k| k == key) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown setting '{}' (expected {})", key, keys.join(", ")),
+            ))
+        }
+    }
+
+    /// Parse command-line values for a setting, typed like its default:
+    /// `true`/`false`, a number, a list (one value per item) or a string
+    pub fn parse_value(key: &str, values: &[String]) -> io::Result<Value> {
+        Self::check_key(key)?;
+        let default = match to_value(&Self::default())? {
+            Value::Mapping(mut values) => values.remove(key).unwrap_or(Value::Null),
+            _ => Value::Null,
+        };
+
+        let invalid = |expected: &str| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Setting '{}' takes {}", key, expected),
+        );
+        match (default, values) {
+            (Value::Sequence(_), values) if !values.is_empty() => {
+                Ok(Value::Sequence(values.iter().cloned().map(Value::String).collect()))
+            }
+            (Value::Sequence(_), _) => Err(invalid("one or more values")),
+            (default, [value]) => match default {
+                Value::Bool(_) => value.parse::<bool>()
+                    .map(Value::Bool)
+                    .map_err(|_| invalid("true or false")),
+                Value::Number(_) => value.parse::<u64>()
+                    .map(|n| Value::Number(n.into()))
+                    .map_err(|_| invalid("a number")),
+                _ => Ok(Value::String(value.clone())),
+            },
+            _ => Err(invalid("a single value")),
+        }
+    }
+
+    /// Set (`Some`) or remove (`None`) a setting in a settings file.
+    ///
+    /// The new value is checked first. Only the line(s) of that key are
+    /// rewritten, so comments and the layout of the rest of the file are kept.
+    pub fn write_setting(path: &Path, key: &str, value: Option<&Value>) -> io::Result<()> {
+        let mut layer = read_layer(path)?;
+        match value {
+            Some(value) => {
+                layer.insert(Value::String(key.to_string()), value.clone());
+            }
+            None => {
+                layer.remove(key);
+            }
+        }
+        check_layer(path, &layer)?;
+
+        let content = if path.exists() { fs::read_to_string(path)? } else { String::new() };
+        let line = value.map(|value| format!("{}: {}", key, render_value(value)));
+        util::atomic_write(path, &frontmatter::replace_key_lines(&content, key, line.as_deref()))
+    }
+
+    /// Convert a title to a filename slug using the configured style
+    pub fn slugify(&self, title: &str) -> String {
+        match self.slug {
+            SlugStyle::Unicode => util::slugify(title),
+            SlugStyle::Pinyin => util::slugify(&deunicode::deunicode(title)),
+        }
+    }
+
+    /// Extensions tried when resolving a note reference
+    pub fn extensions(&self) -> Vec<&str> {
+        self.extensions.iter().map(String::as_str).collect()
+    }
+
+    /// Tag index entry for a note
+    pub fn tag_link(&self, title: &str, path: &str) -> String {
+        self.tag_link.replace("{title}", title).replace("{path}", path)
+    }
+
+    /// Abbreviate a source hash to the configured length
+    pub fn abbreviate_hash(&self, full_hash: &str) -> String {
+        full_hash.chars().take(self.hash_length).collect()
+    }
+
+    /// Settings of every layer merged, with the source of each key that was set
+    fn resolve(capsa_path: Option<&Path>) -> io::Result<(Self, Vec<(String, ConfigSource)>)> {
+        let mut layers = Vec::new();
+        if let Some(home) = HOME_DIR.read().ok().and_then(|dir| dir.clone()) {
+            layers.push((ConfigSource::Home, Self::home_file(&home)));
+        }
+        if let Some(capsa_path) = capsa_path {
+            layers.push((ConfigSource::Capsa, Self::file(capsa_path)));
+        }
+        Self::merge(&layers)
+    }
+
+    /// Merge settings files, lowest precedence first, then apply environment
+    /// variables
+    fn merge(layers: &[(ConfigSource, PathBuf)]) -> io::Result<(Self, Vec<(String, ConfigSource)>)> {
+        let mut merged = Mapping::new();
+        let mut sources = Vec::new();
+        for (source, path) in layers {
+            for (key, value) in read_layer(path)? {
+                if let Some(name) = key.as_str() {
+                    sources.push((name.to_string(), *source));
+                }
+                merged.insert(key, value);
+            }
+        }
+
+        let mut config: CapsaConfig = serde_yaml::from_value(Value::Mapping(merged))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        if let Some(task_file) = std::env::var(ENV_TASKFILE).ok().filter(|s| !s.is_empty()) {
+            config.task_file = task_file;
+            sources.push(("task_file".to_string(), ConfigSource::Env));
+        }
+
+        Ok((config, sources))
+    }
+
+    /// Check values that deserialize but make no sense
+    fn validate(&self) -> Result<(), String> {
+        if self.task_prefix.trim().is_empty() || self.task_prefix.contains(char::is_whitespace) {
+            return Err("task_prefix must be a non-empty word".to_string());
+        }
+        let task_file = Path::new(&self.task_file);
+        if self.task_file.is_empty() || !task_file.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err("task_file must be a path inside the capsa".to_string());
+        }
+        if self.daily_title.trim().is_empty() {
+            return Err("daily_title must not be empty".to_string());
+        }
+        let daily_dir = Path::new(&self.daily_dir);
+        if !matches!(daily_dir.components().collect::<Vec<_>>().as_slice(), [Component::Normal(_)])
+            || self.daily_dir.starts_with('.')
+            || self.daily_dir == C::NOTE_SUBDIR
+        {
+            return Err(format!("daily_dir must be a directory name in the capsa root other than {}", C::NOTE_SUBDIR));
+        }
+        if self.extensions.is_empty() || self.extensions.iter().any(|ext| ext.len() < 2 || !ext.starts_with('.')) {
+            return Err("extensions must be a list like [.md, .txt]".to_string());
+        }
+        if !self.tag_link.contains("]({path})") {
+            return Err("tag_link must contain a markdown link to {path}, like \"- [{title}]({path})\"".to_string());
+        }
+        if !(1..=64).contains(&self.hash_length) {
+            return Err("hash_length must be between 1 and 64".to_string());
+        }
+        for template in [&self.note_template, &self.daily_template] {
+            if !template.is_empty() {
+                crate::template::check_name(template)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Top-level keys of a settings file (empty if the file is missing or blank)
+fn read_layer(path: &Path) -> io::Result<Mapping> {
+    if !path.exists() {
+        return Ok(Mapping::new());
+    }
+
+    let content = fs::read_to_string(path)?;
+    if content.trim().is_empty() {
+        return Ok(Mapping::new());
+    }
+
+    let layer = match serde_yaml::from_str::<Value>(&content) {
+        Ok(Value::Mapping(layer)) => layer,
+        Ok(Value::Null) => Mapping::new(),
+        Ok(_) => return Err(invalid_settings(path, "expected key: value pairs")),
+        Err(e) => return Err(invalid_settings(path, e)),
+    };
+    check_layer(path, &layer)?;
+    Ok(layer)
+}
+
+/// Check that the keys of one settings file are valid on their own
+fn check_layer(path: &Path, layer: &Mapping) -> io::Result<()> {
+    let config: CapsaConfig = serde_yaml::from_value(Value::Mapping(layer.clone()))
+        .map_err(|e| invalid_settings(path, e))?;
+    config.validate().map_err(|e| invalid_settings(path, e))
+}
+
+fn invalid_settings(path: &Path, error: impl fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid settings in {}: {}", util::display_path(path), error),
+    )
+}
+
+fn to_value(config: &CapsaConfig) -> io::Result<Value> {
+    serde_yaml::to_value(config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// A value on one line: scalars as YAML, lists in flow style (`[.md, .txt]`)
+pub fn render_value(value: &Value) -> String {
+    match value {
+        Value::Sequence(items) => format!("[{}]", items.iter().map(render_value).collect::<Vec<_>>().join(", ")),
+        _ => serde_yaml::to_string(value).map(|s| s.trim_end().to_string()).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_missing_file_uses_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = CapsaConfig::load(temp_dir.path()).unwrap();
+        assert_eq!(config.slug, SlugStyle::Unicode);
+        assert!(!config.backup);
+    }
+
+    #[test]
+    fn test_load_pinyin_slug() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(CapsaConfig::file(temp_dir.path()), "slug: pinyin\n").unwrap();
+        let config = CapsaConfig::load(temp_dir.path()).unwrap();
+        assert_eq!(config.slug, SlugStyle::Pinyin);
+        assert_eq!(config.slugify("会议记录 Q1"), "hui-yi-ji-lu-q1");
+    }
+
+    #[test]
+    fn test_load_invalid_value() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(CapsaConfig::file(temp_dir.path()), "slug: klingon\n").unwrap();
+        let err = CapsaConfig::load(temp_dir.path()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(CapsaConfig::load(temp_dir.path()).unwrap().on_conflict, ConflictPolicy::Error);
+
+        fs::write(CapsaConfig::file(temp_dir.path()), "on_conflict: timestamp\n").unwrap();
+        assert_eq!(CapsaConfig::load(temp_dir.path()).unwrap().on_conflict, ConflictPolicy::Timestamp);
+
+        assert_eq!("Append".parse::<ConflictPolicy>(), Ok(ConflictPolicy::Append));
+        assert!("overwrite".parse::<ConflictPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_home_and_capsa_layers() {
+        let home = TempDir::new().unwrap();
+        let capsa = TempDir::new().unwrap();
+        fs::write(CapsaConfig::home_file(home.path()), "backup: true\ntask_prefix: H-\n").unwrap();
+        fs::write(CapsaConfig::file(capsa.path()), "task_prefix: C-\nextensions: [.md, .txt]\n").unwrap();
+
+        let layers = [
+            (ConfigSource::Home, CapsaConfig::home_file(home.path())),
+            (ConfigSource::Capsa, CapsaConfig::file(capsa.path())),
+        ];
+        let (config, sources) = CapsaConfig::merge(&layers).unwrap();
+        assert!(config.backup);
+        assert_eq!(config.task_prefix, "C-");
+        assert_eq!(config.extensions(), vec![".md", ".txt"]);
+        assert_eq!(config.slug, SlugStyle::Unicode);
+        let source = |key: &str| sources.iter().rev().find(|(k, _)| k == key).map(|(_, s)| *s);
+        assert_eq!(source("backup"), Some(ConfigSource::Home));
+        assert_eq!(source("task_prefix"), Some(ConfigSource::Capsa));
+        assert_eq!(source("slug"), None);
+
+        // Without the capsa file, the home-level settings apply
+        let (config, _) = CapsaConfig::merge(&layers[..1]).unwrap();
+        assert_eq!(config.task_prefix, "H-");
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(CapsaConfig::parse_value("backup", &["true".to_string()]).unwrap(), Value::Bool(true));
+        assert_eq!(CapsaConfig::parse_value("hash_length", &["8".to_string()]).unwrap(), Value::Number(8.into()));
+        assert_eq!(
+            CapsaConfig::parse_value("extensions", &[".md".to_string(), ".txt".to_string()]).unwrap(),
+            Value::Sequence(vec![Value::String(".md".into()), Value::String(".txt".into())]),
+        );
+        assert_eq!(CapsaConfig::parse_value("slug", &["pinyin".to_string()]).unwrap(), Value::String("pinyin".into()));
+        assert!(CapsaConfig::parse_value("backup", &["yes please".to_string()]).is_err());
+        assert!(CapsaConfig::parse_value("slug", &[]).is_err());
+        let err = CapsaConfig::parse_value("colour", &["red".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("expected slug, on_conflict, backup"));
+    }
+
+    #[test]
+    fn test_daily_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        assert_eq!(CapsaConfig::load(temp_dir.path()).unwrap().daily_dir, "#daily");
+
+        let file = CapsaConfig::file(temp_dir.path());
+        fs::write(&file, "daily_dir: journal\n").unwrap();
+        assert_eq!(CapsaConfig::load(temp_dir.path()).unwrap().daily_dir, "journal");
+
+        for invalid in ["note", "a/b", "../up", ".hidden", "\"\""] {
+            fs::write(&file, format!("daily_dir: {}\n", invalid)).unwrap();
+            assert!(CapsaConfig::load(temp_dir.path()).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_write_setting_keeps_other_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = CapsaConfig::file(temp_dir.path());
+        fs::write(&file, "# Team settings\nslug: pinyin\nextensions:\n  - .md\n  - .txt\nbackup: false\n").unwrap();
+
+        CapsaConfig::write_setting(&file, "backup", Some(&Value::Bool(true))).unwrap();
+        CapsaConfig::write_setting(&file, "extensions", Some(&Value::Sequence(vec![Value::String(".md".into())]))).unwrap();
+        CapsaConfig::write_setting(&file, "slug", None).unwrap();
+        CapsaConfig::write_setting(&file, "tag_link", Some(&Value::String("- [{title}]({path}) #x".into()))).unwrap();
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "# Team settings\nextensions: [.md]\nbackup: true\ntag_link: '- [{title}]({path}) #x'\n",
+        );
+        let config = CapsaConfig::load(temp_dir.path()).unwrap();
+        assert_eq!(config.tag_link("Idea", "note/idea.md"), "- [Idea](note/idea.md) #x");
+
+        // Invalid values are refused before anything is written
+        assert!(CapsaConfig::write_setting(&file, "tag_link", Some(&Value::String("{title}".into()))).is_err());
+        assert!(CapsaConfig::write_setting(&file, "hash_length", Some(&Value::Number(0.into()))).is_err());
+        assert!(CapsaConfig::write_setting(&file, "task_file", Some(&Value::String("../TASK.md".into()))).is_err());
+        assert!(CapsaConfig::write_setting(&file, "slug", Some(&Value::String("klingon".into()))).is_err());
+        assert!(fs::read_to_string(&file).unwrap().ends_with("#x'\n"));
+    }
+
+    #[test]
+    fn test_unicode_slug_keeps_cjk() {
+        let config = CapsaConfig::default();
+        assert_eq!(config.slugify("会议记录 Q1"), "会议记录-q1");
+    }
+}
@@ -20,9 +20,36 @@ pub const DAILY_LINK_FILENAME: &str = "#daily.md";
 /// Filename for task file
 pub const TASK_FILENAME: &str = "TASK.md";
 
+/// Per-capsa settings file (in the capsa root)
+pub const CONFIG_FILENAME: &str = ".emx-note.yaml";
+
+/// Advisory lock file (in the capsa root)
+pub const LOCK_FILENAME: &str = ".emx-note.lock";
+
+/// Hidden directory for persistent per-capsa indexes
+pub const INDEX_DIR: &str = ".index";
+
+/// Filename of the full-text search index (inside INDEX_DIR)
+pub const SEARCH_INDEX_FILENAME: &str = "search.json";
+
+/// Filename of the link cache (inside INDEX_DIR)
+pub const LINK_CACHE_FILENAME: &str = "links.json";
+
+/// Hidden directory for note templates (in the capsa root)
+pub const TEMPLATE_DIR: &str = ".template";
+
+/// Hidden directory for deleted notes (in the capsa root)
+pub const TRASH_DIR: &str = ".trash";
+
+/// Extension of trash sidecar records (`.trash/{id}.yaml`)
+pub const TRASH_RECORD_EXTENSION: &str = ".yaml";
+
 /// Default file extension for notes
 pub const MARKDOWN_EXTENSION: &str = ".md";
 
+/// Suffix for the previous version of a rewritten file (when backups are enabled)
+pub const BACKUP_EXTENSION: &str = ".bak";
+
 /// Prefix for tag files
 pub const TAG_PREFIX: &str = "#";
 
@@ -51,6 +78,24 @@ pub const DAILY_TIME_FORMAT: &str = "%H%M%S";
 /// Display format for dates in links: %Y-%m-%d
 pub const DAILY_DATE_DISPLAY_FORMAT: &str = "%Y-%m-%d";
 
+/// Time format of the `{{time}}` template variable: %H:%M
+pub const TEMPLATE_TIME_FORMAT: &str = "%H:%M";
+
+/// Timestamp suffix for colliding note filenames: %Y%m%d%H%M%S
+pub const CONFLICT_TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M%S";
+
+/// Frontmatter key bumped when a note is edited
+pub const MODIFIED_KEY: &str = "modified";
+
+/// Frontmatter key listing a note's tags
+pub const TAGS_KEY: &str = "tags";
+
+/// `modified` frontmatter value written after an edit: %Y-%m-%d %H:%M:%S
+pub const MODIFIED_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Deletion time in trash records: %Y-%m-%d %H:%M:%S
+pub const TRASH_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
 // === Markdown Format Strings ===
 
 /// Daily note link format: - [{}](#daily/{}/{})
@@ -60,9 +105,9 @@ pub const DAILY_LINK_FORMAT: &str = "- [{}](#daily/{}/{})";
 /// Header for daily notes index file
 pub const DAILY_NOTES_HEADER: &str = "# Daily Notes\n\n";
 
-/// Tag link format: - [{}]({})
-/// Arguments: note title, relative path
-pub const TAG_LINK_FORMAT: &str = "- [{}]({})";
+/// Default tag index entry (`tag_link` setting): `{title}` and `{path}`
+/// (relative to the capsa root) are replaced
+pub const TAG_LINK_FORMAT: &str = "- [{title}]({path})";
 
 /// Date header format in tag files: ## {}
 pub const TAG_DATE_HEADER_FORMAT: &str = "## {}";
@@ -88,11 +133,74 @@ pub const TASK_CHECKBOX_DONE: &str = "[x]";
 /// Checkbox format for pending tasks
 pub const TASK_CHECKBOX_PENDING: &str = "[ ]";
 
+// === Note Creation Constants ===
+
+/// Highest numeric suffix tried for colliding note filenames (`idea-2.md` ... `idea-N.md`)
+pub const MAX_CONFLICT_SUFFIX: u32 = 1000;
+
+// === Locking Constants ===
+
+/// How long to wait for a held capsa lock before giving up
+pub const LOCK_TIMEOUT_SECS: u64 = 10;
+
+/// Age after which a lock is considered left behind by a crashed process
+pub const LOCK_STALE_SECS: u64 = 30;
+
+/// Delay between attempts to acquire a held lock
+pub const LOCK_RETRY_MS: u64 = 20;
+
+// === Edit Constants ===
+
+/// Re-read and re-apply attempts when a file changes between read and write
+pub const EDIT_MAX_RETRIES: usize = 3;
+
+/// Context lines a patch hunk may ignore at each end to apply (like `patch -F`)
+pub const PATCH_DEFAULT_FUZZ: usize = 2;
+
 // === Hash and ID Constants ===
 
 /// Length of abbreviated hash for source tracking
 pub const HASH_ABBREVIATION_LENGTH: usize = 12;
 
+// === Search Constants ===
+
+/// On-disk format version of the search index (bump to force a rebuild)
+pub const SEARCH_INDEX_VERSION: u32 = 3;
+
+/// BM25 term frequency saturation parameter
+pub const BM25_K1: f64 = 1.2;
+
+/// BM25 document length normalization parameter
+pub const BM25_B: f64 = 0.75;
+
+/// Default number of search results
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+// === Link Constants ===
+
+/// Lines shown before and after a backlink
+pub const BACKLINK_CONTEXT_LINES: usize = 1;
+
+/// Longest snippet line shown for a backlink (characters)
+pub const BACKLINK_SNIPPET_WIDTH: usize = 160;
+
+/// Links followed from the note given to `link graph --note`
+pub const LINK_GRAPH_DEFAULT_DEPTH: usize = 1;
+
+/// Largest share of characters that may differ for a heading to be suggested
+/// for a broken anchor
+pub const ANCHOR_SUGGESTION_MAX_DIFF: f64 = 1.0 / 3.0;
+
+/// Most repair candidates proposed for a broken link
+pub const LINK_FIX_MAX_CANDIDATES: usize = 5;
+
+/// Largest share of characters that may differ for a file name to be proposed
+/// for a broken link
+pub const LINK_FIX_MAX_NAME_DIFF: f64 = 1.0 / 3.0;
+
+/// On-disk format version of the link cache (bump to force a rebuild)
+pub const LINK_CACHE_VERSION: u32 = 6;
+
 // === Validation Limits ===
 
 /// Maximum size of frontmatter to parse (prevents DoS on malformed files)
@@ -2,11 +2,29 @@
 //!
 //! Inspired by LLM Code Agent's edit tool pattern. Provides precise,
 //! verifiable editing operations that validate content before modification.
+//!
+//! File edits use optimistic concurrency: the content hash (SHA-256) taken
+//! when a file is read is checked again before writing, and a write to a file
+//! that changed in between fails with a [`ConflictError`]. [`edit_file`] and
+//! [`update_file`] re-read and re-apply their edits on conflict.
 
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::constants as C;
+use crate::markdown;
+use crate::util;
 
 /// Validation error for edit operations
-#[derive(Debug, Clone)]
+///
+/// Serializes as `{"kind": "MultipleMatches", "pattern": "...", "count": 3}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
 pub enum ValidationError {
     /// Pattern not found in content
     NotFound { pattern: String },
@@ -14,6 +32,12 @@ pub enum ValidationError {
     MultipleMatches { pattern: String, count: usize },
     /// Invalid line number
     InvalidLine { line: usize, max_line: usize },
+    /// Invalid line range (`start..end`, 0-indexed, end exclusive)
+    InvalidRange { start: usize, end: usize, max_line: usize },
+    /// Pattern matched a different number of times than expected
+    MatchCount { pattern: String, expected: usize, count: usize },
+    /// Regular expression does not compile
+    InvalidPattern { pattern: String, message: String },
 }
 
 impl fmt::Display for ValidationError {
@@ -28,14 +52,73 @@ impl fmt::Display for ValidationError {
             ValidationError::InvalidLine { line, max_line } => {
                 write!(f, "Invalid line {} (max: {})", line, max_line)
             }
+            ValidationError::InvalidRange { start, end, max_line } => {
+                write!(f, "Invalid line range {}..{} (max: {})", start, end, max_line)
+            }
+            ValidationError::MatchCount { pattern, expected, count } => {
+                write!(f, "Pattern found {} times (expected {}): {:?}", count, expected, pattern)
+            }
+            ValidationError::InvalidPattern { pattern, message } => {
+                write!(f, "Invalid pattern {:?}: {}", pattern, message)
+            }
         }
     }
 }
 
 impl std::error::Error for ValidationError {}
 
-/// Edit operation type
+/// A file changed on disk between reading and writing it
 #[derive(Debug, Clone, PartialEq)]
+pub struct ConflictError {
+    pub path: PathBuf,
+    /// Version seen when the file was read (None: file did not exist)
+    pub expected: Option<String>,
+    /// Version found when writing (None: file no longer exists)
+    pub actual: Option<String>,
+}
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let short = |v: &Option<String>| match v {
+            Some(v) => util::abbreviate_hash(v),
+            None => "(missing)".to_string(),
+        };
+        write!(
+            f,
+            "File changed since it was read: {} (read version {}, now {})",
+            util::display_path(&self.path),
+            short(&self.expected),
+            short(&self.actual)
+        )
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+impl From<ConflictError> for io::Error {
+    fn from(err: ConflictError) -> Self {
+        io::Error::other(err)
+    }
+}
+
+impl ConflictError {
+    /// Get the conflict behind an io::Error, if that is what it is
+    pub fn from_io(err: &io::Error) -> Option<&ConflictError> {
+        err.get_ref().and_then(|e| e.downcast_ref::<ConflictError>())
+    }
+}
+
+/// Edit operation type
+///
+/// Serializes as `{"op": "replace", "old": "...", "new": "..."}`, with the
+/// variant name in snake_case as `op` and the fields as below.
+///
+/// Line numbers are 0-indexed and ranges are `start..end` (end exclusive).
+/// A heading is matched by its text (and level, if given) and must occur
+/// exactly once; its section runs to the next heading of the same or a
+/// higher level.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
 pub enum EditOp {
     /// Replace old string with new string (validates old exists exactly once)
     Replace { old: String, new: String },
@@ -45,9 +128,62 @@ pub enum EditOp {
     Append { content: String },
     /// Delete a line that matches exactly (validates existence)
     DeleteLine { content: String },
+    /// Insert content after the last non-blank line of a heading's section
+    AppendUnderHeading {
+        heading: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        level: Option<u8>,
+        content: String,
+    },
+    /// Replace the body of a heading's section (blank lines before the next
+    /// heading are kept)
+    ReplaceSection {
+        heading: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        level: Option<u8>,
+        content: String,
+    },
+    /// Delete lines `start..end`
+    DeleteRange { start: usize, end: usize },
+    /// Replace lines `start..end` with content
+    ReplaceLineRange { start: usize, end: usize, content: String },
+    /// Replace every match of a regular expression (`$1` expands groups),
+    /// validating that it matches exactly `count` times
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+        #[serde(default = "default_match_count")]
+        count: usize,
+    },
+}
+
+fn default_match_count() -> usize {
+    1
 }
 
 impl EditOp {
+    /// Parse a JSON list of edit operations
+    pub fn parse_list(json: &str) -> io::Result<Vec<EditOp>> {
+        serde_json::from_str(json).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid edit ops: {}", e))
+        })
+    }
+
+    /// Operation name as used in JSON (`replace`, `insert_at_line`, ...)
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditOp::Replace { .. } => "replace",
+            EditOp::InsertAtLine { .. } => "insert_at_line",
+            EditOp::Append { .. } => "append",
+            EditOp::DeleteLine { .. } => "delete_line",
+            EditOp::AppendUnderHeading { .. } => "append_under_heading",
+            EditOp::ReplaceSection { .. } => "replace_section",
+            EditOp::DeleteRange { .. } => "delete_range",
+            EditOp::ReplaceLineRange { .. } => "replace_line_range",
+            EditOp::RegexReplace { .. } => "regex_replace",
+        }
+    }
+
     /// Create a replace operation
     pub fn replace(old: impl Into<String>, new: impl Into<String>) -> Self {
         EditOp::Replace {
@@ -77,6 +213,59 @@ impl EditOp {
             content: content.into(),
         }
     }
+
+    /// Create an append under heading operation
+    pub fn append_under_heading(
+        heading: impl Into<String>,
+        level: Option<u8>,
+        content: impl Into<String>,
+    ) -> Self {
+        EditOp::AppendUnderHeading {
+            heading: heading.into(),
+            level,
+            content: content.into(),
+        }
+    }
+
+    /// Create a replace section operation
+    pub fn replace_section(
+        heading: impl Into<String>,
+        level: Option<u8>,
+        content: impl Into<String>,
+    ) -> Self {
+        EditOp::ReplaceSection {
+            heading: heading.into(),
+            level,
+            content: content.into(),
+        }
+    }
+
+    /// Create a delete range operation
+    pub fn delete_range(start: usize, end: usize) -> Self {
+        EditOp::DeleteRange { start, end }
+    }
+
+    /// Create a replace line range operation
+    pub fn replace_line_range(start: usize, end: usize, content: impl Into<String>) -> Self {
+        EditOp::ReplaceLineRange {
+            start,
+            end,
+            content: content.into(),
+        }
+    }
+
+    /// Create a regex replace operation
+    pub fn regex_replace(
+        pattern: impl Into<String>,
+        replacement: impl Into<String>,
+        count: usize,
+    ) -> Self {
+        EditOp::RegexReplace {
+            pattern: pattern.into(),
+            replacement: replacement.into(),
+            count,
+        }
+    }
 }
 
 /// Apply a list of edit operations to content
@@ -154,7 +343,230 @@ fn apply_single_edit(content: &str, edit: EditOp) -> Result<String, ValidationEr
             let suffix = if content.ends_with('\n') { "\n" } else { "" };
             Ok(new_lines.join("\n") + suffix)
         }
+        EditOp::AppendUnderHeading { heading, level, content: new_content } => {
+            let lines: Vec<&str> = content.lines().collect();
+            let (heading_line, end) = find_section(content, &heading, level)?;
+
+            // After the section's last non-blank line
+            let insert_at = (heading_line + 1..end).rev()
+                .find(|&i| !lines[i].trim().is_empty())
+                .map_or(heading_line + 1, |i| i + 1);
+            Ok(splice_lines(content, insert_at..insert_at, &new_content))
+        }
+        EditOp::ReplaceSection { heading, level, content: new_content } => {
+            let lines: Vec<&str> = content.lines().collect();
+            let (heading_line, end) = find_section(content, &heading, level)?;
+
+            // Keep the blank lines separating the section from the next heading
+            let body_end = (heading_line + 1..end).rev()
+                .find(|&i| !lines[i].trim().is_empty())
+                .map_or(heading_line + 1, |i| i + 1);
+            Ok(splice_lines(content, heading_line + 1..body_end, &new_content))
+        }
+        EditOp::DeleteRange { start, end } => {
+            check_range(content, start, end)?;
+            let lines: Vec<&str> = content.lines().collect();
+            let new_lines = [&lines[..start], &lines[end..]].concat();
+
+            let suffix = if content.ends_with('\n') && !new_lines.is_empty() { "\n" } else { "" };
+            Ok(new_lines.join("\n") + suffix)
+        }
+        EditOp::ReplaceLineRange { start, end, content: new_content } => {
+            check_range(content, start, end)?;
+            Ok(splice_lines(content, start..end, &new_content))
+        }
+        EditOp::RegexReplace { pattern, replacement, count: expected } => {
+            let re = Regex::new(&pattern).map_err(|e| ValidationError::InvalidPattern {
+                pattern: pattern.clone(),
+                message: e.to_string(),
+            })?;
+            let count = re.find_iter(content).count();
+            if count == 0 && expected > 0 {
+                return Err(ValidationError::NotFound { pattern });
+            }
+            if count != expected {
+                return Err(ValidationError::MatchCount { pattern, expected, count });
+            }
+            Ok(re.replace_all(content, replacement.as_str()).into_owned())
+        }
+    }
+}
+
+/// Find the section of a heading: its line and the (exclusive) end line of
+/// its body, i.e. the next heading of the same or a higher level
+fn find_section(content: &str, heading: &str, level: Option<u8>) -> Result<(usize, usize), ValidationError> {
+    let headings = markdown::heading_lines(content);
+    let matches: Vec<&markdown::HeadingLine> = headings.iter()
+        .filter(|h| h.text == heading && level.is_none_or(|l| h.level == l))
+        .collect();
+
+    let found = match matches.as_slice() {
+        [found] => *found,
+        [] => return Err(ValidationError::NotFound { pattern: heading.to_string() }),
+        _ => {
+            return Err(ValidationError::MultipleMatches {
+                pattern: heading.to_string(),
+                count: matches.len(),
+            })
+        }
+    };
+
+    let end = headings.iter()
+        .find(|h| h.line > found.line && h.level <= found.level)
+        .map_or(content.lines().count(), |h| h.line);
+    Ok((found.line, end))
+}
+
+/// Validate a line range `start..end` (non-empty, within the content)
+fn check_range(content: &str, start: usize, end: usize) -> Result<(), ValidationError> {
+    let max_line = content.lines().count();
+    if start >= end || end > max_line {
+        return Err(ValidationError::InvalidRange { start, end, max_line });
     }
+    Ok(())
+}
+
+/// Replace a range of lines with new content (an empty string removes them),
+/// keeping the trailing newline of the original
+fn splice_lines(content: &str, range: std::ops::Range<usize>, new_content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut new_lines = Vec::with_capacity(lines.len() + 1);
+    new_lines.extend_from_slice(&lines[..range.start]);
+    if !new_content.is_empty() {
+        new_lines.push(new_content.strip_suffix('\n').unwrap_or(new_content));
+    }
+    new_lines.extend_from_slice(&lines[range.end..]);
+
+    let suffix = if content.ends_with('\n') { "\n" } else { "" };
+    new_lines.join("\n") + suffix
+}
+
+// === Versioned file edits ===
+
+/// Content version: SHA-256 of the content (hex)
+pub fn content_version(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// File content together with the version it was read at
+#[derive(Debug, Clone)]
+pub struct VersionedFile {
+    path: PathBuf,
+    content: String,
+    version: Option<String>,
+    backup: bool,
+}
+
+impl VersionedFile {
+    /// Read a file and remember its version (a missing file reads as empty)
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let (content, version) = Self::current(path)?;
+        Ok(VersionedFile {
+            path: path.to_path_buf(),
+            content: content.unwrap_or_default(),
+            version,
+            backup: false,
+        })
+    }
+
+    /// Keep the replaced version as `{file}.bak` when writing
+    pub fn with_backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// File path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Content at read time
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Version at read time (None if the file did not exist)
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Whether the file existed at read time
+    pub fn exists(&self) -> bool {
+        self.version.is_some()
+    }
+
+    /// Write new content atomically, unless the file changed since it was read
+    pub fn write(&self, new_content: &str) -> io::Result<()> {
+        let (_, actual) = Self::current(&self.path)?;
+        if actual != self.version {
+            return Err(ConflictError {
+                path: self.path.clone(),
+                expected: self.version.clone(),
+                actual,
+            }.into());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if self.backup {
+            util::backup_file(&self.path)?;
+        }
+        util::atomic_write(&self.path, new_content)
+    }
+
+    /// Current content and version on disk
+    fn current(path: &Path) -> io::Result<(Option<String>, Option<String>)> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                let version = content_version(&content);
+                Ok((Some(content), Some(version)))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok((None, None)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Read-modify-write a file with optimistic concurrency.
+///
+/// `update` gets the freshly read file and returns the new content, or None to
+/// leave the file unchanged. If the file changes before the write, it is re-read
+/// and `update` runs again, up to `C::EDIT_MAX_RETRIES` times, so it should
+/// derive the new content from the file it is given. With `backup`,
+/// the replaced version is kept as `{file}.bak`.
+/// Returns the written content (None if nothing was written).
+pub fn update_file<F>(path: &Path, backup: bool, mut update: F) -> io::Result<Option<String>>
+where
+    F: FnMut(&VersionedFile) -> io::Result<Option<String>>,
+{
+    let mut attempt = 0;
+    loop {
+        let file = VersionedFile::read(path)?.with_backup(backup);
+        let new_content = match update(&file)? {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
+        match file.write(&new_content) {
+            Ok(()) => return Ok(Some(new_content)),
+            Err(e) if ConflictError::from_io(&e).is_some() && attempt < C::EDIT_MAX_RETRIES => {
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Apply edit operations to a file, re-applying them to the new content if
+/// the file changes between read and write
+pub fn edit_file(path: &Path, edits: &[EditOp], backup: bool) -> io::Result<String> {
+    update_file(path, backup, |file| {
+        apply_edits(file.content(), edits.to_vec())
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    })
+    .map(|c| c.unwrap_or_default())
 }
 
 #[cfg(test)]
@@ -287,4 +699,173 @@ mod tests {
         let result = apply_edits(content, edits).unwrap();
         assert_eq!(result, "line 1\ninserted\nline 2\n");
     }
+
+    #[test]
+    fn test_append_under_heading() {
+        let content = "# Note\n\n## Todo\n- a\n\n## Done\n- b\n";
+        let edits = vec![EditOp::append_under_heading("Todo", Some(2), "- c")];
+        let result = apply_edits(content, edits).unwrap();
+        assert_eq!(result, "# Note\n\n## Todo\n- a\n- c\n\n## Done\n- b\n");
+
+        // Last section runs to the end of the note
+        let edits = vec![EditOp::append_under_heading("Done", None, "- d")];
+        let result = apply_edits(content, edits).unwrap();
+        assert_eq!(result, "# Note\n\n## Todo\n- a\n\n## Done\n- b\n- d\n");
+    }
+
+    #[test]
+    fn test_heading_not_found_or_ambiguous() {
+        let content = "## A\nx\n## A\ny\n";
+        let err = apply_edits(content, vec![EditOp::append_under_heading("B", None, "z")]).unwrap_err();
+        assert!(matches!(err, ValidationError::NotFound { .. }));
+        let err = apply_edits(content, vec![EditOp::replace_section("A", None, "z")]).unwrap_err();
+        assert!(matches!(err, ValidationError::MultipleMatches { count: 2, .. }));
+        let err = apply_edits(content, vec![EditOp::replace_section("A", Some(1), "z")]).unwrap_err();
+        assert!(matches!(err, ValidationError::NotFound { .. }));
+    }
+
+    #[test]
+    fn test_replace_section() {
+        let content = "# Note\n## Plan\nold 1\n### Detail\nold 2\n\n## Next\nkeep\n";
+        let edits = vec![EditOp::replace_section("Plan", Some(2), "new")];
+        let result = apply_edits(content, edits).unwrap();
+        assert_eq!(result, "# Note\n## Plan\nnew\n\n## Next\nkeep\n");
+
+        // Headings in code blocks do not end a section
+        let content = "## Plan\n```\n## not a heading\n```\n## Next\n";
+        let edits = vec![EditOp::replace_section("Plan", None, "")];
+        assert_eq!(apply_edits(content, edits).unwrap(), "## Plan\n## Next\n");
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let content = "a\nb\nc\nd\n";
+        let result = apply_edits(content, vec![EditOp::delete_range(1, 3)]).unwrap();
+        assert_eq!(result, "a\nd\n");
+
+        let err = apply_edits(content, vec![EditOp::delete_range(2, 5)]).unwrap_err();
+        assert!(matches!(err, ValidationError::InvalidRange { start: 2, end: 5, max_line: 4 }));
+        let err = apply_edits(content, vec![EditOp::delete_range(2, 2)]).unwrap_err();
+        assert!(matches!(err, ValidationError::InvalidRange { .. }));
+    }
+
+    #[test]
+    fn test_replace_line_range() {
+        let content = "a\nb\nc";
+        let result = apply_edits(content, vec![EditOp::replace_line_range(0, 2, "x\ny\nz")]).unwrap();
+        assert_eq!(result, "x\ny\nz\nc");
+    }
+
+    #[test]
+    fn test_regex_replace() {
+        let content = "- [ ] one\n- [ ] two\n";
+        let edits = vec![EditOp::regex_replace(r"- \[ \] (\w+)", "- [x] $1", 2)];
+        assert_eq!(apply_edits(content, edits).unwrap(), "- [x] one\n- [x] two\n");
+
+        let err = apply_edits(content, vec![EditOp::regex_replace(r"- \[ \]", "-", 1)]).unwrap_err();
+        assert!(matches!(err, ValidationError::MatchCount { expected: 1, count: 2, .. }));
+        let err = apply_edits(content, vec![EditOp::regex_replace("three", "", 1)]).unwrap_err();
+        assert!(matches!(err, ValidationError::NotFound { .. }));
+        let err = apply_edits(content, vec![EditOp::regex_replace("(", "", 1)]).unwrap_err();
+        assert!(matches!(err, ValidationError::InvalidPattern { .. }));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let ops = EditOp::parse_list(
+            r#"[{"op":"replace","old":"a","new":"b"},{"op":"insert_at_line","line":0,"content":"x"},
+                {"op":"append","content":"y"},{"op":"delete_line","content":"z"}]"#,
+        ).unwrap();
+        assert_eq!(ops, vec![
+            EditOp::replace("a", "b"),
+            EditOp::insert_at_line(0, "x"),
+            EditOp::append("y"),
+            EditOp::delete_line("z"),
+        ]);
+        assert_eq!(ops[1].name(), "insert_at_line");
+
+        let ops = EditOp::parse_list(
+            r#"[{"op":"append_under_heading","heading":"Todo","content":"x"},
+                {"op":"regex_replace","pattern":"a+","replacement":"b"}]"#,
+        ).unwrap();
+        assert_eq!(ops, vec![
+            EditOp::append_under_heading("Todo", None, "x"),
+            EditOp::regex_replace("a+", "b", 1),
+        ]);
+
+        assert!(EditOp::parse_list(r#"[{"op":"rewrite","content":"x"}]"#).is_err());
+        assert!(EditOp::parse_list(r#"[{"op":"replace","old":"a"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_validation_error_json() {
+        let err = ValidationError::MultipleMatches { pattern: "a".to_string(), count: 2 };
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({"kind": "MultipleMatches", "pattern": "a", "count": 2})
+        );
+    }
+
+    #[test]
+    fn test_versioned_write_detects_conflict() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("note.md");
+        fs::write(&path, "v1").unwrap();
+
+        let file = VersionedFile::read(&path).unwrap();
+        assert_eq!(file.version(), Some(content_version("v1").as_str()));
+
+        // Someone else writes in between
+        fs::write(&path, "v2").unwrap();
+        let err = file.write("mine").unwrap_err();
+        let conflict = ConflictError::from_io(&err).expect("conflict error");
+        assert_eq!(conflict.actual, Some(content_version("v2")));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_versioned_write_missing_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("new.md");
+
+        let file = VersionedFile::read(&path).unwrap();
+        assert!(!file.exists());
+        fs::write(&path, "created elsewhere").unwrap();
+        assert!(ConflictError::from_io(&file.write("mine").unwrap_err()).is_some());
+    }
+
+    #[test]
+    fn test_update_file_retries_on_conflict() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("TASK.md");
+        fs::write(&path, "a\n").unwrap();
+
+        let mut calls = 0;
+        let written = update_file(&path, false, |file| {
+            calls += 1;
+            if calls == 1 {
+                // Concurrent writer sneaks in after our read
+                fs::write(&path, "a\nb\n").unwrap();
+            }
+            apply_edits(file.content(), vec![EditOp::append("c")])
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }).unwrap();
+
+        assert_eq!(calls, 2);
+        assert_eq!(written.as_deref(), Some("a\nb\nc"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\nc");
+    }
+
+    #[test]
+    fn test_edit_file_validation_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("note.md");
+        fs::write(&path, "hello").unwrap();
+
+        let err = edit_file(&path, &[EditOp::replace("missing", "x")], false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(edit_file(&path, &[EditOp::replace("hello", "hi")], true).unwrap(), "hi");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("note.md.bak")).unwrap(), "hello");
+    }
 }
@@ -4,20 +4,78 @@
 //! - Note creation (permanent and daily)
 //! - Tag management
 //! - Task file operations
+//!
+//! Mutating operations hold the capsa lock (`lock.rs`) while they read and
+//! rewrite shared files.
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::ops::Deref;
 use chrono::{Local, DateTime, NaiveDateTime, TimeZone};
 
-use crate::{CapsaRef, EditOp, apply_edits, DEFAULT_EXTENSIONS};
+use crate::{CapsaRef, EditOp, apply_edits, extract_references};
+use crate::edit;
 use crate::util;
+use crate::config::{CapsaConfig, ConflictPolicy, ENV_TASKFILE};
+use crate::lock::CapsaLock;
 use crate::note_resolver;
+use crate::relink::{self, FileChange};
+use crate::tag_sync::{self, DriftKind, SyncOptions, TagIndex, TagSync};
+use crate::trash::Trash;
+use crate::frontmatter;
+use crate::search::SearchIndex;
 use crate::constants as C;
 
 // === CapsaEngine ===
 
+/// How a note file ended up on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CreateAction {
+    /// Written at the requested path
+    Created,
+    /// Requested path was taken; written under a suffixed name
+    Renamed,
+    /// Requested path was taken; content appended to the existing note
+    Appended,
+}
+
+/// Result of creating a note
+#[derive(Debug, Clone)]
+pub struct CreatedNote {
+    pub path: PathBuf,
+    pub action: CreateAction,
+}
+
+/// Result of deleting a note
+#[derive(Debug, Clone)]
+pub struct DeletedNote {
+    /// Original path of the note
+    pub path: PathBuf,
+    /// Trash entry id (for `trash restore`)
+    pub id: String,
+    /// Where the note now lives in the trash
+    pub trash_path: PathBuf,
+    /// Tags the note was removed from
+    pub tags: Vec<String>,
+    /// Tasks still referencing the note (only with force)
+    pub tasks: Vec<String>,
+}
+
+/// Result of post-processing a note edited outside emx-note
+#[derive(Debug, Clone, Default)]
+pub struct EditedNote {
+    /// Whether the content changed
+    pub changed: bool,
+    /// Tags added to the frontmatter, or inline with the `inline_tags`
+    /// setting (and to their tag indexes)
+    pub tags_added: Vec<String>,
+    /// Tags removed from the frontmatter or inline (and from their tag indexes)
+    pub tags_removed: Vec<String>,
+}
+
 /// Core engine for capsa operations
 pub struct CapsaEngine {
     inner: CapsaRef,
@@ -29,21 +87,46 @@ impl CapsaEngine {
         Self { inner: ref_ }
     }
 
+    /// Acquire the capsa lock for a read-modify-write cycle (released on drop)
+    pub fn lock(&self) -> io::Result<CapsaLock> {
+        CapsaLock::acquire(&self.inner.path)
+    }
+
+    /// Load the capsa settings (`.emx-note.yaml`)
+    pub fn config(&self) -> io::Result<CapsaConfig> {
+        CapsaConfig::load(&self.inner.path)
+    }
+
     // === Note Operations ===
 
-    /// Create a permanent note
+    /// Create a permanent note, applying the capsa's conflict policy
     pub fn create_permanent_note(
         &self,
         title: Option<&str>,
         source: Option<&str>,
         content: &str,
     ) -> io::Result<PathBuf> {
+        let policy = self.config()?.on_conflict;
+        self.create_permanent_note_with(title, source, content, policy)
+            .map(|created| created.path)
+    }
+
+    /// Create a permanent note with an explicit conflict policy
+    pub fn create_permanent_note_with(
+        &self,
+        title: Option<&str>,
+        source: Option<&str>,
+        content: &str,
+        policy: ConflictPolicy,
+    ) -> io::Result<CreatedNote> {
         let now = Local::now();
         let timestamp = now.format(C::DAILY_TIMESTAMP_FORMAT).to_string();
 
+        let config = self.config()?;
+
         // Generate filename
         let filename = if let Some(t) = title {
-            format!("{}{}", util::slugify(t), C::MARKDOWN_EXTENSION)
+            format!("{}{}", config.slugify(t), C::MARKDOWN_EXTENSION)
         } else {
             format!("{}{}", timestamp, C::MARKDOWN_EXTENSION)
         };
@@ -51,71 +134,215 @@ impl CapsaEngine {
         // Determine the directory path
         let note_dir = if let Some(src) = source {
             // With source: note/{hash}/
-            let hash = util::abbreviate_hash(&util::hash_source(src));
+            let hash = config.abbreviate_hash(&util::hash_source(src));
             self.inner.path.join(C::NOTE_SUBDIR).join(&hash)
         } else {
             // Without source: note/
             self.inner.path.join(C::NOTE_SUBDIR)
         };
 
-        // Create directory and note file
+        // Create directory and note file (never overwriting an existing note)
+        let _lock = self.lock()?;
         fs::create_dir_all(&note_dir)?;
-        let note_path = note_dir.join(&filename);
-
-        // Write the file
-        fs::write(&note_path, content)?;
+        let created = Self::place_note(&note_dir.join(&filename), content, policy, config.backup)?;
 
         // If source is provided, create a .source file with the original source string
         if let Some(src) = source {
             let source_file = note_dir.join(C::SOURCE_FILENAME);
-            fs::write(&source_file, src)?;
+            util::atomic_write(&source_file, src)?;
         }
 
-        Ok(note_path)
+        Ok(created)
     }
 
-    /// Create a daily note
+    /// Create a daily note, applying the capsa's conflict policy
     pub fn create_daily_note(
         &self,
         title: Option<&str>,
         content: &str,
     ) -> io::Result<PathBuf> {
+        let policy = self.config()?.on_conflict;
+        self.create_daily_note_with(title, content, policy)
+            .map(|created| created.path)
+    }
+
+    /// Create a daily note with an explicit conflict policy
+    pub fn create_daily_note_with(
+        &self,
+        title: Option<&str>,
+        content: &str,
+        policy: ConflictPolicy,
+    ) -> io::Result<CreatedNote> {
         let now = Self::get_timestamp();
         let date_str = now.format(C::DAILY_DATE_FORMAT).to_string();
         let time_str = now.format(C::DAILY_TIME_FORMAT).to_string();
         let date_display = now.format(C::DAILY_DATE_DISPLAY_FORMAT).to_string();
 
+        let config = self.config()?;
+
         // Use provided title or default
-        let title = title.unwrap_or(C::DEFAULT_DAILY_TITLE);
+        let title = title.unwrap_or(config.daily_title.as_str());
 
         // Create slug (empty if default title)
-        let slug = if title == C::DEFAULT_DAILY_TITLE {
+        let slug = if title == config.daily_title {
             String::new()
         } else {
-            format!("-{}", util::slugify(title))
+            format!("-{}", config.slugify(title))
         };
 
         // Generate filename: HHmmSS[-title].md
         let filename = format!("{}{}{}", time_str, slug, C::MARKDOWN_EXTENSION);
 
-        // Create daily subdirectory: #daily/YYYYMMDD/
-        let daily_dir = self.inner.path.join(C::DAILY_SUBDIR).join(&date_str);
+        // Create daily subdirectory: #daily/YYYYMMDD/ (or the daily_dir setting)
+        let daily_dir = self.inner.path.join(&config.daily_dir).join(&date_str);
         fs::create_dir_all(&daily_dir)?;
 
-        // Create note file
-        let note_path = daily_dir.join(&filename);
+        // Create note file (never overwriting an existing note)
+        let _lock = self.lock()?;
+        let created = Self::place_note(&daily_dir.join(&filename), content, policy, config.backup)?;
 
-        // Write the file
-        fs::write(&note_path, content)?;
+        // Update daily link file (note/#daily.md), unless the note was already listed
+        if created.action != CreateAction::Appended {
+            let final_name = created.path.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(filename);
+            self.update_daily_link(&date_str, &date_display, &final_name, title)?;
+        }
 
-        // Update daily link file (note/#daily.md)
-        self.update_daily_link(&date_str, &date_display, &filename, title)?;
+        Ok(created)
+    }
+
+    /// Create a note with `create` and add it to the indexes of `tags` as one
+    /// transaction: the capsa lock is held throughout, and if a tag index
+    /// cannot be written, the tag files and the daily index are restored and
+    /// a newly created note is removed.
+    pub fn create_tagged<F>(&self, tags: &[String], create: F) -> io::Result<CreatedNote>
+    where
+        F: FnOnce(&Self) -> io::Result<CreatedNote>,
+    {
+        for tag in tags {
+            Tags::check_name(tag)?;
+        }
+
+        let _lock = self.lock()?;
+        let mut saved = Vec::new();
+        let files = tags.iter()
+            .map(|tag| self.tags().get(tag).file())
+            .chain([self.inner.path.join(C::NOTE_SUBDIR).join(C::DAILY_LINK_FILENAME)]);
+        for file in files {
+            let content = match fs::read_to_string(&file) {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            saved.push((file, content));
+        }
 
-        Ok(note_path)
+        let created = create(self)?;
+        for tag in tags {
+            if let Err(e) = self.tags().get(tag).add_entry(&created.path) {
+                for (file, content) in &saved {
+                    let _ = match content {
+                        Some(content) => util::atomic_write(file, content),
+                        None => fs::remove_file(file).or_else(|e| match e.kind() {
+                            io::ErrorKind::NotFound => Ok(()),
+                            _ => Err(e),
+                        }),
+                    };
+                }
+                if created.action != CreateAction::Appended {
+                    let _ = fs::remove_file(&created.path);
+                }
+                return Err(e);
+            }
+        }
+        Ok(created)
+    }
+
+    /// Write a new note at `path`, resolving a filename collision with `policy`
+    fn place_note(path: &Path, content: &str, policy: ConflictPolicy, backup: bool) -> io::Result<CreatedNote> {
+        match util::write_new_file(path, content) {
+            Ok(()) => {
+                return Ok(CreatedNote { path: path.to_path_buf(), action: CreateAction::Created });
+            }
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+            Err(_) => {}
+        }
+
+        match policy {
+            ConflictPolicy::Error => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Note already exists: {} (use --on-conflict suffix|timestamp|append)",
+                    util::display_path(path)
+                ),
+            )),
+            ConflictPolicy::Suffix => Self::place_with_suffix(path, content),
+            ConflictPolicy::Timestamp => {
+                let stamp = Self::get_timestamp().format(C::CONFLICT_TIMESTAMP_FORMAT).to_string();
+                let stamped = Self::suffixed_path(path, &stamp);
+                match util::write_new_file(&stamped, content) {
+                    Ok(()) => Ok(CreatedNote { path: stamped, action: CreateAction::Renamed }),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        Self::place_with_suffix(&stamped, content)
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            ConflictPolicy::Append => {
+                let existing = fs::read_to_string(path)?;
+                // Frontmatter of the new content goes into the existing note's
+                let (existing, content) = match frontmatter::split(content) {
+                    Some((yaml, body)) => match serde_yaml::from_str(yaml) {
+                        Ok(serde_yaml::Value::Mapping(fields)) => (frontmatter::merge(&existing, &fields)?, body),
+                        _ => (existing, content),
+                    },
+                    None => (existing, content),
+                };
+                let separator = if existing.is_empty() || existing.ends_with("\n\n") {
+                    ""
+                } else if existing.ends_with('\n') {
+                    "\n"
+                } else {
+                    "\n\n"
+                };
+                if backup {
+                    util::backup_file(path)?;
+                }
+                util::atomic_write(path, &format!("{}{}{}", existing, separator, content))?;
+                Ok(CreatedNote { path: path.to_path_buf(), action: CreateAction::Appended })
+            }
+        }
+    }
+
+    /// Write to the first free `{stem}-N{ext}` path (N = 2, 3, ...)
+    fn place_with_suffix(path: &Path, content: &str) -> io::Result<CreatedNote> {
+        for n in 2..=C::MAX_CONFLICT_SUFFIX {
+            let candidate = Self::suffixed_path(path, &n.to_string());
+            match util::write_new_file(&candidate, content) {
+                Ok(()) => return Ok(CreatedNote { path: candidate, action: CreateAction::Renamed }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("No free filename for {}", util::display_path(path)),
+        ))
+    }
+
+    /// Insert `-{suffix}` between file stem and extension
+    fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, suffix, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, suffix),
+        };
+        path.with_file_name(name)
     }
 
     /// Get current timestamp, allowing override via EMX_TASK_TIMESTAMP for testing
-    fn get_timestamp() -> DateTime<Local> {
+    pub(crate) fn get_timestamp() -> DateTime<Local> {
         if let Ok(ts) = std::env::var("EMX_TASK_TIMESTAMP") {
             // Parse "YYYY-MM-DD HH:MM" format
             if let Ok(naive) = NaiveDateTime::parse_from_str(&ts, "%Y-%m-%d %H:%M") {
@@ -138,10 +365,11 @@ impl CapsaEngine {
         let daily_link_path = note_dir.join(C::DAILY_LINK_FILENAME);
 
         // Ensure note/ directory exists
+        let _lock = self.lock()?;
         fs::create_dir_all(&note_dir)?;
 
         // The link line (unique content to append)
-        let link_line = format!("- [{}](#daily/{}/{})", title, date_str, filename);
+        let link_line = format!("- [{}]({}/{}/{})", title, self.config()?.daily_dir, date_str, filename);
 
         if daily_link_path.exists() {
             // Append to existing file using EditOp
@@ -149,11 +377,11 @@ impl CapsaEngine {
             let edits = vec![EditOp::append(&link_line)];
             let new_content = apply_edits(&content, edits)
                 .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            fs::write(&daily_link_path, new_content)?;
+            write_capsa_file(&self.inner, &daily_link_path, &new_content)?;
         } else {
             // Create new file with title and link
             let content = format!("# Daily Notes\n\n{}", link_line);
-            fs::write(&daily_link_path, content)?;
+            write_capsa_file(&self.inner, &daily_link_path, &content)?;
         }
 
         Ok(())
@@ -164,12 +392,356 @@ impl CapsaEngine {
         note_resolver::resolve_note_with_force(
             &self.inner.path,
             note_ref,
-            DEFAULT_EXTENSIONS,
+            &self.config()?.extensions(),
             force,
             "resolve note"
         )
     }
 
+    /// Move a note and rewrite every link and task reference pointing at it,
+    /// with `title` as the new title of a note renamed by title.
+    /// Returns the files whose content was rewritten.
+    pub fn move_note(&self, from: &Path, to: &Path, title: Option<&str>) -> io::Result<Vec<FileChange>> {
+        let _lock = self.lock()?;
+        if to.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Target already exists: {}", util::display_path(to)),
+            ));
+        }
+
+        let changes = relink::plan_move(&self.inner.path, from, to, title)?;
+
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from, to)?;
+
+        for change in &changes {
+            write_capsa_file(&self.inner, &change.path, &change.new_content)?;
+        }
+
+        Ok(changes)
+    }
+
+    /// Delete a note: move it to the trash and remove it from tag and daily indexes.
+    ///
+    /// Refuses if tasks reference the note unless `force` is set; task
+    /// references are kept either way, as they are part of the task history.
+    pub fn delete_note(&self, path: &Path, force: bool) -> io::Result<DeletedNote> {
+        let _lock = self.lock()?;
+        let relative = path.strip_prefix(&self.inner.path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let tasks = self.task_file().references_to(path)?;
+        if !tasks.is_empty() && !force {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Note '{}' is referenced by {}\nHint: Use --force to delete it anyway (task references are kept)",
+                    relative,
+                    tasks.join(", ")
+                ),
+            ));
+        }
+
+        let trash = self.trash();
+        let mut entry = trash.put(path)?;
+
+        for tag_name in self.tags().list()? {
+            if self.tags().get(&tag_name).remove_entry(&relative)? {
+                entry.tags.push(tag_name);
+            }
+        }
+        entry.daily_title = self.remove_daily_link(&relative)?;
+        trash.save(&entry)?;
+
+        Ok(DeletedNote {
+            path: path.to_path_buf(),
+            trash_path: trash.file(&entry),
+            id: entry.id,
+            tags: entry.tags,
+            tasks,
+        })
+    }
+
+    /// Restore a note from the trash to its original path and re-register it
+    /// in the tag and daily indexes it was removed from
+    pub fn restore_note(&self, id: &str) -> io::Result<PathBuf> {
+        let _lock = self.lock()?;
+        let trash = self.trash();
+        let entry = trash.get(id)?;
+
+        let path = util::secure_path(&self.inner.path, &entry.path)?;
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Cannot restore: {} already exists", util::display_path(&path)),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(trash.file(&entry), &path)?;
+
+        for tag_name in &entry.tags {
+            self.tags().get(tag_name).add_entry(&path)?;
+        }
+        if let Some(title) = &entry.daily_title {
+            let daily_dir = self.config()?.daily_dir;
+            let parts: Vec<&str> = entry.path.split('/').collect();
+            if let [dir, date, filename] = parts.as_slice() {
+                if *dir == daily_dir {
+                    self.update_daily_link(date, "", filename, title)?;
+                }
+            }
+        }
+
+        trash.remove(id)?;
+        Ok(path)
+    }
+
+    /// Post-process a note after it was edited (e.g. in $EDITOR).
+    ///
+    /// `before` is the content prior to the edit. If it changed: tag indexes
+    /// follow the frontmatter `tags`, the frontmatter `modified` field is bumped
+    /// (notes without frontmatter are left alone) and the search index is updated.
+    pub fn finish_edit(&self, path: &Path, before: &str) -> io::Result<EditedNote> {
+        let _lock = self.lock()?;
+        let after = fs::read_to_string(path)?;
+        if after == before {
+            return Ok(EditedNote::default());
+        }
+
+        let inline = self.config()?.inline_tags;
+        let old_tags = tag_sync::note_tags(before, inline);
+        let new_tags = tag_sync::note_tags(&after, inline);
+        let tags_added: Vec<String> = new_tags.iter().filter(|t| !old_tags.contains(t)).cloned().collect();
+        let tags_removed: Vec<String> = old_tags.iter().filter(|t| !new_tags.contains(t)).cloned().collect();
+
+        let relative = path.strip_prefix(&self.inner.path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        for tag_name in &tags_added {
+            self.tags().get(tag_name).add_entry(path)?;
+        }
+        for tag_name in &tags_removed {
+            self.tags().get(tag_name).remove_entry(&relative)?;
+        }
+
+        let modified = Self::get_timestamp().format(C::MODIFIED_TIME_FORMAT).to_string();
+        if let Some(bumped) = frontmatter::set_scalar(&after, C::MODIFIED_KEY, &modified) {
+            util::atomic_write(path, &bumped)?;
+        }
+
+        SearchIndex::open(&self.inner.path)?;
+
+        Ok(EditedNote { changed: true, tags_added, tags_removed })
+    }
+
+    /// Tag a note: add `tags` to its frontmatter `tags` and the note to their
+    /// tag indexes. Returns the tag files that changed.
+    pub fn tag_note(&self, path: &Path, tags: &[String]) -> io::Result<Vec<PathBuf>> {
+        for tag in tags {
+            Tags::check_name(tag)?;
+        }
+        let tags: Vec<String> = tags.iter().map(|tag| tag.trim_start_matches('#').to_string()).collect();
+
+        let _lock = self.lock()?;
+        self.update_note_tags(path, |current| {
+            for tag in &tags {
+                if !current.contains(tag) {
+                    current.push(tag.clone());
+                }
+            }
+        })?;
+
+        let mut changed = Vec::new();
+        for tag_name in &tags {
+            let tag = self.tags().get(tag_name);
+            if tag.add_entry(path)? {
+                changed.push(tag.file());
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Untag a note: remove `tags` from its frontmatter `tags` and the note
+    /// from their tag indexes. Returns the tag files that changed.
+    pub fn untag_note(&self, path: &Path, tags: &[String]) -> io::Result<Vec<PathBuf>> {
+        let tags: Vec<String> = tags.iter().map(|tag| tag.trim_start_matches('#').to_string()).collect();
+        let relative = path.strip_prefix(&self.inner.path)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        let _lock = self.lock()?;
+        self.update_note_tags(path, |current| current.retain(|tag| !tags.contains(tag)))?;
+
+        let mut changed = Vec::new();
+        for tag_name in &tags {
+            let tag = self.tags().get(tag_name);
+            if tag.remove_entry(&relative)? {
+                changed.push(tag.file());
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Compare the frontmatter tags of the notes with the tag files and,
+    /// with `options.apply`, add the missing entries (and with `prune`
+    /// remove the stale ones)
+    pub fn sync_tags(&self, options: SyncOptions) -> io::Result<TagSync> {
+        let _lock = self.lock()?;
+        let (index, mut result) = self.backfill_tags(&self.config()?, options)?;
+        result.drift = index.drift();
+        result.drift.retain(|d| !result.backfilled.contains(d));
+
+        for item in &result.drift {
+            let tag = self.tags().get(&item.tag);
+            let changed = match item.kind {
+                DriftKind::Missing => !options.apply || tag.add_entry(&self.inner.path.join(&item.path))?,
+                DriftKind::Stale if options.prune => !options.apply || tag.remove_entry(&item.path)?,
+                DriftKind::Stale => false,
+            };
+            if changed && !result.files.contains(&tag.file()) {
+                result.files.push(tag.file());
+            }
+        }
+        Ok(result)
+    }
+
+    /// Regenerate every tag file from the frontmatter tags of the notes
+    /// (written with `options.apply`). Notes already indexed keep their
+    /// date; stale entries are kept unless `prune`.
+    pub fn rebuild_tags(&self, options: SyncOptions) -> io::Result<TagSync> {
+        let _lock = self.lock()?;
+        let config = self.config()?;
+        let (index, mut result) = self.backfill_tags(&config, options)?;
+        let today = Self::get_timestamp().format(C::DAILY_DATE_DISPLAY_FORMAT).to_string();
+
+        for (tag_name, content) in index.rebuild(&config, &today, options.prune) {
+            let tag_file = self.tags().get(&tag_name).file();
+            let current = fs::read_to_string(&tag_file).ok();
+            if current == content {
+                continue;
+            }
+            if options.apply {
+                match &content {
+                    Some(content) => write_capsa_file(&self.inner, &tag_file, content)?,
+                    None => {
+                        if config.backup {
+                            util::backup_file(&tag_file)?;
+                        }
+                        fs::remove_file(&tag_file)?;
+                    }
+                }
+            }
+            result.files.push(tag_file);
+        }
+        result.drift = index.drift();
+        result.drift.retain(|d| !result.backfilled.contains(d));
+        Ok(result)
+    }
+
+    /// Load the tag index and, with `options.from_index`, tag the notes
+    /// listed in tag files but not tagged in their frontmatter (tag files
+    /// written before tags were kept there). Returns the index, reloaded
+    /// after the backfill, and the backfilled entries and notes.
+    fn backfill_tags(&self, config: &CapsaConfig, options: SyncOptions) -> io::Result<(TagIndex, TagSync)> {
+        let index = TagIndex::load(&self.inner.path, config)?;
+        let mut result = TagSync::default();
+        if !options.from_index {
+            return Ok((index, result));
+        }
+
+        result.backfilled = index.backfill();
+        let mut by_note: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for item in &result.backfilled {
+            by_note.entry(&item.path).or_default().push(item.tag.clone());
+        }
+        for (relative, tags) in by_note {
+            let path = self.inner.path.join(relative);
+            if options.apply {
+                self.update_note_tags(&path, |current| {
+                    for tag in &tags {
+                        if !current.contains(tag) {
+                            current.push(tag.clone());
+                        }
+                    }
+                })?;
+            }
+            result.notes.push(path);
+        }
+
+        let index = if options.apply && !result.notes.is_empty() {
+            TagIndex::load(&self.inner.path, config)?
+        } else {
+            index
+        };
+        Ok((index, result))
+    }
+
+    /// Rewrite the frontmatter `tags` of a note with `update`, if it changes them
+    fn update_note_tags<F>(&self, path: &Path, mut update: F) -> io::Result<()>
+    where
+        F: FnMut(&mut Vec<String>),
+    {
+        let backup = self.config()?.backup;
+        edit::update_file(path, backup, |file| {
+            let content = file.content();
+            if frontmatter::extract(content).is_some() && frontmatter::parse(content).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid YAML in frontmatter of {}", util::display_path(path)),
+                ));
+            }
+            let before = frontmatter::tags(content);
+            let mut tags = before.clone();
+            update(&mut tags);
+            Ok((tags != before).then(|| frontmatter::set_tags(content, &tags)))
+        })?;
+        Ok(())
+    }
+
+    /// Remove a note's entry from the daily link file (note/#daily.md).
+    /// Returns the entry's title if there was one.
+    fn remove_daily_link(&self, note_relative: &str) -> io::Result<Option<String>> {
+        let daily_link_path = self.inner.path.join(C::NOTE_SUBDIR).join(C::DAILY_LINK_FILENAME);
+        let _lock = self.lock()?;
+        if !daily_link_path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&daily_link_path)?;
+        let link_line = match content.lines().find(|line| line.contains(&format!("]({})", note_relative))) {
+            Some(line) => line.to_string(),
+            None => return Ok(None),
+        };
+
+        let new_content = apply_edits(&content, vec![EditOp::delete_line(&link_line)])
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        write_capsa_file(&self.inner, &daily_link_path, &new_content)?;
+
+        // "- [Title](#daily/...)" → "Title"
+        let title = link_line.trim_start_matches("- [")
+            .split("](")
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        Ok(Some(title))
+    }
+
+    // === Trash ===
+
+    /// Get the trash bin for this capsa
+    pub fn trash(&self) -> Trash<'_> {
+        Trash::new(&self.inner)
+    }
+
     // === Tags Collection ===
 
     /// Get the Tags collection for this capsa
@@ -185,6 +757,15 @@ impl CapsaEngine {
     }
 }
 
+/// Write a file in a capsa atomically, keeping a `.bak` of the previous
+/// version if the capsa settings enable backups
+fn write_capsa_file(capsa: &CapsaRef, path: &Path, content: &str) -> io::Result<()> {
+    if CapsaConfig::load(&capsa.path)?.backup {
+        util::backup_file(path)?;
+    }
+    util::atomic_write(path, content)
+}
+
 impl Deref for CapsaEngine {
     type Target = CapsaRef;
     fn deref(&self) -> &Self::Target {
@@ -200,6 +781,18 @@ pub struct Tags<'a> {
 }
 
 impl<'a> Tags<'a> {
+    /// Check a tag name (with or without `#`) before creating its tag file
+    pub fn check_name(tag: &str) -> io::Result<()> {
+        let name = tag.trim_start_matches('#');
+        if name.is_empty() || name.starts_with('.') || name.contains(|c: char| c.is_whitespace() || c == '/' || c == '\\') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid tag '{}' (expected a single word without slashes)", tag),
+            ));
+        }
+        Ok(())
+    }
+
     /// Get a specific tag
     pub fn get(&self, tag: &str) -> Tag<'a> {
         Tag {
@@ -252,64 +845,88 @@ impl<'a> Tag<'a> {
             .to_string_lossy()
             .replace('\\', "/");
 
+        if self.add_note_internal(&relative, note_path)? {
+            // Output tag file path
+            println!("{}", util::display_path(&self.file()));
+        }
+        Ok(())
+    }
+
+    /// Add a note to this tag without output.
+    /// Returns false if the note was already tagged.
+    pub fn add_entry(&self, note_path: &Path) -> io::Result<bool> {
+        let relative = note_path.strip_prefix(&self.capsa.path)
+            .unwrap_or(note_path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
         self.add_note_internal(&relative, note_path)
     }
 
     /// Internal add note logic
-    fn add_note_internal(&self, note_relative: &str, note_path: &PathBuf) -> io::Result<()> {
+    fn add_note_internal(&self, note_relative: &str, note_path: &Path) -> io::Result<bool> {
         let tag_file = self.file();
-        let now = Local::now();
-        let date_display = now.format(C::DAILY_DATE_DISPLAY_FORMAT).to_string();
+        let date_display = CapsaEngine::get_timestamp().format(C::DAILY_DATE_DISPLAY_FORMAT).to_string();
 
         // Get note title
         let note_title = Self::extract_note_title(note_path)?;
 
         // The link line is our unique source locator for checking duplicates
-        let link_line = format!("- [{}]({})", note_title, note_relative);
+        let link_line = CapsaConfig::load(&self.capsa.path)?.tag_link(&note_title, note_relative);
 
+        let _lock = CapsaLock::acquire(&self.capsa.path)?;
         if tag_file.exists() {
             // Check if note already tagged
             let content = fs::read_to_string(&tag_file)?;
             if content.lines().any(|line| line.contains(&format!("]({})", note_relative))) {
-                return Ok(()); // Already tagged, silently skip
+                return Ok(false); // Already tagged, silently skip
             }
 
             // Build edits to add date header (if needed) and link
             let date_header = format!("## {}", date_display);
             let has_date_header = content.lines().any(|line| line == date_header);
 
-            let mut edits = Vec::new();
-
-            if !has_date_header {
+            let edits = if has_date_header {
+                // File the link under today's date section
+                vec![EditOp::append_under_heading(&date_display, Some(2), link_line)]
+            } else {
                 // Need to add date header first
-                edits.push(EditOp::append(format!("\n{}", date_header)));
-            }
-
-            // Add the link line (unique source locator is the link content)
-            edits.push(EditOp::append(link_line));
+                vec![
+                    EditOp::append(format!("\n{}", date_header)),
+                    EditOp::append(link_line),
+                ]
+            };
 
             let new_content = apply_edits(&content, edits)
                 .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-            fs::write(&tag_file, new_content)?;
+            write_capsa_file(self.capsa, &tag_file, &new_content)?;
         } else {
             // Create new tag file with heading, date header, and link
-            let content = format!("# {}\n\n{}\n{}", self.name, date_display, link_line);
-            fs::write(&tag_file, content)?;
+            let content = format!("# {}\n\n## {}\n{}\n", self.name, date_display, link_line);
+            write_capsa_file(self.capsa, &tag_file, &content)?;
         }
 
-        // Output tag file path
-        println!("{}", util::display_path(&tag_file));
-
-        Ok(())
+        Ok(true)
     }
 
     /// Remove a note from this tag
     pub fn remove_note(&self, note_relative: &str) -> io::Result<()> {
+        if self.remove_entry(note_relative)? {
+            // Output tag file path
+            println!("{}", util::display_path(&self.file()));
+        }
+        Ok(())
+    }
+
+    /// Remove a note's entry from this tag without output.
+    /// Returns whether the note was tagged.
+    pub fn remove_entry(&self, note_relative: &str) -> io::Result<bool> {
         let tag_file = self.file();
 
+        let _lock = CapsaLock::acquire(&self.capsa.path)?;
         if !tag_file.exists() {
             // Tag doesn't exist, silently succeed (idempotent)
-            return Ok(());
+            return Ok(false);
         }
 
         let content = fs::read_to_string(&tag_file)?;
@@ -321,35 +938,27 @@ impl<'a> Tag<'a> {
 
         let link_line = match line_to_delete {
             Some(line) => line.to_string(),
-            None => return Ok(()), // Note wasn't in this tag, silently succeed
+            None => return Ok(false), // Note wasn't in this tag, silently succeed
         };
 
-        // Step 2: Find the date header for this link (to check if section becomes empty)
-        let mut date_to_delete: Option<String> = None;
-        let mut current_date_header: Option<String> = None;
-        let mut links_count = 0;
-
+        // Step 2: Find the date header of the link's section, deleted with the
+        // link if it was the section's only entry. A header is `## YYYY-MM-DD`
+        // or, in tag files written before date headings, a bare date line.
+        // Each section: (header, other links, whether it holds the link)
+        let mut sections: Vec<(Option<&str>, usize, bool)> = vec![(None, 0, false)];
         for line in content.lines() {
-            if line.starts_with("## ") {
-                // Check previous section
-                if links_count == 0 && current_date_header.is_some() {
-                    // Previous section was already empty (shouldn't happen but handle it)
-                }
-                current_date_header = Some(line.to_string());
-                links_count = 0;
-            } else if line.starts_with("- [") {
-                if line == link_line {
-                    // This is the line we're deleting, don't count it
-                } else {
-                    links_count += 1;
-                }
+            let section = sections.last_mut().expect("at least one section");
+            if line.starts_with("## ") || tag_sync::date_heading(line).is_some() {
+                sections.push((Some(line), 0, false));
+            } else if line == link_line {
+                section.2 = true;
+            } else if line.contains("](") {
+                section.1 += 1;
             }
         }
-
-        // After the loop, check if current section becomes empty
-        if links_count == 0 {
-            date_to_delete = current_date_header;
-        }
+        let date_to_delete = sections.iter()
+            .find(|(_, others, holds_link)| *holds_link && *others == 0)
+            .and_then(|(header, _, _)| header.map(str::to_string));
 
         // Step 3: Build edits - collect all lines to delete
         let mut edits = Vec::new();
@@ -372,15 +981,15 @@ impl<'a> Tag<'a> {
             .collect();
 
         if non_empty.is_empty() || (non_empty.len() == 1 && non_empty[0].starts_with('#')) {
+            if CapsaConfig::load(&self.capsa.path)?.backup {
+                util::backup_file(&tag_file)?;
+            }
             fs::remove_file(&tag_file)?;
         } else {
-            fs::write(&tag_file, &new_content)?;
+            write_capsa_file(self.capsa, &tag_file, &new_content)?;
         }
 
-        // Output tag file path
-        println!("{}", util::display_path(&tag_file));
-
-        Ok(())
+        Ok(true)
     }
 
     /// List notes in this tag
@@ -438,14 +1047,14 @@ pub struct TaskFile<'a> {
 }
 
 impl<'a> TaskFile<'a> {
-    /// Get the task file path
+    /// Get the task file path: `EMX_TASKFILE`, then the `task_file` setting
     pub fn file(&self) -> PathBuf {
-        // Allow override via environment variable
-        if let Ok(filename) = std::env::var("EMX_TASKFILE") {
-            self.capsa.path.join(filename)
-        } else {
-            self.capsa.path.join(C::TASK_FILENAME)
-        }
+        let filename = match CapsaConfig::load(&self.capsa.path) {
+            Ok(config) => config.task_file,
+            // Invalid settings are reported by the commands that load them
+            Err(_) => std::env::var(ENV_TASKFILE).unwrap_or_else(|_| C::TASK_FILENAME.to_string()),
+        };
+        self.capsa.path.join(filename)
     }
 
     /// Get current timestamp for comments
@@ -472,29 +1081,71 @@ impl<'a> TaskFile<'a> {
         if path.exists() {
             fs::read_to_string(&path)
         } else {
-            // Return default empty TASK.md content
-            // Format: frontmatter, blank line, body separator, blank line for references
-            Ok(
-r#"---
-PREFIX: TASK-
----
+            self.default_content()
+        }
+    }
 
----
+    /// IDs of tasks whose note reference resolves to `note_path`
+    pub fn references_to(&self, note_path: &Path) -> io::Result<Vec<String>> {
+        let path = self.file();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
 
-"#.to_string())
+        let content = fs::read_to_string(&path)?;
+        let config = CapsaConfig::load(&self.capsa.path)?;
+        let extensions = config.extensions();
+        let mut tasks = Vec::new();
+        for (task_id, node_ref) in extract_references(&content) {
+            let resolved = note_resolver::resolve_note(&self.capsa.path, &node_ref, &extensions)?;
+            if matches!(resolved, note_resolver::ResolvedNote::Found(ref p) if p == note_path) {
+                tasks.push(task_id);
+            }
         }
+        Ok(tasks)
+    }
+
+    /// Default empty TASK.md content, with the `task_prefix` setting as `PREFIX`
+    /// Format: frontmatter, blank line, body separator, blank line for references
+    fn default_content(&self) -> io::Result<String> {
+        let prefix = CapsaConfig::load(&self.capsa.path)?.task_prefix;
+        Ok(format!("---\nPREFIX: {}\n---\n\n---\n\n", prefix))
+    }
+
+    /// Read-modify-write TASK.md under the capsa lock with optimistic concurrency.
+    ///
+    /// `update` gets the current content (default content if the file is missing)
+    /// and returns the new content, or None to leave the file alone. If TASK.md is
+    /// changed by someone else before the write (e.g. edited by hand), it is re-read
+    /// and `update` runs again on the fresh content.
+    pub fn update<F>(&self, mut update: F) -> io::Result<Option<String>>
+    where
+        F: FnMut(&str) -> io::Result<Option<String>>,
+    {
+        let _lock = CapsaLock::acquire(&self.capsa.path)?;
+        let backup = CapsaConfig::load(&self.capsa.path)?.backup;
+        let default_content = self.default_content()?;
+        edit::update_file(&self.file(), backup, |file| {
+            if file.exists() {
+                update(file.content())
+            } else {
+                update(&default_content)
+            }
+        })
     }
 
-    /// Save content to TASK.md
+    /// Save content to TASK.md.
+    /// Callers doing read-modify-write should hold `CapsaEngine::lock` since the load.
     pub fn save(&self, content: &str) -> io::Result<()> {
         let path = self.file();
+        let _lock = CapsaLock::acquire(&self.capsa.path)?;
 
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        fs::write(&path, content)
+        write_capsa_file(self.capsa, &path, content)
     }
 }
 
@@ -661,6 +1312,108 @@ mod tests {
         assert!(note_path.exists());
     }
 
+    #[test]
+    fn test_create_permanent_note_conflict_policies() {
+        clean_env();
+
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+
+        let first = engine.create_permanent_note(Some("Idea"), None, "first").unwrap();
+
+        // Default policy refuses to overwrite
+        let err = engine.create_permanent_note(Some("Idea"), None, "second").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first");
+
+        let created = engine.create_permanent_note_with(Some("Idea"), None, "second", ConflictPolicy::Suffix).unwrap();
+        assert_eq!(created.action, CreateAction::Renamed);
+        assert!(created.path.ends_with("note/idea-2.md"));
+        let created = engine.create_permanent_note_with(Some("Idea"), None, "third", ConflictPolicy::Suffix).unwrap();
+        assert!(created.path.ends_with("note/idea-3.md"));
+
+        let created = engine.create_permanent_note_with(Some("Idea"), None, "more", ConflictPolicy::Append).unwrap();
+        assert_eq!(created.action, CreateAction::Appended);
+        assert_eq!(created.path, first);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first\n\nmore");
+    }
+
+    #[test]
+    fn test_create_tagged() {
+        clean_env();
+
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+        let tags = vec!["rust".to_string(), "cli".to_string()];
+
+        let created = engine.create_tagged(&tags, |engine| {
+            engine.create_permanent_note_with(Some("Idea"), None, "# Idea\n", ConflictPolicy::Error)
+        }).unwrap();
+        assert!(fs::read_to_string(temp_dir.path().join("#rust.md")).unwrap().contains("](note/idea.md)"));
+        assert!(fs::read_to_string(temp_dir.path().join("#cli.md")).unwrap().contains("](note/idea.md)"));
+
+        // Appending merges the new frontmatter into the existing note's
+        fs::write(&created.path, "---\ntags: [rust]\n---\n# Idea\n").unwrap();
+        engine.create_permanent_note_with(Some("Idea"), None, "---\ntags: [cli]\n---\nMore\n", ConflictPolicy::Append).unwrap();
+        assert_eq!(fs::read_to_string(&created.path).unwrap(), "---\ntags:\n- rust\n- cli\n---\n# Idea\n\nMore\n");
+
+        // A tag index that cannot be written undoes the whole creation
+        let rust_before = fs::read_to_string(temp_dir.path().join("#rust.md")).unwrap();
+        fs::create_dir(temp_dir.path().join("#broken.md")).unwrap();
+        let tags = vec!["rust".to_string(), "broken".to_string()];
+        assert!(engine.create_tagged(&tags, |engine| {
+            engine.create_permanent_note_with(Some("Other"), None, "# Other\n", ConflictPolicy::Error)
+        }).is_err());
+        assert!(!temp_dir.path().join("note/other.md").exists());
+        assert_eq!(fs::read_to_string(temp_dir.path().join("#rust.md")).unwrap(), rust_before);
+
+        // Invalid tag names are refused before anything is written
+        let err = engine.create_tagged(&["two words".to_string()], |engine| {
+            engine.create_permanent_note_with(Some("Third"), None, "", ConflictPolicy::Error)
+        }).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!temp_dir.path().join("note/third.md").exists());
+    }
+
+    #[test]
+    fn test_create_daily_note_timestamp_conflict() {
+        clean_env();
+
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+
+        std::env::set_var("EMX_TASK_TIMESTAMP", "2024-01-15 10:30");
+        engine.create_daily_note(Some("Standup"), "a").unwrap();
+        let created = engine.create_daily_note_with(Some("Standup"), "b", ConflictPolicy::Timestamp).unwrap();
+        std::env::remove_var("EMX_TASK_TIMESTAMP");
+
+        assert_eq!(created.action, CreateAction::Renamed);
+        assert!(created.path.ends_with("#daily/20240115/103000-standup-20240115103000.md"));
+
+        // Both notes are listed in the daily index
+        let index = fs::read_to_string(temp_dir.path().join("note/#daily.md")).unwrap();
+        assert!(index.contains("(#daily/20240115/103000-standup.md)"));
+        assert!(index.contains("(#daily/20240115/103000-standup-20240115103000.md)"));
+    }
+
     // === Tags Tests ===
 
     #[test]
@@ -859,6 +1612,114 @@ mod tests {
         assert_eq!(notes.len(), 2);
     }
 
+    #[test]
+    fn test_tag_note_frontmatter() {
+        let temp_dir = TempDir::new().unwrap();
+        let note_path = temp_dir.path().join("note.md");
+        fs::write(&note_path, "---\ntitle: Note\n---\n# Note\n").unwrap();
+
+        let engine = CapsaEngine::new(CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        });
+
+        let changed = engine.tag_note(&note_path, &["rust".to_string(), "#cli".to_string()]).unwrap();
+        assert_eq!(changed.len(), 2);
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "---\ntitle: Note\ntags: [rust, cli]\n---\n# Note\n");
+        assert!(engine.sync_tags(SyncOptions::default()).unwrap().drift.is_empty());
+
+        // Already tagged: nothing changes
+        assert!(engine.tag_note(&note_path, &["rust".to_string()]).unwrap().is_empty());
+
+        engine.untag_note(&note_path, &["rust".to_string()]).unwrap();
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "---\ntitle: Note\ntags: [cli]\n---\n# Note\n");
+        assert!(!temp_dir.path().join("#rust.md").exists());
+    }
+
+    #[test]
+    fn test_sync_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let note_path = temp_dir.path().join("note.md");
+        fs::write(&note_path, "---\ntags: [rust]\n---\n# Note\n").unwrap();
+        fs::write(temp_dir.path().join("#old.md"), "# old\n\n## 2026-01-02\n- [Note](note.md)\n").unwrap();
+
+        let engine = CapsaEngine::new(CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        });
+
+        // Dry run: reported, not applied
+        let result = engine.sync_tags(SyncOptions { prune: true, ..Default::default() }).unwrap();
+        assert_eq!(result.drift.len(), 2);
+        assert_eq!(result.files.len(), 2);
+        assert!(!temp_dir.path().join("#rust.md").exists());
+
+        // Stale entries are kept without prune
+        let result = engine.sync_tags(SyncOptions { apply: true, ..Default::default() }).unwrap();
+        assert_eq!(result.files, vec![temp_dir.path().join("#rust.md")]);
+        assert!(temp_dir.path().join("#old.md").exists());
+
+        engine.sync_tags(SyncOptions { apply: true, prune: true, ..Default::default() }).unwrap();
+        assert!(!temp_dir.path().join("#old.md").exists());
+        assert!(engine.sync_tags(SyncOptions::default()).unwrap().drift.is_empty());
+        assert!(engine.rebuild_tags(SyncOptions::default()).unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn test_sync_tags_baseline_tag_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let note_path = temp_dir.path().join("legacy.md");
+        fs::write(&note_path, "# Legacy\n").unwrap();
+        // As written by `tag add` before tags were kept in the frontmatter
+        let tag_file = temp_dir.path().join("#rust.md");
+        fs::write(&tag_file, "# rust\n\n2025-01-10\n- [Legacy](legacy.md)").unwrap();
+
+        let engine = CapsaEngine::new(CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        });
+
+        // Neither sync nor rebuild drops the entry without prune
+        let apply = SyncOptions { apply: true, ..Default::default() };
+        assert_eq!(engine.sync_tags(apply).unwrap().drift.len(), 1);
+        engine.rebuild_tags(apply).unwrap();
+        assert_eq!(fs::read_to_string(&tag_file).unwrap(), "# rust\n\n## 2025-01-10\n- [Legacy](legacy.md)\n");
+
+        // --from-index tags the note, after which nothing differs
+        let result = engine.sync_tags(SyncOptions { from_index: true, ..apply }).unwrap();
+        assert_eq!(result.backfilled.len(), 1);
+        assert_eq!(result.notes, vec![note_path.clone()]);
+        assert!(result.drift.is_empty());
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "---\ntags: [rust]\n---\n# Legacy\n");
+        assert!(engine.sync_tags(SyncOptions { prune: true, ..apply }).unwrap().drift.is_empty());
+        assert!(tag_file.exists());
+    }
+
+    #[test]
+    fn test_tag_remove_bare_date_heading() {
+        let temp_dir = TempDir::new().unwrap();
+        let tag_file = temp_dir.path().join("#rust.md");
+        fs::write(&tag_file, "# rust\n\n2025-01-10\n- [Gone](gone.md)\n\n## 2025-02-01\n- [Kept](kept.md)\n").unwrap();
+
+        let engine = CapsaEngine::new(CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        });
+
+        assert!(engine.tags().get("rust").remove_entry("gone.md").unwrap());
+        let content = fs::read_to_string(&tag_file).unwrap();
+        assert!(!content.contains("2025-01-10"));
+        assert!(content.contains("## 2025-02-01\n- [Kept](kept.md)"));
+    }
+
     // === TaskFile Tests ===
 
     #[test]
@@ -945,6 +1806,149 @@ PREFIX: CUSTOM-
         assert!(loaded.contains("PREFIX: CUSTOM-"));
     }
 
+    #[test]
+    fn test_task_file_update() {
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+        let task_file = engine.task_file();
+        let path = temp_dir.path().join("TASK.md");
+
+        // Missing file: update starts from the default content
+        let written = task_file.update(|content| {
+            assert!(content.contains("PREFIX: TASK-"));
+            Ok(Some(format!("{}[TASK-01]: note-a\n", content)))
+        }).unwrap();
+        assert!(written.unwrap().ends_with("[TASK-01]: note-a\n"));
+
+        // A hand edit between read and write is not lost: the update is re-applied
+        let mut calls = 0;
+        task_file.update(|content| {
+            calls += 1;
+            if calls == 1 {
+                fs::write(&path, format!("{}[TASK-02]: note-b\n", content)).unwrap();
+            }
+            Ok(Some(format!("{}[TASK-03]: note-c\n", content)))
+        }).unwrap();
+        assert_eq!(calls, 2);
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("[TASK-02]: note-b"));
+        assert!(content.contains("[TASK-03]: note-c"));
+
+        // None leaves the file alone
+        assert!(task_file.update(|_| Ok(None)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_delete_note() {
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+        let note = engine.create_permanent_note(Some("Idea"), None, "# Idea\n").unwrap();
+        let other = engine.create_permanent_note(Some("Other"), None, "# Other\n").unwrap();
+        engine.tags().get("rust").add_note(&note).unwrap();
+        engine.tags().get("rust").add_note(&other).unwrap();
+        engine.task_file().save("---\nPREFIX: TASK-\n---\n\n---\n\n[TASK-01]: idea\n").unwrap();
+
+        // Referenced by a task: refused, nothing touched
+        let err = engine.delete_note(&note, false).unwrap_err();
+        assert!(err.to_string().contains("TASK-01"));
+        assert!(note.exists());
+
+        let deleted = engine.delete_note(&note, true).unwrap();
+        assert!(!note.exists());
+        assert!(deleted.trash_path.exists());
+        assert!(deleted.trash_path.ends_with("note/idea.md"));
+        assert!(deleted.trash_path.starts_with(temp_dir.path().join(C::TRASH_DIR)));
+        assert_eq!(deleted.tags, vec!["rust".to_string()]);
+        assert_eq!(deleted.tasks, vec!["TASK-01".to_string()]);
+
+        let tag_content = fs::read_to_string(temp_dir.path().join("#rust.md")).unwrap();
+        assert!(!tag_content.contains("note/idea.md"));
+        assert!(tag_content.contains("note/other.md"));
+
+        // Restore puts the file and its tag entry back
+        let restored = engine.restore_note(&deleted.id).unwrap();
+        assert_eq!(restored, note);
+        assert!(note.exists());
+        let tag_content = fs::read_to_string(temp_dir.path().join("#rust.md")).unwrap();
+        assert!(tag_content.contains("note/idea.md"));
+        assert!(engine.trash().list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_finish_edit() {
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+        let before = "---\ntags: [rust]\n---\n# Idea\n";
+        let note = engine.create_permanent_note(Some("Idea"), None, before).unwrap();
+        engine.tags().get("rust").add_note(&note).unwrap();
+
+        // Unchanged: nothing happens
+        let edited = engine.finish_edit(&note, before).unwrap();
+        assert!(!edited.changed);
+        assert_eq!(fs::read_to_string(&note).unwrap(), before);
+
+        fs::write(&note, "---\ntags: [cli]\n---\n# Idea\n\nMore text\n").unwrap();
+        let edited = engine.finish_edit(&note, before).unwrap();
+        assert!(edited.changed);
+        assert_eq!(edited.tags_added, vec!["cli".to_string()]);
+        assert_eq!(edited.tags_removed, vec!["rust".to_string()]);
+
+        let content = fs::read_to_string(&note).unwrap();
+        assert!(content.contains("\nmodified: "));
+        assert!(fs::read_to_string(temp_dir.path().join("#cli.md")).unwrap().contains("note/idea.md"));
+        assert!(SearchIndex::file(temp_dir.path()).exists());
+    }
+
+    #[test]
+    fn test_backup_setting() {
+        let temp_dir = TempDir::new().unwrap();
+        let capsa_ref = CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        };
+        let engine = CapsaEngine::new(capsa_ref);
+        let task_file = engine.task_file();
+        let path = temp_dir.path().join("TASK.md");
+        let bak = temp_dir.path().join("TASK.md.bak");
+
+        // Backups are off by default
+        task_file.save("v1\n").unwrap();
+        task_file.save("v2\n").unwrap();
+        assert!(!bak.exists());
+
+        fs::write(CapsaConfig::file(temp_dir.path()), "backup: true\n").unwrap();
+        task_file.update(|content| Ok(Some(format!("{}v3\n", content)))).unwrap();
+        assert_eq!(fs::read_to_string(&bak).unwrap(), "v2\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v2\nv3\n");
+
+        // No temp files are left behind
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path()).unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
     #[test]
     fn test_task_file_get_timestamp() {
         clean_env();
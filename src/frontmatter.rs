@@ -0,0 +1,357 @@
+//! YAML frontmatter helpers
+//!
+//! Frontmatter is a `---` delimited YAML block at the very start of a note.
+//! Scalar updates are done line by line so the rest of the block keeps its
+//! formatting, order and comments.
+
+use std::io;
+use serde_yaml::{Mapping, Value};
+
+use crate::constants as C;
+
+/// Extract the YAML text of the frontmatter block (without the `---` lines)
+pub fn extract(content: &str) -> Option<&str> {
+    let rest = content.strip_prefix("---")?;
+    let end = rest.find("\n---")?;
+    let yaml = &rest[..end];
+    if yaml.len() > C::MAX_FRONTMATTER_SIZE {
+        return None;
+    }
+    Some(yaml.strip_prefix('\n').unwrap_or(yaml))
+}
+
+/// Split a note into the YAML text of its frontmatter and the body after the
+/// closing `---` line (None if the note has no frontmatter)
+pub fn split(content: &str) -> Option<(&str, &str)> {
+    let yaml = extract(content)?;
+    let closing = 3 + content[3..].find("\n---")? + 1;
+    let rest = &content[closing + 3..];
+    let body = match rest.find('\n') {
+        Some(end) if rest[..end].trim().is_empty() => &rest[end + 1..],
+        None if rest.trim().is_empty() => "",
+        _ => return None,
+    };
+    Some((yaml, body))
+}
+
+/// Parse the frontmatter as YAML (None if missing or invalid)
+pub fn parse(content: &str) -> Option<Value> {
+    serde_yaml::from_str(extract(content)?).ok()
+}
+
+/// Tags listed in the `tags` key (a list, or a comma/space separated string),
+/// without `#` prefixes
+pub fn tags(content: &str) -> Vec<String> {
+    match parse(content) {
+        Some(Value::Mapping(map)) => map.get(C::TAGS_KEY).map(tag_values).unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Tags of a `tags` value (a list, or a comma/space separated string),
+/// without `#` prefixes or duplicates
+pub fn tag_values(value: &Value) -> Vec<String> {
+    let raw: Vec<String> = match value {
+        Value::Sequence(items) => items.iter()
+            .filter_map(|item| match item {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+            .collect(),
+        Value::String(s) => s.split([',', ' ']).map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw {
+        let tag = tag.trim().trim_start_matches('#').to_string();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Set a top-level scalar key in existing frontmatter, replacing its line or
+/// adding one at the end of the block. Returns None if the note has no frontmatter.
+pub fn set_scalar(content: &str, key: &str, value: &str) -> Option<String> {
+    let yaml = extract(content)?;
+    let start = content.len() - content.strip_prefix("---\n").or_else(|| content.strip_prefix("---"))?.len();
+    let block = &content[start..start + yaml.len()];
+    let new_line = format!("{}: {}", key, value);
+    let prefix = format!("{}:", key);
+
+    let mut found = false;
+    let mut lines: Vec<String> = block.lines()
+        .map(|line| {
+            if !found && line.starts_with(&prefix) {
+                found = true;
+                new_line.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(new_line);
+    }
+
+    let mut new_block = lines.join("\n");
+    if block.is_empty() || block.ends_with('\n') {
+        new_block.push('\n');
+    }
+    Some(format!("{}{}{}", &content[..start], new_block, &content[start + block.len()..]))
+}
+
+/// Set a value in YAML by key, where `a.b` is the key `b` of the mapping `a`
+/// (created, or replacing a non-mapping value, as needed)
+pub fn set_nested(yaml: Value, key: &str, value: Value) -> Value {
+    let parts: Vec<&str> = key.split('.').collect();
+
+    if parts.len() == 1 {
+        if let Value::Mapping(mut map) = yaml {
+            map.insert(
+                Value::String(parts[0].to_string()),
+                value
+            );
+            return Value::Mapping(map);
+        }
+        return yaml;
+    }
+
+    // Nested path - need to insert into a mapping
+    let current_key = Value::String(parts[0].to_string());
+    let rest_key = parts[1..].join(".");
+
+    let nested_value = if let Value::Mapping(ref map) = yaml {
+        if let Some(existing) = map.get(&current_key) {
+            // If existing value is not a mapping, we need to replace it
+            if !matches!(existing, Value::Mapping(_)) {
+                // Create fresh nested structure, discarding the non-mapping value
+                let new_map = Mapping::new();
+                let empty = Value::Mapping(new_map);
+                set_nested(empty, &rest_key, value)
+            } else {
+                // Recurse into existing mapping
+                set_nested(existing.clone(), &rest_key, value)
+            }
+        } else {
+            // Create new nested structure
+            let new_map = Mapping::new();
+            let empty = Value::Mapping(new_map);
+            set_nested(empty, &rest_key, value)
+        }
+    } else {
+        // yaml is not a mapping, create new nested structure from scratch
+        let new_map = Mapping::new();
+        let empty = Value::Mapping(new_map);
+        set_nested(empty, &rest_key, value)
+    };
+
+    // Insert into mapping, creating one if yaml isn't a mapping
+    if let Value::Mapping(mut map) = yaml {
+        map.insert(current_key, nested_value);
+        Value::Mapping(map)
+    } else {
+        // Convert non-mapping yaml to a new mapping with the nested value
+        let mut new_map = Mapping::new();
+        new_map.insert(current_key, nested_value);
+        Value::Mapping(new_map)
+    }
+}
+
+/// Merge `fields` into the frontmatter of a note, adding a frontmatter block
+/// if it has none. Mappings are merged key by key, `tags` become the union of
+/// both lists, and other values replace the note's.
+pub fn merge(content: &str, fields: &Mapping) -> io::Result<String> {
+    if fields.is_empty() {
+        return Ok(content.to_string());
+    }
+
+    let (mut yaml, body) = match split(content) {
+        Some((yaml, body)) => {
+            let value = match serde_yaml::from_str::<Value>(yaml) {
+                Ok(Value::Null) => Mapping::new(),
+                Ok(Value::Mapping(map)) => map,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid YAML in frontmatter",
+                    ))
+                }
+            };
+            (value, body)
+        }
+        None => (Mapping::new(), content),
+    };
+
+    merge_mapping(&mut yaml, fields, true);
+    let yaml = serde_yaml::to_string(&yaml)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    Ok(format!("---\n{}---\n{}", yaml, body))
+}
+
+/// Merge `fields` into `yaml`; `top` is the frontmatter's top level, where
+/// `tags` lists are joined
+fn merge_mapping(yaml: &mut Mapping, fields: &Mapping, top: bool) {
+    for (key, value) in fields {
+        let merged = match (yaml.get(key), value) {
+            (Some(existing), added) if top && key.as_str() == Some(C::TAGS_KEY) => {
+                let mut tags = tag_values(existing);
+                for tag in tag_values(added) {
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                Value::Sequence(tags.into_iter().map(Value::String).collect())
+            }
+            (Some(Value::Mapping(existing)), Value::Mapping(added)) => {
+                let mut existing = existing.clone();
+                merge_mapping(&mut existing, added, false);
+                Value::Mapping(existing)
+            }
+            (_, value) => value.clone(),
+        };
+        yaml.insert(key.clone(), merged);
+    }
+}
+
+/// Set the `tags` of a note's frontmatter to `tags` (as a flow list), keeping
+/// the other lines of the block. The key is removed for an empty list, and a
+/// frontmatter block left empty is removed; a note without frontmatter gets one.
+pub fn set_tags(content: &str, tags: &[String]) -> String {
+    let line = (!tags.is_empty()).then(|| {
+        let items: Vec<Value> = tags.iter().cloned().map(Value::String).collect();
+        format!("{}: {}", C::TAGS_KEY, crate::config::render_value(&Value::Sequence(items)))
+    });
+
+    match split(content) {
+        Some((yaml, body)) => {
+            let yaml = replace_key_lines(yaml, C::TAGS_KEY, line.as_deref());
+            if yaml.trim().is_empty() {
+                body.to_string()
+            } else {
+                format!("---\n{}---\n{}", yaml, body)
+            }
+        }
+        None => match line {
+            Some(line) => format!("---\n{}\n---\n{}", line, content),
+            None => content.to_string(),
+        },
+    }
+}
+
+/// Replace the line of a top-level key (and the indented or `- ` lines of
+/// its block value) with `line`, remove it if `line` is None, or append
+/// `line` if the key is missing
+pub(crate) fn replace_key_lines(content: &str, key: &str, line: Option<&str>) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let is_key_line = |l: &str| {
+        l.strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with(':'))
+    };
+
+    let mut out: Vec<&str> = Vec::new();
+    let mut replaced = false;
+    let mut i = 0;
+    while i < lines.len() {
+        if !replaced && is_key_line(lines[i]) {
+            replaced = true;
+            out.extend(line);
+            i += 1;
+            while i < lines.len() && (lines[i].starts_with([' ', '\t']) || lines[i].starts_with("- ")) {
+                i += 1;
+            }
+            continue;
+        }
+        out.push(lines[i]);
+        i += 1;
+    }
+    if !replaced {
+        out.extend(line);
+    }
+
+    let mut result = out.join("\n");
+    if !result.is_empty() {
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(extract("---\ntitle: A\n---\n# A\n"), Some("title: A"));
+        assert_eq!(extract("# No frontmatter\n"), None);
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("---\ntitle: A\n---\n# A\n"), Some(("title: A", "# A\n")));
+        assert_eq!(split("---\ntitle: A\n---"), Some(("title: A", "")));
+        assert_eq!(split("---\n---\nbody"), Some(("", "body")));
+        assert_eq!(split("---\ntitle: A\n----\nbody"), None);
+        assert_eq!(split("# A\n"), None);
+    }
+
+    #[test]
+    fn test_set_nested() {
+        let yaml: Value = serde_yaml::from_str("status: old\nreview: done").unwrap();
+        let yaml = set_nested(yaml, "status", Value::from("new"));
+        let yaml = set_nested(yaml, "review.by", Value::from("bob"));
+        assert_eq!(serde_yaml::to_string(&yaml).unwrap(), "status: new\nreview:\n  by: bob\n");
+    }
+
+    #[test]
+    fn test_merge() {
+        let fields: Mapping = serde_yaml::from_str("tags: [cli, rust]\nproject:\n  phase: 2\nstatus: draft").unwrap();
+        assert_eq!(
+            merge("---\ntags: rust notes\nproject:\n  name: emx\nstatus: old\n---\n# A\n", &fields).unwrap(),
+            "---\ntags:\n- rust\n- notes\n- cli\nproject:\n  name: emx\n  phase: 2\nstatus: draft\n---\n# A\n"
+        );
+        assert_eq!(
+            merge("# A\n", &fields).unwrap(),
+            "---\ntags:\n- cli\n- rust\nproject:\n  phase: 2\nstatus: draft\n---\n# A\n"
+        );
+        assert_eq!(merge("# A\n", &Mapping::new()).unwrap(), "# A\n");
+        assert!(merge("---\n[broken\n---\n", &fields).is_err());
+    }
+
+    #[test]
+    fn test_set_tags() {
+        let content = "---\ntitle: A # keep\ntags:\n  - rust\nstatus: draft\n---\n# A\n";
+        assert_eq!(
+            set_tags(content, &["rust".to_string(), "c++".to_string()]),
+            "---\ntitle: A # keep\ntags: [rust, c++]\nstatus: draft\n---\n# A\n"
+        );
+        assert_eq!(set_tags(content, &[]), "---\ntitle: A # keep\nstatus: draft\n---\n# A\n");
+        assert_eq!(set_tags("---\ntags: [rust]\n---\n# A\n", &[]), "# A\n");
+        assert_eq!(set_tags("# A\n", &["rust".to_string()]), "---\ntags: [rust]\n---\n# A\n");
+        assert_eq!(set_tags("# A\n", &[]), "# A\n");
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(tags("---\ntags: [rust, '#cli']\n---\n"), vec!["rust", "cli"]);
+        assert_eq!(tags("---\ntags: rust, cli\n---\n"), vec!["rust", "cli"]);
+        assert_eq!(tags("---\ntitle: x\n---\n"), Vec::<String>::new());
+        assert_eq!(tags("# Note\n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_set_scalar() {
+        let content = "---\ntitle: A # keep\nmodified: old\n---\n# A\n";
+        assert_eq!(
+            set_scalar(content, "modified", "new").unwrap(),
+            "---\ntitle: A # keep\nmodified: new\n---\n# A\n"
+        );
+        assert_eq!(
+            set_scalar("---\ntitle: A\n---\nbody", "modified", "now").unwrap(),
+            "---\ntitle: A\nmodified: now\n---\nbody"
+        );
+        assert_eq!(set_scalar("# A\n", "modified", "now"), None);
+    }
+}
@@ -0,0 +1,368 @@
+//! Note graph of a capsa
+//!
+//! Notes are the nodes and the resolved links between them are the edges.
+//! Tag indexes, the daily index and TASK.md are not nodes: a tag index gives
+//! its tag to the notes it lists, on top of the `tags` of each note's
+//! frontmatter (and its inline hashtags with the `inline_tags` setting). The graph can be cut down to a tag or to the neighbourhood of
+//! a note, and written as Graphviz DOT, GraphML or node-link JSON.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::constants as C;
+use crate::link_cache::LinkCache;
+use crate::links::resolve;
+use crate::note_resolver::{NoteArea, NoteResolver, ResolvedNote};
+use crate::relink::normalize;
+use crate::util;
+
+/// A note of the graph
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphNode {
+    /// Path relative to the capsa root (forward slashes), used as node id
+    pub id: String,
+    /// Note title (first H1 heading or filename)
+    pub title: String,
+    /// Tags from the frontmatter and the tag indexes, sorted
+    pub tags: Vec<String>,
+    pub area: NoteArea,
+    /// `status` key of the frontmatter
+    pub status: Option<String>,
+}
+
+/// Links from one note to another
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    /// Number of links from `source` to `target`
+    pub weight: usize,
+}
+
+/// Output format of `link graph`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    #[default]
+    Dot,
+    /// GraphML (XML)
+    GraphMl,
+    /// Node-link JSON, as read by networkx and d3
+    Json,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" | "gv" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!("Unknown graph format '{}' (expected dot, graphml or json)", s)),
+        }
+    }
+}
+
+/// Directed graph of the notes of a capsa
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkGraph {
+    /// Notes, sorted by id
+    pub nodes: Vec<GraphNode>,
+    /// Edges, sorted by source then target; self-links are left out
+    pub edges: Vec<GraphEdge>,
+}
+
+impl LinkGraph {
+    /// Build the graph of every note of a capsa, from the link cache
+    pub fn build(capsa_path: &Path) -> io::Result<Self> {
+        let cache = LinkCache::open(capsa_path)?;
+        let resolver = NoteResolver::from_cache(capsa_path, &cache)?;
+        let inline = resolver.config().inline_tags;
+
+        let mut nodes: BTreeMap<String, GraphNode> = BTreeMap::new();
+        for (id, cached) in cache.files() {
+            let Some(area) = NoteArea::from_relative(id, &resolver.config().daily_dir) else { continue };
+            nodes.insert(id.clone(), GraphNode {
+                id: id.clone(),
+                title: cached.title.clone(),
+                tags: cached.note_tags(inline),
+                area,
+                status: cached.frontmatter_scalar("status"),
+            });
+        }
+
+        let mut edges: BTreeMap<(String, String), usize> = BTreeMap::new();
+        for (id, cached) in cache.files() {
+            let file = capsa_path.join(id);
+            let tag = tag_name(capsa_path, &file);
+            for link in &cached.links {
+                let ResolvedNote::Found(target) = resolve(&resolver, &file, link)? else { continue };
+                let target = util::relative_path(capsa_path, &normalize(&target));
+                let Some(node) = nodes.get_mut(&target) else { continue };
+
+                if let Some(tag) = &tag {
+                    if !node.tags.contains(tag) {
+                        node.tags.push(tag.clone());
+                    }
+                } else if nodes.contains_key(id) && *id != target {
+                    *edges.entry((id.clone(), target)).or_default() += 1;
+                }
+            }
+        }
+
+        let mut nodes: Vec<GraphNode> = nodes.into_values().collect();
+        for node in &mut nodes {
+            node.tags.sort();
+        }
+        let edges = edges.into_iter()
+            .map(|((source, target), weight)| GraphEdge { source, target, weight })
+            .collect();
+
+        Ok(LinkGraph { nodes, edges })
+    }
+
+    /// Whether a note is a node of the graph
+    pub fn contains(&self, id: &str) -> bool {
+        self.nodes.iter().any(|node| node.id == id)
+    }
+
+    /// Keep only the notes tagged `tag` and the edges between them
+    pub fn retain_tag(&mut self, tag: &str) {
+        let tag = tag.trim_start_matches('#');
+        let keep: HashSet<String> = self.nodes.iter()
+            .filter(|node| node.tags.iter().any(|t| t == tag))
+            .map(|node| node.id.clone())
+            .collect();
+        self.retain_nodes(&keep);
+    }
+
+    /// Keep only the notes at most `depth` links away from `center`, following
+    /// links in either direction
+    pub fn retain_neighbourhood(&mut self, center: &str, depth: usize) {
+        let mut keep: HashSet<String> = HashSet::new();
+        let mut queue = VecDeque::new();
+        if self.contains(center) {
+            keep.insert(center.to_string());
+            queue.push_back((center.to_string(), 0));
+        }
+
+        while let Some((id, distance)) = queue.pop_front() {
+            if distance == depth {
+                continue;
+            }
+            for edge in &self.edges {
+                let next = if edge.source == id {
+                    &edge.target
+                } else if edge.target == id {
+                    &edge.source
+                } else {
+                    continue;
+                };
+                if keep.insert(next.clone()) {
+                    queue.push_back((next.clone(), distance + 1));
+                }
+            }
+        }
+
+        self.retain_nodes(&keep);
+    }
+
+    fn retain_nodes(&mut self, keep: &HashSet<String>) {
+        self.nodes.retain(|node| keep.contains(&node.id));
+        self.edges.retain(|edge| keep.contains(&edge.source) && keep.contains(&edge.target));
+    }
+
+    /// Write the graph in the given format
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::GraphMl => self.to_graphml(),
+            GraphFormat::Json => self.to_json(),
+        }
+    }
+
+    /// Graphviz DOT; node attributes other than `label` are kept as is for tools
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph notes {\n");
+        for node in &self.nodes {
+            let _ = write!(out, "  {} [label={}, area={}, tags={}",
+                dot_quote(&node.id),
+                dot_quote(&node.title),
+                dot_quote(node.area.as_str()),
+                dot_quote(&node.tags.join(",")),
+            );
+            if let Some(status) = &node.status {
+                let _ = write!(out, ", status={}", dot_quote(status));
+            }
+            out.push_str("];\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "  {} -> {} [weight={}];",
+                dot_quote(&edge.source), dot_quote(&edge.target), edge.weight);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// GraphML, with `title`, `tags` (comma separated), `area` and `status`
+    /// node data and `weight` edge data
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"title\" for=\"node\" attr.name=\"title\" attr.type=\"string\"/>\n",
+            "  <key id=\"tags\" for=\"node\" attr.name=\"tags\" attr.type=\"string\"/>\n",
+            "  <key id=\"area\" for=\"node\" attr.name=\"area\" attr.type=\"string\"/>\n",
+            "  <key id=\"status\" for=\"node\" attr.name=\"status\" attr.type=\"string\"/>\n",
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+            "  <graph id=\"notes\" edgedefault=\"directed\">\n",
+        ));
+        for node in &self.nodes {
+            let _ = writeln!(out, "    <node id=\"{}\">", xml_escape(&node.id));
+            let _ = writeln!(out, "      <data key=\"title\">{}</data>", xml_escape(&node.title));
+            let _ = writeln!(out, "      <data key=\"tags\">{}</data>", xml_escape(&node.tags.join(",")));
+            let _ = writeln!(out, "      <data key=\"area\">{}</data>", node.area.as_str());
+            if let Some(status) = &node.status {
+                let _ = writeln!(out, "      <data key=\"status\">{}</data>", xml_escape(status));
+            }
+            out.push_str("    </node>\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "    <edge source=\"{}\" target=\"{}\">",
+                xml_escape(&edge.source), xml_escape(&edge.target));
+            let _ = writeln!(out, "      <data key=\"weight\">{}</data>", edge.weight);
+            out.push_str("    </edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// Node-link JSON: `{"directed", "multigraph", "graph", "nodes", "links"}`
+    pub fn to_json(&self) -> String {
+        json!({
+            "directed": true,
+            "multigraph": false,
+            "graph": {},
+            "nodes": self.nodes,
+            "links": self.edges,
+        }).to_string()
+    }
+}
+
+/// Tag of a tag index (`#tag.md` in the capsa root)
+fn tag_name(capsa_path: &Path, file: &Path) -> Option<String> {
+    if file.parent() != Some(capsa_path) {
+        return None;
+    }
+    let name = file.file_name()?.to_str()?;
+    let tag = name.strip_prefix(C::TAG_PREFIX)?.strip_suffix(C::MARKDOWN_EXTENSION)?;
+    Some(tag.to_string())
+}
+
+/// DOT quoted string
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// Escape text for XML content and attribute values
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::TempDir;
+
+    fn sample() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/a.md", "---\ntags: [draft]\nstatus: active\n---\n# Alpha\n\n[[Beta]] [b](b.md) [[Alpha#Self]]\n");
+        write(root, "note/b.md", "# Beta\n\n[[note/c]]\n");
+        write(root, "note/c.md", "# Gamma\n\n[[Delta]]\n");
+        write(root, "note/d.md", "# Delta\n");
+        write(root, "note/1234abcd/book.md", "# Book \"One\"\n\n[[Alpha]]\n");
+        write(root, "#rust.md", "# rust\n\n## 2026-02-14\n- [Beta](note/b.md)\n- [Alpha](note/a.md)\n");
+        write(root, "TASK.md", "# Tasks\n\n[TASK-01]: note/d.md\n");
+        temp_dir
+    }
+
+    fn ids(graph: &LinkGraph) -> Vec<&str> {
+        graph.nodes.iter().map(|n| n.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_build() {
+        let temp_dir = sample();
+        let graph = LinkGraph::build(temp_dir.path()).unwrap();
+
+        assert_eq!(ids(&graph), vec!["note/1234abcd/book.md", "note/a.md", "note/b.md", "note/c.md", "note/d.md"]);
+        let a = &graph.nodes[1];
+        assert_eq!(a.title, "Alpha");
+        assert_eq!(a.tags, vec!["draft", "rust"]);
+        assert_eq!(a.area, NoteArea::Permanent);
+        assert_eq!(a.status.as_deref(), Some("active"));
+        assert_eq!(graph.nodes[0].area, NoteArea::Literature);
+
+        let edges: Vec<(&str, &str, usize)> = graph.edges.iter()
+            .map(|e| (e.source.as_str(), e.target.as_str(), e.weight))
+            .collect();
+        assert_eq!(edges, vec![
+            ("note/1234abcd/book.md", "note/a.md", 1),
+            ("note/a.md", "note/b.md", 2),
+            ("note/b.md", "note/c.md", 1),
+            ("note/c.md", "note/d.md", 1),
+        ]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let temp_dir = sample();
+        let graph = LinkGraph::build(temp_dir.path()).unwrap();
+
+        let mut tagged = graph.clone();
+        tagged.retain_tag("#rust");
+        assert_eq!(ids(&tagged), vec!["note/a.md", "note/b.md"]);
+        assert_eq!(tagged.edges.len(), 1);
+
+        let mut near = graph.clone();
+        near.retain_neighbourhood("note/b.md", 1);
+        assert_eq!(ids(&near), vec!["note/a.md", "note/b.md", "note/c.md"]);
+
+        let mut far = graph.clone();
+        far.retain_neighbourhood("note/b.md", 2);
+        assert_eq!(far.nodes.len(), 5);
+    }
+
+    #[test]
+    fn test_render() {
+        let temp_dir = sample();
+        let graph = LinkGraph::build(temp_dir.path()).unwrap();
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph notes {\n"));
+        assert!(dot.contains("\"note/1234abcd/book.md\" [label=\"Book \\\"One\\\"\", area=\"literature\""));
+        assert!(dot.contains("\"note/a.md\" -> \"note/b.md\" [weight=2];"));
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<data key=\"title\">Book &quot;One&quot;</data>"));
+        assert!(graphml.contains("<edge source=\"note/a.md\" target=\"note/b.md\">"));
+
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["nodes"][1]["tags"], json!(["draft", "rust"]));
+        assert_eq!(json["links"][1]["weight"], 2);
+        assert_eq!(json["nodes"][2]["status"], serde_json::Value::Null);
+    }
+}
@@ -1,23 +1,52 @@
 pub mod cli;
 pub mod edit;
+pub mod patch;
 pub mod markdown;
 pub mod resolve;
 pub mod util;
 pub mod note_resolver;
 pub mod engine;
 pub mod constants;
+pub mod search;
+pub mod tokenizer;
+pub mod config;
+pub mod lock;
+pub mod relink;
+pub mod links;
+pub mod link_cache;
+pub mod link_fix;
+pub mod graph;
+pub mod trash;
+pub mod frontmatter;
+pub mod template;
+pub mod tag_sync;
+#[cfg(test)]
+mod test_util;
 
-pub use cli::{Cli, Command, CapsaCommand, TagCommand, LinkCommand, TaskCommand};
-pub use edit::{EditOp, ValidationError, apply_edits};
-pub use engine::{CapsaEngine, Tags, Tag, TaskFile};
+pub use cli::{Cli, Command, CapsaCommand, ConfigCommand, CreateArgs, TagCommand, TrashCommand, LinkCommand, TaskCommand, SearchArgs};
+pub use edit::{EditOp, ValidationError, ConflictError, VersionedFile, apply_edits, edit_file, update_file};
+pub use patch::{Patch, PatchError, PatchResult, AppliedHunk, RejectedHunk};
+pub use engine::{CapsaEngine, CreatedNote, CreateAction, DeletedNote, EditedNote, Tags, Tag, TaskFile};
 pub use markdown::{
-    MarkdownHeading, MarkdownLink,
+    MarkdownHeading, HeadingLine, MarkdownLink, LinkSpan, WikiLink, link_spans, wiki_links, heading_lines,
+    heading_anchor, heading_anchors,
     extract_references, extract_headings, extract_links,
     has_reference, get_reference_dest, find_heading_line, extract_frontmatter_prefix,
 };
 pub use resolve::{ResolveContext, CapsaRef, DEFAULT_CAPSA_NAME, GLOBAL_NAMESPACE_MARKER, SHARED_NAMESPACE};
 pub use util::{secure_path, validate_link_target, extract_note_title, slugify, hash_source, abbreviate_hash, read_stdin_content};
-pub use note_resolver::{ResolvedNote, resolve_note, resolve_note_or_error, resolve_note_with_force};
+pub use note_resolver::{ResolvedNote, NoteArea, NoteResolver, resolve_note, resolve_link_target, resolve_note_or_error, resolve_note_with_force};
+pub use search::{SearchIndex, SearchFilter, SearchHit, Query};
+pub use config::{CapsaConfig, ConfigSource, ConflictPolicy, Setting, SlugStyle};
+pub use lock::CapsaLock;
+pub use relink::FileChange;
+pub use links::{NoteLink, LinkKind, ParsedLink};
+pub use link_cache::{LinkCache, CachedFile};
+pub use link_fix::{BrokenLink, FixCandidate, FixReason};
+pub use graph::{LinkGraph, GraphNode, GraphEdge, GraphFormat};
+pub use trash::{Trash, TrashEntry};
+pub use template::{Template, TemplateVars};
+pub use tag_sync::{TagDrift, DriftKind, SyncOptions, TagIndex, TagSync};
 pub use constants::MAX_FRONTMATTER_SIZE;
 
 /// Default notes directory name (relative to home)
@@ -0,0 +1,297 @@
+//! Link cache
+//!
+//! Keeps what the link commands need from every markdown file of a capsa
+//! (parsed links, headings, title and frontmatter) under `.index/links.json`,
+//! so that `link`, `gc` and the note graph don't re-read and re-parse the
+//! whole capsa on every run.
+//!
+//! The cache is updated incrementally: a file is parsed again only when its
+//! modification time or size changed since the last update. Link resolution
+//! depends on the other files of the capsa, so it is not cached.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::CapsaConfig;
+use crate::constants as C;
+use crate::links::{markdown_files, parse_links, ParsedLink};
+use crate::lock::CapsaLock;
+use crate::markdown::{self, heading_lines, HeadingLine};
+use crate::{frontmatter, util};
+
+/// What the cache keeps of one file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedFile {
+    /// Modification time (nanoseconds since epoch) when parsed
+    mtime: u64,
+    /// File size when parsed
+    size: u64,
+    /// First H1 heading or filename
+    pub title: String,
+    pub headings: Vec<HeadingLine>,
+    /// Frontmatter, converted to JSON (None if missing, invalid or not
+    /// representable, e.g. non-string keys)
+    pub frontmatter: Option<serde_json::Value>,
+    /// `tags` of the frontmatter, without `#`
+    pub tags: Vec<String>,
+    /// Inline `#hashtags` of the body, without `#`
+    pub inline_tags: Vec<String>,
+    pub links: Vec<ParsedLink>,
+}
+
+impl CachedFile {
+    /// Parse a file's content
+    pub fn parse(path: &Path, content: &str, is_task_file: bool) -> Self {
+        CachedFile {
+            mtime: 0,
+            size: 0,
+            title: util::extract_note_title(path, content),
+            headings: heading_lines(content),
+            frontmatter: frontmatter::parse(content).and_then(|yaml| serde_json::to_value(yaml).ok()),
+            tags: frontmatter::tags(content),
+            inline_tags: markdown::inline_tags(content),
+            links: parse_links(content, is_task_file),
+        }
+    }
+
+    /// Tags of the note: its frontmatter `tags`, then with `inline` its
+    /// inline hashtags
+    pub fn note_tags(&self, inline: bool) -> Vec<String> {
+        let mut tags = self.tags.clone();
+        if inline {
+            for tag in &self.inline_tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+        }
+        tags
+    }
+
+    /// Top-level scalar of the frontmatter, as a string
+    pub fn frontmatter_scalar(&self, key: &str) -> Option<String> {
+        match self.frontmatter.as_ref()?.get(key)? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            serde_json::Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Persistent per-capsa cache of parsed markdown files
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LinkCache {
+    version: u32,
+    /// Task file setting the files were parsed with
+    task_file: String,
+    /// Path relative to the capsa root (forward slashes) -> parsed file
+    files: BTreeMap<String, CachedFile>,
+}
+
+impl LinkCache {
+    /// Path of the cache file for a capsa
+    pub fn file(capsa_path: &Path) -> PathBuf {
+        capsa_path.join(C::INDEX_DIR).join(C::LINK_CACHE_FILENAME)
+    }
+
+    /// Load the cache from disk, or start an empty one if missing/outdated
+    pub fn load(capsa_path: &Path) -> io::Result<Self> {
+        let path = Self::file(capsa_path);
+        if !path.exists() {
+            return Ok(Self::empty());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        match serde_json::from_str::<LinkCache>(&content) {
+            Ok(cache) if cache.version == C::LINK_CACHE_VERSION => Ok(cache),
+            // Outdated or corrupt cache: rebuild from scratch
+            _ => Ok(Self::empty()),
+        }
+    }
+
+    /// Create an empty cache
+    pub fn empty() -> Self {
+        LinkCache {
+            version: C::LINK_CACHE_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Save the cache to disk
+    pub fn save(&self, capsa_path: &Path) -> io::Result<()> {
+        let path = Self::file(capsa_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        util::atomic_write(&path, &content)
+    }
+
+    /// Load the cache, bring it up to date with the capsa and save it if anything changed
+    pub fn open(capsa_path: &Path) -> io::Result<Self> {
+        let mut cache = Self::load(capsa_path)?;
+        if cache.update(capsa_path)? {
+            let _lock = CapsaLock::acquire(capsa_path)?;
+            cache.save(capsa_path)?;
+        }
+        Ok(cache)
+    }
+
+    /// Parse added/changed files and drop deleted ones.
+    /// Returns true if the cache changed.
+    pub fn update(&mut self, capsa_path: &Path) -> io::Result<bool> {
+        let config = CapsaConfig::load(capsa_path)?;
+        let mut on_disk: BTreeMap<String, (PathBuf, u64, u64)> = BTreeMap::new();
+        for path in markdown_files(capsa_path, &config.extensions())? {
+            let metadata = fs::metadata(&path)?;
+            let mtime = metadata.modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0);
+            on_disk.insert(util::relative_path(capsa_path, &path), (path, mtime, metadata.len()));
+        }
+
+        let before = self.files.len();
+        self.files.retain(|relative, cached| {
+            matches!(on_disk.get(relative), Some((_, mtime, size)) if *mtime == cached.mtime && *size == cached.size)
+        });
+        let mut changed = self.files.len() != before;
+
+        // References of the task file are note references: parse the old
+        // and new task files again when the setting changes
+        if self.task_file != config.task_file {
+            self.files.remove(&self.task_file);
+            self.files.remove(&config.task_file);
+            self.task_file = config.task_file.clone();
+            changed = true;
+        }
+
+        for (relative, (path, mtime, size)) in on_disk {
+            if self.files.contains_key(&relative) {
+                continue;
+            }
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(_) => continue, // Unreadable (e.g. not UTF-8): skip
+            };
+            let mut cached = CachedFile::parse(&path, &content, relative == config.task_file);
+            cached.mtime = mtime;
+            cached.size = size;
+            self.files.insert(relative, cached);
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
+    /// Task file of the capsa, relative to its root, as of the last update
+    pub fn task_file(&self) -> &str {
+        &self.task_file
+    }
+
+    /// Parsed file by path relative to the capsa root
+    pub fn get(&self, relative: &str) -> Option<&CachedFile> {
+        self.files.get(relative)
+    }
+
+    /// Parsed file by full path (None for files outside the capsa)
+    pub fn get_path(&self, capsa_path: &Path, path: &Path) -> Option<&CachedFile> {
+        let relative = path.strip_prefix(capsa_path).ok()?;
+        self.get(&relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    /// Every cached file, by relative path
+    pub fn files(&self) -> impl Iterator<Item = (&String, &CachedFile)> {
+        self.files.iter()
+    }
+
+    /// Number of cached files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether the cache holds no files
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_update_is_incremental() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("note")).unwrap();
+        fs::write(root.join("note/a.md"), "---\ntags: [x]\nstatus: draft\n---\n# Alpha\n\n## Part\n\n[[Beta]]\n").unwrap();
+        fs::write(root.join("note/b.md"), "# Beta\n").unwrap();
+        fs::write(root.join("TASK.md"), "# Tasks\n\n[TASK-01]: beta\n").unwrap();
+
+        let mut cache = LinkCache::empty();
+        assert!(cache.update(root).unwrap());
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.update(root).unwrap());
+
+        let a = cache.get("note/a.md").unwrap();
+        assert_eq!(a.title, "Alpha");
+        assert_eq!(a.tags, vec!["x"]);
+        assert_eq!(a.frontmatter_scalar("status").as_deref(), Some("draft"));
+        assert_eq!(a.headings.iter().map(|h| h.text.as_str()).collect::<Vec<_>>(), vec!["Alpha", "Part"]);
+        assert_eq!(a.links[0].dest, "Beta");
+        assert!(cache.get("TASK.md").unwrap().links[0].note_ref);
+
+        fs::write(root.join("note/b.md"), "# Beta\n\n[[Alpha]]\n").unwrap();
+        fs::remove_file(root.join("TASK.md")).unwrap();
+        assert!(cache.update(root).unwrap());
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("note/b.md").unwrap().links[0].dest, "Alpha");
+    }
+
+    #[test]
+    fn test_task_file_setting() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("TASK.md"), "[TASK-01]: beta\n").unwrap();
+        fs::write(root.join("TODO.md"), "[TASK-01]: beta\n").unwrap();
+
+        let mut cache = LinkCache::empty();
+        cache.update(root).unwrap();
+        assert!(cache.get("TASK.md").unwrap().links[0].note_ref);
+        assert!(!cache.get("TODO.md").unwrap().links[0].note_ref);
+
+        // Both files are parsed again when the setting changes
+        fs::write(root.join(C::CONFIG_FILENAME), "task_file: TODO.md\n").unwrap();
+        assert!(cache.update(root).unwrap());
+        assert_eq!(cache.task_file(), "TODO.md");
+        assert!(!cache.get("TASK.md").unwrap().links[0].note_ref);
+        assert!(cache.get("TODO.md").unwrap().links[0].note_ref);
+    }
+
+    #[test]
+    fn test_open_persists_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("note")).unwrap();
+        fs::write(root.join("note/a.md"), "# Alpha\n").unwrap();
+
+        let cache = LinkCache::open(root).unwrap();
+        assert!(LinkCache::file(root).exists());
+        let loaded = LinkCache::load(root).unwrap();
+        assert_eq!(loaded.get("note/a.md"), cache.get("note/a.md"));
+
+        // An outdated cache starts over
+        fs::write(LinkCache::file(root), "{\"version\":0,\"files\":{}}").unwrap();
+        assert!(LinkCache::load(root).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,396 @@
+//! Repair suggestions for broken links
+//!
+//! For each link that resolves to no file, or to several, candidates are
+//! proposed, best first:
+//! - the notes an ambiguous link resolves to
+//! - what the note resolver finds for the target's name alone (the path
+//!   part may be stale)
+//! - files with the same name elsewhere in the capsa
+//! - daily notes with the same name under another date or time
+//! - files with a similar name
+//!
+//! Each candidate comes with the link rewritten in the form it was written
+//! in; `fix_ops` turns the chosen ones into edit operations.
+
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::constants as C;
+use crate::edit::{apply_edits, EditOp};
+use crate::link_cache::LinkCache;
+use crate::links::{edit_distance, is_path_ref, parsed_links, resolve, LinkKind, ParsedLink};
+use crate::markdown::{link_spans, task_references, wiki_links};
+use crate::note_resolver::{NoteArea, NoteResolver, ResolvedNote};
+use crate::relink::{relative_path, task_ref};
+use crate::util;
+
+/// Why a file is proposed for a broken link, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FixReason {
+    /// One of the notes an ambiguous link resolves to
+    Ambiguous,
+    /// Found by the note resolver from the target's name
+    Resolver,
+    /// Same file name elsewhere in the capsa
+    SameName,
+    /// Daily note with the same name under another date or time
+    Daily,
+    /// Similar file name
+    Similar,
+}
+
+impl FixReason {
+    /// Short description for listings
+    pub fn describe(&self) -> &'static str {
+        match self {
+            FixReason::Ambiguous => "ambiguous",
+            FixReason::Resolver => "resolves by name",
+            FixReason::SameName => "same name",
+            FixReason::Daily => "moved daily note",
+            FixReason::Similar => "similar name",
+        }
+    }
+}
+
+/// A file a broken link could point to instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixCandidate {
+    pub path: PathBuf,
+    pub reason: FixReason,
+    /// Edit distance between the names (0 unless `Similar`)
+    pub distance: usize,
+    /// Link as it would be written: the destination of markdown links,
+    /// `[[...]]` for wiki links
+    pub replacement: String,
+}
+
+/// A broken link and its repair candidates
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// Linking file
+    pub source: PathBuf,
+    /// Line number (1-based)
+    pub line: usize,
+    /// Link as written
+    pub target: String,
+    pub kind: LinkKind,
+    /// Candidates, best first (empty if nothing comes close)
+    pub candidates: Vec<FixCandidate>,
+}
+
+impl BrokenLink {
+    /// Candidate to use without asking: the best one, unless another one
+    /// ranks the same
+    pub fn best(&self) -> Option<&FixCandidate> {
+        let first = self.candidates.first()?;
+        let tied = self.candidates.get(1)
+            .is_some_and(|next| (next.reason, next.distance) == (first.reason, first.distance));
+        (!tied).then_some(first)
+    }
+}
+
+/// Broken links of `files`, with repair candidates from the files of the capsa
+pub fn broken_links(capsa_path: &Path, files: &[PathBuf]) -> io::Result<Vec<BrokenLink>> {
+    let cache = LinkCache::open(capsa_path)?;
+    let resolver = NoteResolver::from_cache(capsa_path, &cache)?;
+    let known: Vec<(String, PathBuf)> = cache.files()
+        .map(|(relative, _)| (relative.clone(), capsa_path.join(relative)))
+        .collect();
+
+    let mut broken = Vec::new();
+    for file in files {
+        for link in parsed_links(&cache, capsa_path, file)?.iter().filter(|l| !l.dest.is_empty()) {
+            let found: Vec<(PathBuf, FixReason, usize)> = match resolve(&resolver, file, link)? {
+                ResolvedNote::Found(_) => continue,
+                ResolvedNote::Ambiguous(paths) => paths.into_iter()
+                    .map(|path| (path, FixReason::Ambiguous, 0))
+                    .collect(),
+                ResolvedNote::NotFound => candidates(&resolver, file, &link.dest, &known)?,
+            };
+
+            let mut candidates: Vec<FixCandidate> = Vec::new();
+            for (path, reason, distance) in found {
+                if path != *file && !candidates.iter().any(|c| c.path == path) {
+                    let replacement = rewrite(&resolver, file, link, &path);
+                    candidates.push(FixCandidate { path, reason, distance, replacement });
+                }
+            }
+            candidates.truncate(C::LINK_FIX_MAX_CANDIDATES);
+
+            broken.push(BrokenLink {
+                source: file.clone(),
+                line: link.line,
+                target: link.target.clone(),
+                kind: link.kind,
+                candidates,
+            });
+        }
+    }
+    Ok(broken)
+}
+
+/// Files that a link to `dest` may have meant, best first
+fn candidates(
+    resolver: &NoteResolver,
+    source: &Path,
+    dest: &str,
+    known: &[(String, PathBuf)],
+) -> io::Result<Vec<(PathBuf, FixReason, usize)>> {
+    let extensions = resolver.extensions();
+    let name = file_name(dest, &extensions);
+    let wanted = util::slugify(&name);
+    if wanted.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut found = Vec::new();
+    match resolver.resolve_note(&name)? {
+        ResolvedNote::Found(path) => found.push((path, FixReason::Resolver, 0)),
+        ResolvedNote::Ambiguous(paths) => {
+            found.extend(paths.into_iter().map(|path| (path, FixReason::Resolver, 0)));
+        }
+        ResolvedNote::NotFound => {}
+    }
+
+    for (relative, path) in known {
+        if path == source {
+            continue;
+        }
+        let slug = util::slugify(&file_name(relative, &extensions));
+        if slug == wanted {
+            found.push((path.clone(), FixReason::SameName, 0));
+        } else if NoteArea::from_relative(relative, &resolver.config().daily_dir) == Some(NoteArea::Daily)
+            && without_time(&slug) == without_time(&wanted)
+        {
+            found.push((path.clone(), FixReason::Daily, 0));
+        } else {
+            let distance = edit_distance(&slug, &wanted);
+            let len = slug.chars().count().max(wanted.chars().count());
+            if distance as f64 <= len as f64 * C::LINK_FIX_MAX_NAME_DIFF {
+                found.push((path.clone(), FixReason::Similar, distance));
+            }
+        }
+    }
+
+    found.sort_by(|a, b| (a.1, a.2).cmp(&(b.1, b.2)).then_with(|| a.0.cmp(&b.0)));
+    Ok(found)
+}
+
+/// Last path segment without a note extension
+fn file_name(dest: &str, extensions: &[&str]) -> String {
+    let name = dest.rsplit('/').next().unwrap_or(dest);
+    extensions.iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name)
+        .to_string()
+}
+
+/// Daily note name without its `HHMMSS-` prefix
+fn without_time(slug: &str) -> &str {
+    match slug.split_once('-') {
+        Some((time, rest)) if time.len() == 6 && time.chars().all(|c| c.is_ascii_digit()) => rest,
+        _ => slug,
+    }
+}
+
+/// The link pointing at `target` instead, written in the same form
+fn rewrite(resolver: &NoteResolver, source: &Path, link: &ParsedLink, target: &Path) -> String {
+    let capsa_path = resolver.capsa_path();
+    let extensions = resolver.extensions();
+    match link.kind {
+        LinkKind::Markdown if link.note_ref && !is_path_ref(&link.dest, &extensions) => task_ref(capsa_path, &resolver.config().daily_dir, target),
+        LinkKind::Markdown => {
+            let source_dir = source.parent().unwrap_or(capsa_path);
+            let path = if link.dest.starts_with('/') {
+                format!("/{}", relative_path(capsa_path, target))
+            } else if is_root_based(capsa_path, source_dir, &link.dest) {
+                relative_path(capsa_path, target)
+            } else {
+                let path = relative_path(source_dir, target);
+                // Keep an explicit `./`
+                if link.dest.starts_with("./") && !path.starts_with("../") {
+                    format!("./{}", path)
+                } else {
+                    path
+                }
+            };
+            match &link.fragment {
+                Some(fragment) => format!("{}#{}", path, fragment),
+                None => path,
+            }
+        }
+        LinkKind::Wiki => {
+            let relative = relative_path(capsa_path, target);
+            let path = extensions.iter()
+                .find_map(|ext| relative.strip_suffix(ext))
+                .unwrap_or(&relative);
+            // Keep `#heading` and `|alias`
+            let inner = link.target.trim_start_matches("[[").trim_end_matches("]]");
+            let rest = inner.find(['#', '|']).map_or("", |i| &inner[i..]);
+            format!("[[{}{}]]", path, rest)
+        }
+    }
+}
+
+/// Whether a broken link path was written relative to the capsa root: paths
+/// starting with `#` (`#daily/...`) or whose directory exists only there
+fn is_root_based(capsa_path: &Path, source_dir: &Path, dest: &str) -> bool {
+    if dest.starts_with('#') {
+        return true;
+    }
+    match Path::new(dest).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            capsa_path.join(dir).is_dir() && !source_dir.join(dir).is_dir()
+        }
+        _ => false,
+    }
+}
+
+/// Edit operations that apply `fixes` (broken link and chosen candidate) to
+/// the content of their source file
+///
+/// Each changed line becomes a `replace` of that line, or a
+/// `replace_line_range` when the line occurs more than once.
+pub fn fix_ops(content: &str, fixes: &[(&BrokenLink, &FixCandidate)]) -> Vec<EditOp> {
+    let spans = link_spans(content);
+    let task_refs = task_references(content);
+    let wikis = wiki_links(content);
+
+    let mut replacements: Vec<(Range<usize>, &str)> = Vec::new();
+    for (broken, candidate) in fixes {
+        let range = match broken.kind {
+            LinkKind::Markdown => spans.iter()
+                .filter(|span| span.line == broken.line && span.dest == broken.target)
+                .map(|span| span.range.clone())
+                // Task references run to the end of the line
+                .chain(task_refs.iter()
+                    .filter(|reference| reference.line == broken.line && reference.node_ref == broken.target)
+                    .map(|reference| reference.range.clone()))
+                .find(|range| !replacements.iter().any(|(r, _)| r == range)),
+            LinkKind::Wiki => wikis.iter()
+                .filter(|wiki| wiki.line == broken.line)
+                .map(|wiki| if wiki.embed { wiki.range.start + 1..wiki.range.end } else { wiki.range.clone() })
+                .filter(|range| content[range.clone()] == broken.target)
+                .find(|range| !replacements.iter().any(|(r, _)| r == range)),
+        };
+        if let Some(range) = range {
+            replacements.push((range, &candidate.replacement));
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut fixed = content.to_string();
+    for (range, replacement) in replacements.iter().rev() {
+        fixed.replace_range(range.clone(), replacement);
+    }
+
+    // Link rewrites never add or remove lines
+    let mut ops = Vec::new();
+    let mut current = content.to_string();
+    for (i, (old, new)) in content.lines().zip(fixed.lines()).enumerate() {
+        if old == new {
+            continue;
+        }
+        let op = if current.matches(old).count() == 1 {
+            EditOp::replace(old, new)
+        } else {
+            EditOp::replace_line_range(i, i + 1, new)
+        };
+        if let Ok(next) = apply_edits(&current, vec![op.clone()]) {
+            current = next;
+            ops.push(op);
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::TempDir;
+
+    fn summary(broken: &BrokenLink) -> Vec<(String, FixReason, String)> {
+        broken.candidates.iter()
+            .map(|c| (c.path.file_name().unwrap().to_string_lossy().to_string(), c.reason, c.replacement.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_broken_links_candidates() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/archive/roadmap.md", "# Roadmap\n");
+        write(root, "note/project-plan.md", "# Project Plan\n");
+        write(root, "#daily/20260215/093000-standup.md", "# Standup\n");
+        write(root, "note/source.md", "# Source\n\n\
+            [map](roadmap.md#goals)\n\
+            [[Project Plna|the plan]]\n\
+            [day](#daily/20260214/100000-standup.md)\n\
+            [[Nothing Like It]]\n");
+
+        let broken = broken_links(root, &[root.join("note/source.md")]).unwrap();
+        assert_eq!(broken.len(), 4);
+
+        assert_eq!(summary(&broken[0]), vec![
+            ("roadmap.md".to_string(), FixReason::SameName, "archive/roadmap.md#goals".to_string()),
+        ]);
+        assert_eq!(summary(&broken[1]), vec![
+            ("project-plan.md".to_string(), FixReason::Similar, "[[note/project-plan|the plan]]".to_string()),
+        ]);
+        assert_eq!(summary(&broken[2]), vec![
+            ("093000-standup.md".to_string(), FixReason::Daily, "#daily/20260215/093000-standup.md".to_string()),
+        ]);
+        assert!(broken[3].candidates.is_empty());
+        assert!(broken[3].best().is_none());
+        assert_eq!(broken[0].best().unwrap().reason, FixReason::SameName);
+    }
+
+    #[test]
+    fn test_tied_candidates_have_no_best() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/a1b2c3d4e5f6/idea.md", "# Idea\n");
+        write(root, "note/f6e5d4c3b2a1/idea.md", "# Idea\n");
+        write(root, "note/source.md", "# Source\n\n[[Idea]]\n");
+
+        let broken = broken_links(root, &[root.join("note/source.md")]).unwrap();
+        assert_eq!(broken[0].candidates.len(), 2);
+        assert_eq!(broken[0].candidates[1].reason, FixReason::SameName);
+        assert!(broken[0].best().is_none());
+    }
+
+    #[test]
+    fn test_fix_ops() {
+        let content = "# Source\n\nSee [a](old.md) and ![[Old#Part|o]].\nSee [a](old.md) and ![[Old#Part|o]].\n";
+        let link = |kind, target: &str, line| BrokenLink {
+            source: PathBuf::from("note/source.md"),
+            line,
+            target: target.to_string(),
+            kind,
+            candidates: Vec::new(),
+        };
+        let candidate = |replacement: &str| FixCandidate {
+            path: PathBuf::from("note/new.md"),
+            reason: FixReason::SameName,
+            distance: 0,
+            replacement: replacement.to_string(),
+        };
+        let (a, b, c) = (link(LinkKind::Markdown, "old.md", 3), link(LinkKind::Wiki, "[[Old#Part|o]]", 3), link(LinkKind::Markdown, "old.md", 4));
+        let (new_md, new_wiki) = (candidate("new.md"), candidate("[[note/new#Part|o]]"));
+
+        let ops = fix_ops(content, &[(&a, &new_md), (&b, &new_wiki), (&c, &new_md)]);
+        assert_eq!(ops, vec![
+            EditOp::replace_line_range(2, 3, "See [a](new.md) and ![[note/new#Part|o]]."),
+            EditOp::replace("See [a](old.md) and ![[Old#Part|o]].", "See [a](new.md) and ![[Old#Part|o]]."),
+        ]);
+        assert_eq!(
+            apply_edits(content, ops).unwrap(),
+            "# Source\n\nSee [a](new.md) and ![[note/new#Part|o]].\nSee [a](new.md) and ![[Old#Part|o]].\n"
+        );
+    }
+}
@@ -0,0 +1,562 @@
+//! Links between the files of a capsa
+//!
+//! Collects the local links of markdown files and resolves them to files:
+//! - CommonMark links, images and reference definitions, relative to the
+//!   linking file or to the capsa root (the form used by tag indexes)
+//! - wiki links (`[[Title]]`, `[[note#heading|alias]]`), with the note
+//!   resolver rules
+//! - task references in the task file (`[TASK-01]: node_ref`), which are
+//!   note references unless written as a path (`[TASK-01]: ./note/idea.md`)
+//! - `href`/`src` attributes of raw HTML (`<a href>`, `<img src>`)
+//!
+//! Parsing and resolution are separate steps: parsed links only depend on the
+//! file itself and are kept in the link cache (`link_cache.rs`), while
+//! resolution depends on the rest of the capsa and is done on every scan.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::constants as C;
+use crate::link_cache::LinkCache;
+use crate::markdown::{heading_anchor, heading_anchors, heading_lines, link_spans, task_references, wiki_links, HeadingLine};
+use crate::note_resolver::{NoteResolver, ResolvedNote};
+use crate::relink::{collect_markdown_files, normalize, split_dest};
+
+/// How a link is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `[text](dest)`, `![alt](dest)`, `[id]: dest` or `<a href="dest">`
+    Markdown,
+    /// `[[target]]` or `![[target]]`
+    Wiki,
+}
+
+/// A local link as parsed from a file, before resolution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParsedLink {
+    /// Line number (1-based)
+    pub line: usize,
+    /// Link as written: the destination of markdown links, `[[...]]` for wiki links
+    pub target: String,
+    pub kind: LinkKind,
+    /// Path of a markdown link (without `#fragment`) or target of a wiki link;
+    /// empty for a heading of the same file (`#part`, `[[#part]]`)
+    pub dest: String,
+    /// `#fragment` of a markdown link or heading of a wiki link, without `#`
+    pub fragment: Option<String>,
+    /// Task reference in TASK.md, resolved as a note reference
+    pub note_ref: bool,
+}
+
+/// A local link found in a file
+#[derive(Debug, Clone)]
+pub struct NoteLink {
+    /// Linking file
+    pub source: PathBuf,
+    /// Line number (1-based)
+    pub line: usize,
+    /// Link as written: the destination of markdown links, `[[...]]` for wiki links
+    pub target: String,
+    pub kind: LinkKind,
+    /// What the link points to
+    pub resolution: ResolvedNote,
+}
+
+impl NoteLink {
+    /// Linked file, if the link resolves to exactly one
+    pub fn resolved(&self) -> Option<&Path> {
+        match &self.resolution {
+            ResolvedNote::Found(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Whether the link does not resolve to exactly one file
+    pub fn is_broken(&self) -> bool {
+        self.resolved().is_none()
+    }
+}
+
+/// Markdown files (with one of `extensions`) under `dir`, sorted, skipping
+/// hidden directories (`.trash`, ...)
+pub fn markdown_files(dir: &Path, extensions: &[&str]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_markdown_files(dir, extensions, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Attachments under `dir`: files other than markdown notes (with one of
+/// `extensions`), sorted, skipping hidden files and directories (`.index`,
+/// `.trash`, ...) and note backups (`idea.md.bak`)
+pub fn attachment_files(dir: &Path, extensions: &[&str]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_attachments(dir, extensions, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_attachments(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_attachments(&path, extensions, files)?;
+        } else {
+            let note_name = name.strip_suffix(C::BACKUP_EXTENSION).unwrap_or(&name);
+            if !extensions.iter().any(|ext| note_name.ends_with(ext)) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Local links of every file in `files`
+///
+/// Files of the capsa are read from the link cache, which is brought up to
+/// date first; other files are parsed from disk.
+pub fn scan(capsa_path: &Path, files: &[PathBuf]) -> io::Result<Vec<NoteLink>> {
+    let cache = LinkCache::open(capsa_path)?;
+    let resolver = NoteResolver::from_cache(capsa_path, &cache)?;
+    let mut links = Vec::new();
+    for file in files {
+        links.extend(resolve_all(&resolver, file, &parsed_links(&cache, capsa_path, file)?)?);
+    }
+    Ok(links)
+}
+
+/// Local links of one file, in order of appearance
+///
+/// External URLs and links to a heading of the same file (`#part`, `[[#part]]`)
+/// are skipped; see `broken_anchors` for those.
+pub fn links_in(capsa_path: &Path, source: &Path, content: &str) -> io::Result<Vec<NoteLink>> {
+    let resolver = NoteResolver::open(capsa_path)?;
+    let is_task_file = source == capsa_path.join(&resolver.config().task_file);
+    resolve_all(&resolver, source, &parse_links(content, is_task_file))
+}
+
+/// Parse the local links of a file, in order of appearance, including links
+/// to headings of the same file. With `is_task_file`, reference definitions
+/// are note references, read up to the end of the line.
+pub fn parse_links(content: &str, is_task_file: bool) -> Vec<ParsedLink> {
+    let mut links = Vec::new();
+    let task_refs = if is_task_file { task_references(content) } else { Vec::new() };
+
+    for span in link_spans(content) {
+        if task_refs.iter().any(|reference| reference.range.contains(&span.range.start)) {
+            continue;
+        }
+        let (path, fragment) = match split_dest(&span.dest) {
+            Some(split) => split,
+            None if span.dest.starts_with('#') => ("", span.dest.as_str()),
+            None => continue,
+        };
+        links.push(ParsedLink {
+            line: span.line,
+            dest: path.to_string(),
+            fragment: fragment.strip_prefix('#').filter(|f| !f.is_empty()).map(str::to_string),
+            note_ref: false,
+            target: span.dest.clone(),
+            kind: LinkKind::Markdown,
+        });
+    }
+
+    for reference in task_refs {
+        let Some((path, fragment)) = split_dest(&reference.node_ref) else { continue };
+        links.push(ParsedLink {
+            line: reference.line,
+            dest: path.to_string(),
+            fragment: fragment.strip_prefix('#').filter(|f| !f.is_empty()).map(str::to_string),
+            note_ref: true,
+            target: reference.node_ref.clone(),
+            kind: LinkKind::Markdown,
+        });
+    }
+
+    for wiki in wiki_links(content) {
+        if wiki.target.is_empty() && wiki.heading.as_deref().unwrap_or("").is_empty() {
+            continue;
+        }
+        links.push(ParsedLink {
+            line: wiki.line,
+            target: content[wiki.range.clone()].trim_start_matches('!').to_string(),
+            kind: LinkKind::Wiki,
+            dest: wiki.target,
+            fragment: wiki.heading,
+            note_ref: false,
+        });
+    }
+
+    links.sort_by_key(|link| link.line);
+    links
+}
+
+/// Resolve a parsed link of `source`
+pub fn resolve(resolver: &NoteResolver, source: &Path, link: &ParsedLink) -> io::Result<ResolvedNote> {
+    if link.dest.is_empty() {
+        return Ok(ResolvedNote::Found(source.to_path_buf()));
+    }
+    let capsa_path = resolver.capsa_path();
+    match link.kind {
+        LinkKind::Markdown if link.note_ref => match resolve_dest(capsa_path, source, &link.dest) {
+            found @ ResolvedNote::Found(_) if is_path_ref(&link.dest, &resolver.extensions()) => Ok(found),
+            _ => resolver.resolve_note(&link.dest),
+        },
+        LinkKind::Markdown => Ok(resolve_dest(capsa_path, source, &link.dest)),
+        LinkKind::Wiki => resolver.resolve_link_target(&link.dest),
+    }
+}
+
+/// Whether a task reference is written as a path (`./note/idea.md`) rather
+/// than as a note reference (`idea`)
+pub(crate) fn is_path_ref(dest: &str, extensions: &[&str]) -> bool {
+    dest.starts_with("./")
+        || dest.starts_with("../")
+        || dest.starts_with('/')
+        || extensions.iter().any(|ext| dest.ends_with(ext))
+}
+
+fn resolve_all(resolver: &NoteResolver, source: &Path, parsed: &[ParsedLink]) -> io::Result<Vec<NoteLink>> {
+    parsed.iter()
+        .filter(|link| !link.dest.is_empty())
+        .map(|link| Ok(NoteLink {
+            source: source.to_path_buf(),
+            line: link.line,
+            target: link.target.clone(),
+            kind: link.kind,
+            resolution: resolve(resolver, source, link)?,
+        }))
+        .collect()
+}
+
+/// Links from other files of the capsa that resolve to `target`, including
+/// tag index entries and TASK.md references
+pub fn backlinks(capsa_path: &Path, target: &Path) -> io::Result<Vec<NoteLink>> {
+    let target = normalize(target);
+    let cache = LinkCache::open(capsa_path)?;
+    let resolver = NoteResolver::from_cache(capsa_path, &cache)?;
+    let mut found = Vec::new();
+    for (relative, cached) in cache.files() {
+        let file = capsa_path.join(relative);
+        if normalize(&file) == target {
+            continue;
+        }
+        found.extend(
+            resolve_all(&resolver, &file, &cached.links)?
+                .into_iter()
+                .filter(|link| link.resolved().is_some_and(|path| normalize(path) == target)),
+        );
+    }
+    Ok(found)
+}
+
+/// A link whose `#fragment` matches no heading of the linked file
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenAnchor {
+    /// Linking file
+    pub source: PathBuf,
+    /// Line number (1-based)
+    pub line: usize,
+    /// Link as written
+    pub target: String,
+    pub kind: LinkKind,
+    /// Fragment as written, without `#`
+    pub fragment: String,
+    /// Closest heading, written as the link would need it: the anchor for
+    /// markdown links, the heading text for wiki links
+    pub suggestion: Option<String>,
+}
+
+/// Links of `files` to a heading (`note.md#part`, `#part`, `[[note#Part]]`)
+/// that the linked markdown file does not have
+///
+/// A fragment matches a heading when it is the heading's GitHub-style anchor
+/// (percent-encoding decoded), or when its own anchor is: `#Part%202`,
+/// `#part-2` and `[[note#Part 2]]` all match `## Part 2`. Links that don't
+/// resolve, links to other kinds of files and wiki block references
+/// (`[[note#^id]]`) are not checked.
+pub fn broken_anchors(capsa_path: &Path, files: &[PathBuf]) -> io::Result<Vec<BrokenAnchor>> {
+    let cache = LinkCache::open(capsa_path)?;
+    let resolver = NoteResolver::from_cache(capsa_path, &cache)?;
+    let extensions = resolver.extensions();
+    let headings_of = |path: &Path| -> Option<Vec<HeadingLine>> {
+        match cache.get_path(capsa_path, path) {
+            Some(cached) => Some(cached.headings.clone()),
+            None if is_markdown(path, &extensions) => fs::read_to_string(path).ok().map(|c| heading_lines(&c)),
+            None => None,
+        }
+    };
+
+    let mut found = Vec::new();
+    for file in files {
+        for link in &parsed_links(&cache, capsa_path, file)? {
+            let Some(fragment) = &link.fragment else { continue };
+            if link.kind == LinkKind::Wiki && fragment.starts_with('^') {
+                continue;
+            }
+            let ResolvedNote::Found(target) = resolve(&resolver, file, link)? else { continue };
+            let Some(headings) = headings_of(&target) else { continue };
+
+            let anchors = heading_anchors(&headings);
+            let decoded = percent_decode(fragment);
+            let wanted = heading_anchor(&decoded);
+            if anchors.contains(&decoded) || anchors.contains(&wanted) {
+                continue;
+            }
+
+            let suggestion = closest(&anchors, &wanted).map(|i| match link.kind {
+                LinkKind::Markdown => anchors[i].clone(),
+                LinkKind::Wiki => headings[i].text.clone(),
+            });
+            found.push(BrokenAnchor {
+                source: file.clone(),
+                line: link.line,
+                target: link.target.clone(),
+                kind: link.kind,
+                fragment: fragment.clone(),
+                suggestion,
+            });
+        }
+    }
+    Ok(found)
+}
+
+/// Parsed links of a file: from the cache for files of the capsa, parsed from
+/// disk otherwise
+pub(crate) fn parsed_links(cache: &LinkCache, capsa_path: &Path, file: &Path) -> io::Result<Vec<ParsedLink>> {
+    if let Some(cached) = cache.get_path(capsa_path, file) {
+        return Ok(cached.links.clone());
+    }
+    let content = fs::read_to_string(file).map_err(|e| {
+        io::Error::new(e.kind(), format!("Failed to read '{}': {}", file.display(), e))
+    })?;
+    Ok(parse_links(&content, file == capsa_path.join(cache.task_file())))
+}
+
+/// Index of the anchor closest to `wanted`, if few enough characters differ
+fn closest(anchors: &[String], wanted: &str) -> Option<usize> {
+    anchors.iter()
+        .enumerate()
+        .map(|(i, anchor)| (i, edit_distance(anchor, wanted), anchor.chars().count().max(wanted.chars().count())))
+        .filter(|&(_, distance, len)| distance as f64 <= len as f64 * C::ANCHOR_SUGGESTION_MAX_DIFF)
+        .min_by_key(|&(_, distance, _)| distance)
+        .map(|(i, _, _)| i)
+}
+
+/// Levenshtein distance, in characters
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Decode `%XX` escapes (invalid escapes are kept as is)
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn is_markdown(path: &Path, extensions: &[&str]) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    extensions.iter().any(|ext| name.ends_with(ext))
+}
+
+/// Resolve a markdown link path (without `#fragment`): `/`-prefixed paths from
+/// the capsa root, others relative to the linking file, then to the root
+pub fn resolve_dest(capsa_path: &Path, source: &Path, path: &str) -> ResolvedNote {
+    let candidates = match path.strip_prefix('/') {
+        Some(rooted) => vec![capsa_path.join(rooted)],
+        None => {
+            let dir = source.parent().unwrap_or(capsa_path);
+            vec![dir.join(path), capsa_path.join(path)]
+        }
+    };
+
+    candidates.into_iter()
+        .map(|candidate| normalize(&candidate))
+        .find(|candidate| candidate.exists())
+        .map_or(ResolvedNote::NotFound, ResolvedNote::Found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_links_in() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n");
+        write(root, "note/other.md", "# Other\n");
+        let content = "[idea](idea.md#part) [root](note/other.md) [gone](missing.md)\n\
+                       [[Idea]] [[note/other|o]] [[Nowhere]] [[#local]] [web](https://x.org)\n";
+        let source = root.join("note/source.md");
+
+        let links = links_in(root, &source, content).unwrap();
+        let summary: Vec<(&str, LinkKind, bool)> = links.iter()
+            .map(|l| (l.target.as_str(), l.kind, l.is_broken()))
+            .collect();
+        assert_eq!(summary, vec![
+            ("idea.md#part", LinkKind::Markdown, false),
+            ("note/other.md", LinkKind::Markdown, false),
+            ("missing.md", LinkKind::Markdown, true),
+            ("[[Idea]]", LinkKind::Wiki, false),
+            ("[[note/other|o]]", LinkKind::Wiki, false),
+            ("[[Nowhere]]", LinkKind::Wiki, true),
+        ]);
+        assert_eq!(links[3].resolved(), Some(root.join("note/idea.md").as_path()));
+        assert_eq!(links[3].line, 2);
+    }
+
+    #[test]
+    fn test_parse_links() {
+        let parsed = parse_links("[a](idea.md#Part%202) [[Other#Intro|o]]\n\n[TASK-01]: idea\n", true);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].dest, "idea.md");
+        assert_eq!(parsed[0].fragment.as_deref(), Some("Part%202"));
+        assert!(!parsed[0].note_ref);
+        assert_eq!(parsed[1].dest, "Other");
+        assert_eq!(parsed[1].fragment.as_deref(), Some("Intro"));
+        assert_eq!(parsed[2].dest, "idea");
+        assert!(parsed[2].note_ref);
+    }
+
+    #[test]
+    fn test_task_paths_and_html_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n");
+        write(root, "note/pics/logo.png", "png");
+
+        let task = root.join(C::TASK_FILENAME);
+        let links = links_in(root, &task, "[TASK-01]: ./note/idea.md\n[TASK-02]: idea\n[TASK-03]: ./note/gone.md\n").unwrap();
+        assert_eq!(links[0].resolved(), Some(root.join("note/idea.md").as_path()));
+        assert_eq!(links[1].resolved(), Some(root.join("note/idea.md").as_path()));
+        assert!(links[2].is_broken());
+
+        let source = root.join("note/idea.md");
+        let links = links_in(root, &source, "<img src=\"pics/logo.png\"> <a href=\"other.md\">o</a>\n").unwrap();
+        assert_eq!(links[0].resolved(), Some(root.join("note/pics/logo.png").as_path()));
+        assert!(links[1].is_broken());
+    }
+
+    #[test]
+    fn test_attachment_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n");
+        write(root, "note/idea.md.bak", "# Idea\n");
+        write(root, "note/pics/logo.png", "png");
+        write(root, "report.pdf", "pdf");
+        write(root, ".index/links.json", "{}");
+        write(root, ".source", "x");
+
+        let found: Vec<PathBuf> = attachment_files(root, crate::DEFAULT_EXTENSIONS).unwrap();
+        assert_eq!(found, vec![root.join("note/pics/logo.png"), root.join("report.pdf")]);
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n\n[[Idea#Self]] [other](other.md)\n");
+        write(root, "note/other.md", "# Other\n\n[[Idea]]\n\n[back](idea.md)\n");
+        write(root, "#rust.md", "# rust\n\n## 2026-02-14\n- [Idea](note/idea.md)\n");
+        write(root, ".trash/1/note/old.md", "[[Idea]]\n");
+
+        let target = root.join("note/idea.md");
+        let found: Vec<(String, usize)> = backlinks(root, &target).unwrap().iter()
+            .map(|l| (l.source.strip_prefix(root).unwrap().to_string_lossy().to_string(), l.line))
+            .collect();
+        assert_eq!(found, vec![
+            ("#rust.md".to_string(), 4),
+            ("note/other.md".to_string(), 3),
+            ("note/other.md".to_string(), 5),
+        ]);
+    }
+
+    #[test]
+    fn test_broken_anchors() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n\n## Part 2: Setup\n\n## Notes\n\n## Notes\n");
+        write(root, "note/other.md", "# Other\n\n\
+            [a](idea.md#part-2-setup) [b](idea.md#Part%202%3A%20Setup) [c](idea.md#notes-1)\n\
+            [[Idea#Part 2: Setup]] [[Idea#^block]] [local](#other) [[#Other]]\n\
+            [d](idea.md#part-2-stup) [[Idea#Notez]] [local](#nowhere) [e](idea.md#zzz)\n");
+
+        let found = broken_anchors(root, &[root.join("note/other.md")]).unwrap();
+        let summary: Vec<(usize, &str, Option<&str>)> = found.iter()
+            .map(|b| (b.line, b.fragment.as_str(), b.suggestion.as_deref()))
+            .collect();
+        assert_eq!(summary, vec![
+            (5, "part-2-stup", Some("part-2-setup")),
+            (5, "nowhere", None),
+            (5, "zzz", None),
+            (5, "Notez", Some("Notes")),
+        ]);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("会议", "会意"), 1);
+        assert_eq!(percent_decode("Part%202%zz"), "Part 2%zz");
+    }
+
+    #[test]
+    fn test_task_references_resolve_as_notes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n");
+        let task = root.join(C::TASK_FILENAME);
+        let links = links_in(root, &task, "---\nPREFIX: TASK-\n---\n\n---\n\n[TASK-01]: idea\n").unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].resolved(), Some(root.join("note/idea.md").as_path()));
+
+        // With another task file, TASK.md is a plain note
+        write(root, C::CONFIG_FILENAME, "task_file: TODO.md\n");
+        assert!(links_in(root, &task, "[TASK-01]: idea\n").unwrap()[0].is_broken());
+        let links = links_in(root, &root.join("TODO.md"), "[TASK-01]: idea\n").unwrap();
+        assert_eq!(links[0].resolved(), Some(root.join("note/idea.md").as_path()));
+    }
+}
@@ -0,0 +1,364 @@
+//! Capsa-level advisory locking
+//!
+//! Serializes read-modify-write cycles on shared files (TASK.md, `#tag.md`,
+//! `note/#daily.md`, note frontmatter) between concurrent processes, e.g.
+//! several agents running `task take` at once.
+//!
+//! The lock is a file in the capsa root (`.emx-note.lock`) created with
+//! create-new semantics. It records the holder's pid, agent name, creation
+//! time and a token unique to this acquisition. A lock older than
+//! `LOCK_STALE_SECS` (by `created`, or by file mtime while `created` is not
+//! written yet) whose holder pid is no longer running is considered left
+//! behind by a crashed process and is broken. Waiting for a held lock gives up
+//! after `LOCK_TIMEOUT_SECS` (override with `EMX_LOCK_TIMEOUT`, in seconds).
+//!
+//! Removing the lock file, whether releasing our own lock or breaking a stale
+//! one, first renames it aside and checks what was moved: a lock created by
+//! someone else in the meantime is put back instead of being deleted.
+//!
+//! Locks are reentrant within a process: nested operations (e.g. creating a
+//! daily note, which also updates `#daily.md`) share the outer lock.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
+
+use crate::constants as C;
+use crate::util;
+
+/// Lock files held by this process, with nesting depth and token
+static HELD: Lazy<Mutex<HashMap<PathBuf, (usize, String)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Per-process counter making lock tokens and aside names unique
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Guard for a held capsa lock; released on drop
+#[derive(Debug)]
+pub struct CapsaLock {
+    path: PathBuf,
+    /// Token written into the lock file by the outermost acquisition
+    token: String,
+}
+
+/// Holder information recorded in the lock file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub agent: String,
+    /// Creation time (seconds since epoch)
+    pub created: u64,
+    /// Unique token of the acquisition that created the lock
+    pub token: String,
+}
+
+impl CapsaLock {
+    /// Path of the lock file for a capsa
+    pub fn file(capsa_path: &Path) -> PathBuf {
+        capsa_path.join(C::LOCK_FILENAME)
+    }
+
+    /// Acquire the capsa lock with the default timeout
+    pub fn acquire(capsa_path: &Path) -> io::Result<Self> {
+        Self::acquire_with(capsa_path, lock_timeout(), Duration::from_secs(C::LOCK_STALE_SECS))
+    }
+
+    /// Acquire the capsa lock, waiting up to `timeout` and breaking locks older than `stale_after`
+    pub fn acquire_with(capsa_path: &Path, timeout: Duration, stale_after: Duration) -> io::Result<Self> {
+        let path = Self::file(capsa_path);
+
+        // Reentrant: already held by this process
+        {
+            let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((depth, token)) = held.get_mut(&path) {
+                *depth += 1;
+                return Ok(CapsaLock { path, token: token.clone() });
+            }
+        }
+
+        let start = Instant::now();
+        loop {
+            let token = new_token();
+            match Self::try_create(&path, &token) {
+                Ok(()) => {
+                    HELD.lock().unwrap_or_else(|e| e.into_inner()).insert(path.clone(), (1, token.clone()));
+                    return Ok(CapsaLock { path, token });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+
+            let info = Self::read_info(&path);
+            if let Some(stale) = info.as_ref().filter(|i| i.is_stale(&path, stale_after)) {
+                // Left behind by a crashed process: break it (unless it was
+                // replaced since we looked) and retry at once
+                remove_if(&path, |current| current == stale);
+                continue;
+            }
+
+            if start.elapsed() >= timeout {
+                let holder = match info {
+                    Some(info) if !info.agent.is_empty() => format!("@{} (pid {})", info.agent, info.pid),
+                    Some(info) => format!("pid {}", info.pid),
+                    None => "another process".to_string(),
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Capsa is locked by {} (lock file: {})\nHint: Retry later, or delete the lock file if no emx-note process is running",
+                        holder,
+                        util::display_path(&path)
+                    ),
+                ));
+            }
+
+            thread::sleep(Duration::from_millis(C::LOCK_RETRY_MS));
+        }
+    }
+
+    /// Read holder information from a lock file
+    pub fn read_info(path: &Path) -> Option<LockInfo> {
+        let content = fs::read_to_string(path).ok()?;
+        let mut info = LockInfo::default();
+        for line in content.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim();
+                match key.trim() {
+                    "pid" => info.pid = value.parse().unwrap_or(0),
+                    "agent" => info.agent = value.to_string(),
+                    "created" => info.created = value.parse().unwrap_or(0),
+                    "token" => info.token = value.to_string(),
+                    _ => {}
+                }
+            }
+        }
+        Some(info)
+    }
+
+    /// Create the lock file exclusively
+    fn try_create(path: &Path, token: &str) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+        let agent = std::env::var("EMX_AGENT_NAME").unwrap_or_default();
+        write!(
+            file,
+            "pid: {}\nagent: {}\ncreated: {}\ntoken: {}\n",
+            std::process::id(),
+            agent,
+            now_secs(),
+            token
+        )
+    }
+}
+
+impl Drop for CapsaLock {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((depth, _)) = held.get_mut(&self.path) {
+            *depth -= 1;
+            if *depth == 0 {
+                held.remove(&self.path);
+                // Only remove the lock file if it is still ours
+                remove_if(&self.path, |info| info.token == self.token);
+            }
+        }
+    }
+}
+
+impl LockInfo {
+    /// Time since the lock was created (zero for timestamps in the future)
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now_secs().saturating_sub(self.created))
+    }
+
+    /// Whether the lock at `path` was left behind: older than `stale_after`
+    /// and its holder is not known to be running.
+    /// A lock whose file is still being written (`created == 0`) is aged by the file mtime.
+    fn is_stale(&self, path: &Path, stale_after: Duration) -> bool {
+        let age = if self.created > 0 {
+            self.age()
+        } else {
+            match fs::metadata(path).and_then(|m| m.modified()) {
+                Ok(modified) => SystemTime::now().duration_since(modified).unwrap_or_default(),
+                Err(_) => return false,
+            }
+        };
+        age > stale_after && !(self.pid > 0 && process_alive(self.pid) == Some(true))
+    }
+}
+
+/// Remove the lock file at `path` if its content satisfies `matches`.
+///
+/// The file is renamed aside first, so only one process can take it; if what
+/// was taken does not match (the lock was released and re-created meanwhile),
+/// it is put back. Returns whether the file was removed.
+fn remove_if(path: &Path, matches: impl Fn(&LockInfo) -> bool) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or(C::LOCK_FILENAME);
+    let aside = path.with_file_name(format!("{}.{}.old", name, new_token()));
+    if fs::rename(path, &aside).is_err() {
+        return false;
+    }
+    let removed = CapsaLock::read_info(&aside).is_some_and(|info| matches(&info));
+    if !removed {
+        // Fails only if another lock was created meanwhile, which then stands
+        let _ = fs::hard_link(&aside, path);
+    }
+    let _ = fs::remove_file(&aside);
+    removed
+}
+
+/// Whether a process is running; `None` when this can't be determined
+#[cfg(unix)]
+fn process_alive(pid: u32) -> Option<bool> {
+    let pid = libc::pid_t::try_from(pid).ok()?;
+    // SAFETY: signal 0 performs the existence and permission checks only
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Some(true);
+    }
+    match io::Error::last_os_error().raw_os_error() {
+        Some(libc::ESRCH) => Some(false),
+        // EPERM: the process exists but belongs to another user
+        Some(libc::EPERM) => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> Option<bool> {
+    None
+}
+
+/// Token unique to one lock acquisition: pid, time and a per-process counter
+fn new_token() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    format!("{}-{}-{}", std::process::id(), nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Lock wait timeout (EMX_LOCK_TIMEOUT seconds, or the default)
+fn lock_timeout() -> Duration {
+    std::env::var("EMX_LOCK_TIMEOUT").ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(C::LOCK_TIMEOUT_SECS))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Above the Linux pid limit, so never a running process
+    const DEAD_PID: u32 = 999_999_999;
+
+    #[test]
+    fn test_acquire_and_release() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        {
+            let _lock = CapsaLock::acquire(temp_dir.path()).unwrap();
+            assert!(lock_file.exists());
+            let info = CapsaLock::read_info(&lock_file).unwrap();
+            assert_eq!(info.pid, std::process::id());
+        }
+        assert!(!lock_file.exists());
+    }
+
+    #[test]
+    fn test_reentrant() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        let outer = CapsaLock::acquire(temp_dir.path()).unwrap();
+        {
+            let _inner = CapsaLock::acquire_with(temp_dir.path(), Duration::ZERO, Duration::from_secs(60)).unwrap();
+        }
+        // Inner release keeps the outer lock
+        assert!(lock_file.exists());
+        drop(outer);
+        assert!(!lock_file.exists());
+    }
+
+    #[test]
+    fn test_timeout_when_held_elsewhere() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        fs::write(&lock_file, format!("pid: 1\nagent: alice\ncreated: {}\n", now_secs())).unwrap();
+
+        let err = CapsaLock::acquire_with(temp_dir.path(), Duration::from_millis(50), Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("@alice"));
+        // Foreign lock is left alone
+        assert!(lock_file.exists());
+    }
+
+    #[test]
+    fn test_stale_lock_is_broken() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        fs::write(&lock_file, format!("pid: {}\nagent: crashed\ncreated: 1\n", DEAD_PID)).unwrap();
+
+        let _lock = CapsaLock::acquire_with(temp_dir.path(), Duration::ZERO, Duration::from_secs(60)).unwrap();
+        assert_eq!(CapsaLock::read_info(&lock_file).unwrap().pid, std::process::id());
+    }
+
+    #[test]
+    fn test_stale_lock_without_created_uses_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        // Partially written lock file
+        fs::write(&lock_file, format!("pid: {}\n", DEAD_PID)).unwrap();
+
+        // Fresh by mtime: not broken
+        let err = CapsaLock::acquire_with(temp_dir.path(), Duration::from_millis(50), Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options().write(true).open(&lock_file).unwrap().set_modified(old).unwrap();
+        let _lock = CapsaLock::acquire_with(temp_dir.path(), Duration::ZERO, Duration::from_secs(60)).unwrap();
+        assert_eq!(CapsaLock::read_info(&lock_file).unwrap().pid, std::process::id());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_old_lock_of_running_process_is_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        // A long operation of a live process (our parent, which is not in HELD)
+        let pid = std::os::unix::process::parent_id();
+        fs::write(&lock_file, format!("pid: {}\nagent: slow\ncreated: 1\ntoken: x\n", pid)).unwrap();
+
+        let err = CapsaLock::acquire_with(temp_dir.path(), Duration::from_millis(50), Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(CapsaLock::read_info(&lock_file).unwrap().token, "x");
+    }
+
+    #[test]
+    fn test_drop_keeps_lock_of_another_holder() {
+        let temp_dir = TempDir::new().unwrap();
+        let lock_file = CapsaLock::file(temp_dir.path());
+        let lock = CapsaLock::acquire(temp_dir.path()).unwrap();
+        assert!(!CapsaLock::read_info(&lock_file).unwrap().token.is_empty());
+
+        // Our lock was broken and someone else now holds the capsa
+        fs::remove_file(&lock_file).unwrap();
+        fs::write(&lock_file, format!("pid: {}\nagent: other\ncreated: {}\ntoken: other\n", DEAD_PID, now_secs())).unwrap();
+        drop(lock);
+
+        assert_eq!(CapsaLock::read_info(&lock_file).unwrap().token, "other");
+        // Nothing left aside
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+}
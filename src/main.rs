@@ -7,22 +7,37 @@ fn main() -> std::io::Result<()> {
     let ctx = ResolveContext::new(home_path, cli.global, cli.json);
 
     match cli.command {
-        Command::Daily { title } => cmd::daily::run(&ctx, cli.caps.as_deref(), title),
-        Command::Note { title, source } => cmd::note::run(&ctx, cli.caps.as_deref(), title, source),
+        Command::Daily { title, args } => {
+            cmd::daily::run(&ctx, cli.caps.as_deref(), title, args)
+        }
+        Command::Note { title, source, args } => {
+            cmd::note::run(&ctx, cli.caps.as_deref(), title, source, args)
+        }
         Command::Resolve { note_name } => cmd::note_resolve::run(&ctx, cli.caps.as_deref(), note_name),
         Command::List { filter } => cmd::list::run(&ctx, cli.caps.as_deref(), filter),
         Command::Print { note_name } => {
             cmd::print::run(&ctx, cli.caps.as_deref(), note_name)
         }
+        Command::Edit { note_ref, ops } => cmd::edit::run(&ctx, cli.caps.as_deref(), &note_ref, ops),
+        Command::Patch { note_ref, fuzz, dry_run } => {
+            cmd::patch::run(&ctx, cli.caps.as_deref(), &note_ref, fuzz, dry_run)
+        }
+        Command::Mv { note_ref, new_name, dry_run } => {
+            cmd::mv::run(&ctx, cli.caps.as_deref(), &note_ref, &new_name, dry_run)
+        }
+        Command::Rm { note_ref, force } => cmd::rm::run(&ctx, cli.caps.as_deref(), &note_ref, force),
+        Command::Search(args) => cmd::search::run(&ctx, cli.caps.as_deref(), &args),
         Command::Meta { note_ref, key, value, delete } => {
             cmd::meta::run(&ctx, cli.caps.as_deref(), note_ref, key, value, delete)
         }
         Command::Capsa(cmd) => cmd::capsa::run(&ctx, cmd),
         Command::Default { caps } => cmd::default::run(&ctx, caps),
+        Command::Config(config_cmd) => cmd::config::run(&ctx, cli.caps.as_deref(), config_cmd),
         Command::Gc { days, execute, force, verbose } => {
             cmd::gc::run(&ctx, cli.caps.as_deref(), days, execute, force, verbose)
         }
         Command::Tag(tag_cmd) => cmd::tag::run(&ctx, cli.caps.as_deref(), tag_cmd),
+        Command::Trash(trash_cmd) => cmd::trash::run(&ctx, cli.caps.as_deref(), trash_cmd),
         Command::Link(link_cmd) => cmd::link::run(&ctx, cli.caps.as_deref(), &link_cmd),
         Command::Task(task_cmd) => cmd::task::run(&ctx, cli.caps.as_deref(), task_cmd),
     }
@@ -34,12 +49,19 @@ mod cmd {
     pub mod note_resolve;
     pub mod list;
     pub mod print;
+    pub mod edit;
+    pub mod patch;
+    pub mod mv;
+    pub mod rm;
     pub mod meta;
     pub mod capsa;
     pub mod default;
+    pub mod config;
     pub mod tag;
+    pub mod trash;
     pub mod gc;
     pub mod link;
     pub mod resolve;
     pub mod task;
+    pub mod search;
 }
@@ -2,7 +2,15 @@
 //!
 //! Provides unified markdown parsing functions for the codebase.
 
-use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::ops::Range;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{Event, HeadingLevel, LinkType, Parser, Tag, TagEnd};
+use regex::Regex;
+
+/// `href` / `src` attribute of an HTML tag, quoted or not
+static HTML_LINK_ATTR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)\s(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+))"#).expect("valid regex")
+});
 
 /// Represents a markdown heading
 #[derive(Debug, Clone, PartialEq)]
@@ -13,6 +21,17 @@ pub struct MarkdownHeading {
     pub text: String,
 }
 
+/// An ATX heading (`## Text`) and the line it is on
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct HeadingLine {
+    /// Line number (0-indexed, as in `find_heading_line`)
+    pub line: usize,
+    /// Heading level (1-6)
+    pub level: u8,
+    /// Heading text, without the closing `#`s
+    pub text: String,
+}
+
 /// Represents a markdown link
 #[derive(Debug, Clone, PartialEq)]
 pub struct MarkdownLink {
@@ -24,56 +43,261 @@ pub struct MarkdownLink {
     pub is_reference: bool,
 }
 
-/// Extract link reference definitions from markdown content
-///
-/// Reference definitions are in the format `[id]: dest "optional title"`
-/// Returns a list of (id, dest) pairs.
-pub fn extract_references(content: &str) -> Vec<(String, String)> {
-    let mut references = Vec::new();
-    let mut in_ref_def = false;
-    let mut current_id = String::new();
-    let mut current_dest = String::new();
+/// Location of a link destination in markdown source, for rewriting it in place
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkSpan {
+    /// Link destination as written
+    pub dest: String,
+    /// Byte range of the destination in the content
+    pub range: Range<usize>,
+    /// Line number (1-based)
+    pub line: usize,
+    /// Whether this is a reference definition (`[id]: dest`) rather than an inline link
+    pub is_definition: bool,
+}
 
-    for event in Parser::new(content) {
-        match event {
-            Event::Start(Tag::Link { link_type, dest_url, .. }) => {
-                // Check if this is a reference definition
-                if matches!(link_type, pulldown_cmark::LinkType::Reference | pulldown_cmark::LinkType::ReferenceUnknown) {
-                    in_ref_def = true;
-                    current_dest = dest_url.to_string();
-                }
-            }
-            Event::End(TagEnd::Link) => {
-                if in_ref_def && !current_id.is_empty() {
-                    references.push((current_id.clone(), current_dest.clone()));
-                }
-                in_ref_def = false;
-                current_id.clear();
-                current_dest.clear();
+/// An Obsidian-style wiki link: `[[note]]`, `[[note#heading]]`, `[[note|alias]]`
+/// or an embed `![[note]]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiLink {
+    /// Note reference (empty for a link to a heading of the same note, `[[#heading]]`)
+    pub target: String,
+    /// Heading or block after `#`
+    pub heading: Option<String>,
+    /// Display text after `|`
+    pub alias: Option<String>,
+    /// Whether this is an embed (`![[...]]`)
+    pub embed: bool,
+    /// Byte range of the whole link, including brackets and the `!` of embeds
+    pub range: Range<usize>,
+    /// Line number (1-based)
+    pub line: usize,
+}
+
+/// Find wiki links (`[[...]]`), skipping code spans and code blocks
+pub fn wiki_links(content: &str) -> Vec<WikiLink> {
+    // Byte ranges of code, where `[[` is literal text
+    let code: Vec<Range<usize>> = Parser::new(content).into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect();
+
+    let mut links = Vec::new();
+    let mut search_from = 0;
+    while let Some(found) = content[search_from..].find("[[") {
+        let open = search_from + found;
+        let inner_start = open + 2;
+        let Some(len) = content[inner_start..].find("]]") else { break };
+        let inner = &content[inner_start..inner_start + len];
+        let end = inner_start + len + 2;
+        search_from = inner_start;
+
+        if inner.trim().is_empty() || inner.contains(['\n', '[', ']']) || code.iter().any(|r| r.contains(&open)) {
+            continue;
+        }
+        search_from = end;
+
+        let embed = content[..open].ends_with('!');
+        let (reference, alias) = match inner.split_once('|') {
+            Some((reference, alias)) => (reference, Some(alias.trim().to_string())),
+            None => (inner, None),
+        };
+        let (target, heading) = match reference.split_once('#') {
+            Some((target, heading)) => (target, Some(heading.trim().to_string())),
+            None => (reference, None),
+        };
+
+        let start = if embed { open - 1 } else { open };
+        links.push(WikiLink {
+            target: target.trim().to_string(),
+            heading,
+            alias,
+            embed,
+            range: start..end,
+            line: line_at(content, open),
+        });
+    }
+
+    links
+}
+
+/// Find the destinations of inline links, images, reference definitions and
+/// `href`/`src` attributes of raw HTML (`<a href>`, `<img src>`)
+///
+/// Links inside code spans and code blocks are skipped. Destinations written
+/// with escapes are skipped too, since their source text differs from the
+/// parsed destination.
+pub fn link_spans(content: &str) -> Vec<LinkSpan> {
+    let mut spans = Vec::new();
+
+    for (event, range) in Parser::new(content).into_offset_iter() {
+        let dest_url = match event {
+            Event::Start(Tag::Link { link_type: LinkType::Inline, dest_url, .. })
+            | Event::Start(Tag::Image { link_type: LinkType::Inline, dest_url, .. }) => dest_url,
+            Event::Html(_) | Event::InlineHtml(_) => {
+                spans.extend(html_spans(content, range));
+                continue;
             }
-            _ => {}
+            _ => continue,
+        };
+        if dest_url.is_empty() {
+            continue;
+        }
+
+        // The destination follows the last `](` whose text starts with it
+        let source = &content[range.clone()];
+        let found = source.match_indices("](").filter_map(|(pos, _)| {
+            let after = &source[pos + 2..];
+            let rest = after.trim_start();
+            let rest_start = pos + 2 + (after.len() - rest.len());
+            let start = if rest.starts_with('<') { rest_start + 1 } else { rest_start };
+            source[start..].starts_with(dest_url.as_ref()).then_some(start)
+        }).last();
+
+        if let Some(start) = found {
+            let start = range.start + start;
+            spans.push(LinkSpan {
+                dest: dest_url.to_string(),
+                range: start..start + dest_url.len(),
+                line: line_at(content, start),
+                is_definition: false,
+            });
         }
     }
 
-    // Also parse raw reference definitions manually since pulldown-cmark
-    // might not expose them as link events in all cases
-    for line in content.lines() {
-        let trimmed = line.trim();
-        // Match: [id]: dest or [id]:dest
-        if trimmed.starts_with('[') && trimmed.contains("]:") {
-            if let Some(end_bracket) = trimmed.find(']') {
-                let id = trimmed[1..end_bracket].to_string();
-                let after_bracket = &trimmed[end_bracket + 1..];
-                if let Some(rest) = after_bracket.strip_prefix(':') {
-                    let dest = rest.trim().split_whitespace().next().unwrap_or("");
-                    if !dest.is_empty() && !references.iter().any(|(ref_id, _)| ref_id == &id) {
-                        references.push((id, dest.to_string()));
-                    }
-                }
+    spans.extend(definition_spans(content));
+    spans.sort_by_key(|span| span.range.start);
+    spans
+}
+
+/// Find reference definitions line by line, outside fenced code blocks
+fn definition_spans(content: &str) -> Vec<LinkSpan> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    let mut in_fence = false;
+
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence || line.len() - trimmed.len() > 3 || !trimmed.starts_with('[') {
+            continue;
+        }
+
+        // Match: [id]: dest or [id]: <dest>
+        let Some(colon) = trimmed.find("]:") else { continue };
+        if trimmed[1..colon].is_empty() || trimmed[1..colon].contains(['[', ']']) {
+            continue;
+        }
+        let after = &trimmed[colon + 2..];
+        let rest = after.trim_start();
+        let mut start = line_start + (line.len() - trimmed.len()) + colon + 2 + (after.len() - rest.len());
+        let dest = if let Some(inner) = rest.strip_prefix('<') {
+            start += 1;
+            inner.split('>').next().unwrap_or("")
+        } else {
+            rest.split_whitespace().next().unwrap_or("")
+        };
+        if dest.is_empty() {
+            continue;
+        }
+
+        spans.push(LinkSpan {
+            dest: dest.to_string(),
+            range: start..start + dest.len(),
+            line: index + 1,
+            is_definition: true,
+        });
+    }
+
+    spans
+}
+
+/// Find `href`/`src` attributes in a piece of raw HTML
+///
+/// Values with character references (`&amp;`) are skipped, since their
+/// source text differs from the URL they stand for.
+fn html_spans(content: &str, range: Range<usize>) -> Vec<LinkSpan> {
+    HTML_LINK_ATTR.captures_iter(&content[range.clone()])
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)).or_else(|| caps.get(3)))
+        .filter(|value| !value.as_str().is_empty() && !value.as_str().contains('&'))
+        .map(|value| {
+            let start = range.start + value.start();
+            LinkSpan {
+                dest: value.as_str().to_string(),
+                range: start..start + value.len(),
+                line: line_at(content, start),
+                is_definition: false,
             }
+        })
+        .collect()
+}
+
+/// 1-based line number of a byte offset
+fn line_at(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+/// A reference definition of a task file: `[TASK-01]: node_ref`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskReference {
+    pub id: String,
+    /// Note reference, up to the end of the line
+    pub node_ref: String,
+    /// Byte range of the note reference in the content
+    pub range: Range<usize>,
+    /// Line number (1-based)
+    pub line: usize,
+}
+
+/// Find the reference definitions of a task file
+///
+/// Unlike a CommonMark definition, the note reference runs to the end of the
+/// line: `task add "Weekly Sync"` writes `[TASK-01]: Weekly Sync`.
+pub fn task_references(content: &str) -> Vec<TaskReference> {
+    let mut references = Vec::new();
+    let mut offset = 0;
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+
+        let text = line.trim_end();
+        let indent = text.len() - text.trim_start().len();
+        let Some(rest) = text[indent..].strip_prefix('[') else { continue };
+        let Some(end_bracket) = rest.find(']') else { continue };
+        let Some(value) = rest[end_bracket + 1..].strip_prefix(':') else { continue };
+        let node_ref = value.trim();
+        if node_ref.is_empty() {
+            continue;
         }
+
+        let start = line_start + indent + 1 + end_bracket + 2 + (value.len() - value.trim_start().len());
+        references.push(TaskReference {
+            id: rest[..end_bracket].to_string(),
+            node_ref: node_ref.to_string(),
+            range: start..start + node_ref.len(),
+            line: i + 1,
+        });
     }
+    references
+}
 
+/// Extract the reference definitions of a task file as (id, node_ref) pairs,
+/// the first one of each id, see `task_references`
+pub fn extract_references(content: &str) -> Vec<(String, String)> {
+    let mut references: Vec<(String, String)> = Vec::new();
+    for reference in task_references(content) {
+        if !references.iter().any(|(id, _)| *id == reference.id) {
+            references.push((reference.id, reference.node_ref));
+        }
+    }
     references
 }
 
@@ -170,25 +394,11 @@ pub fn has_reference(content: &str, ref_id: &str) -> bool {
     get_reference_dest(content, ref_id).is_some()
 }
 
-/// Get the destination of a reference definition
+/// Get the note reference of a task file reference definition
 pub fn get_reference_dest(content: &str, ref_id: &str) -> Option<String> {
-    // Parse manually to get reference definitions
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with('[') && trimmed.contains("]:") {
-            if let Some(end_bracket) = trimmed.find(']') {
-                let id = &trimmed[1..end_bracket];
-                if id.eq_ignore_ascii_case(ref_id) {
-                    let after_bracket = &trimmed[end_bracket + 1..];
-                    if let Some(rest) = after_bracket.strip_prefix(':') {
-                        let dest = rest.trim().split_whitespace().next()?;
-                        return Some(dest.to_string());
-                    }
-                }
-            }
-        }
-    }
-    None
+    task_references(content).into_iter()
+        .find(|reference| reference.id.eq_ignore_ascii_case(ref_id))
+        .map(|reference| reference.node_ref)
 }
 
 /// Find the line number of a specific heading
@@ -217,6 +427,161 @@ pub fn find_heading_line(content: &str, heading_text: &str, level: Option<u8>) -
     None
 }
 
+/// Find the ATX headings of a note, line by line
+///
+/// Unlike `find_heading_line`, a heading needs a space after its `#`s (so
+/// `#tag` lines are not headings), and lines in the frontmatter and in fenced
+/// code blocks are skipped.
+pub fn heading_lines(content: &str) -> Vec<HeadingLine> {
+    let mut headings = Vec::new();
+    let mut in_fence = false;
+    let mut in_frontmatter = content.starts_with("---");
+
+    for (line_num, line) in content.lines().enumerate() {
+        if in_frontmatter {
+            if line_num > 0 && line.trim_end() == "---" {
+                in_frontmatter = false;
+            }
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence || line.len() - trimmed.len() > 3 {
+            continue;
+        }
+
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let rest = &trimmed[level..];
+        if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+            continue;
+        }
+
+        headings.push(HeadingLine {
+            line: line_num,
+            level: level as u8,
+            text: rest.trim().trim_end_matches('#').trim_end().to_string(),
+        });
+    }
+
+    headings
+}
+
+/// GitHub-style anchor of a heading: lowercased, spaces turned into `-`,
+/// punctuation other than `-` and `_` dropped (`## Part 2: Setup` → `part-2-setup`)
+pub fn heading_anchor(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Anchors of a note's headings, in order. Repeated anchors get a `-1`,
+/// `-2`, ... suffix as on GitHub.
+pub fn heading_anchors(headings: &[HeadingLine]) -> Vec<String> {
+    let mut anchors: Vec<String> = Vec::new();
+    for heading in headings {
+        let base = heading_anchor(&heading.text);
+        let mut anchor = base.clone();
+        let mut n = 0;
+        while anchors.contains(&anchor) {
+            n += 1;
+            anchor = format!("{}-{}", base, n);
+        }
+        anchors.push(anchor);
+    }
+    anchors
+}
+
+/// Inline `#hashtags` of a note's body, without `#`, in order of first appearance
+///
+/// The frontmatter, code, headings, links, wiki links and HTML blocks are skipped. A `#`
+/// inside a word or a URL (`page#anchor`, `site/#route`, `&#38;`) or escaped
+/// (`\#`) does not start a tag, and neither do numbers alone (`#42`).
+pub fn inline_tags(content: &str) -> Vec<String> {
+    let body = crate::frontmatter::split(content).map_or(content, |(_, body)| body);
+    let wiki: Vec<Range<usize>> = wiki_links(body).into_iter().map(|link| link.range).collect();
+    let mut tags = Vec::new();
+    let mut text = String::new();
+    let mut skipped = 0usize;
+
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        match event {
+            // Source text, where entities are still as written; the
+            // backslash of an escape comes just before the text
+            Event::Text(_) if skipped == 0 => {
+                if body[..range.start].ends_with('\\') {
+                    text.push('\\');
+                }
+                // Wiki links (`[[#Heading]]`, `[[note#Heading]]`) are left out
+                let mut start = range.start;
+                for link in wiki.iter().filter(|link| link.start < range.end && link.end > range.start) {
+                    if link.start > start {
+                        text.push_str(&body[start..link.start]);
+                    }
+                    text.push(' ');
+                    start = start.max(link.end);
+                }
+                if start < range.end {
+                    text.push_str(&body[start..range.end]);
+                }
+                continue;
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                text.push(' ');
+                continue;
+            }
+            Event::Start(Tag::CodeBlock(_) | Tag::Heading { .. } | Tag::Link { .. } | Tag::Image { .. } | Tag::HtmlBlock) => {
+                skipped += 1;
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::Heading(_) | TagEnd::Link | TagEnd::Image | TagEnd::HtmlBlock) => {
+                skipped = skipped.saturating_sub(1);
+            }
+            _ => {}
+        }
+        // Code spans, HTML and block boundaries end a run of text
+        hashtags(&text, &mut tags);
+        text.clear();
+    }
+    hashtags(&text, &mut tags);
+
+    tags
+}
+
+/// Add the hashtags of a run of text to `tags`
+fn hashtags(text: &str, tags: &mut Vec<String>) {
+    let mut prev: Option<char> = None;
+    for (i, c) in text.char_indices() {
+        let starts_tag = c == '#' && match prev {
+            Some(p) => !p.is_alphanumeric() && !"/#&_:.=@\\-".contains(p),
+            None => true,
+        };
+        prev = Some(c);
+        if !starts_tag {
+            continue;
+        }
+
+        let rest = &text[i + 1..];
+        let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-')).unwrap_or(rest.len());
+        let tag = rest[..end].trim_end_matches('-');
+        if tag.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            && !tag.chars().all(|c| c.is_ascii_digit())
+            && !tags.iter().any(|t| t == tag)
+        {
+            tags.push(tag.to_string());
+        }
+    }
+}
+
 /// Extract the prefix from a frontmatter PREFIX field
 pub fn extract_frontmatter_prefix(content: &str) -> Option<String> {
     let mut found_prefix = None;
@@ -263,6 +628,18 @@ mod tests {
         assert_eq!(refs[1], ("task-02".to_string(), "notes/note2.md".to_string()));
     }
 
+    #[test]
+    fn test_task_references_run_to_end_of_line() {
+        let content = "- [TASK-01] Sync\n\n[TASK-01]: Weekly Sync  \n[TASK-02]:idea\n";
+        let refs = task_references(content);
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].node_ref, "Weekly Sync");
+        assert_eq!(&content[refs[0].range.clone()], "Weekly Sync");
+        assert_eq!(refs[0].line, 3);
+        assert_eq!(&content[refs[1].range.clone()], "idea");
+        assert_eq!(get_reference_dest(content, "task-01").as_deref(), Some("Weekly Sync"));
+    }
+
     #[test]
     fn test_extract_headings() {
         let content = r#"
@@ -309,15 +686,103 @@ mod tests {
         assert_eq!(find_heading_line(content, "Section", Some(1)), None);
     }
 
+    #[test]
+    fn test_wiki_links() {
+        let content = "See [[Idea]] and [[note/other#Part 2|the other]].\n\n![[diagram.png]] `[[code]]`\n\n```\n[[fenced]]\n```\n[[#Local]] [[ ]] [[a\nb]]\n";
+        let links = wiki_links(content);
+        assert_eq!(links.len(), 4);
+
+        assert_eq!(links[0].target, "Idea");
+        assert_eq!(&content[links[0].range.clone()], "[[Idea]]");
+        assert_eq!(links[0].line, 1);
+
+        assert_eq!(links[1].target, "note/other");
+        assert_eq!(links[1].heading.as_deref(), Some("Part 2"));
+        assert_eq!(links[1].alias.as_deref(), Some("the other"));
+
+        assert!(links[2].embed);
+        assert_eq!(&content[links[2].range.clone()], "![[diagram.png]]");
+        assert_eq!(links[2].line, 3);
+
+        assert_eq!(links[3].target, "");
+        assert_eq!(links[3].heading.as_deref(), Some("Local"));
+    }
+
+    #[test]
+    fn test_heading_anchors() {
+        assert_eq!(heading_anchor("Part 2: Setup"), "part-2-setup");
+        assert_eq!(heading_anchor("`code` & **bold**_x"), "code--bold_x");
+        assert_eq!(heading_anchor("项目 会议"), "项目-会议");
+
+        let headings = heading_lines("# Notes\n\n## Notes\n\n## Notes\n\n## Notes-1\n");
+        assert_eq!(heading_anchors(&headings), vec!["notes", "notes-1", "notes-2", "notes-1-1"]);
+    }
+
+    #[test]
+    fn test_heading_lines() {
+        let content = "---\n# not: heading\n---\n# Title\n#tag\n```\n## Code\n```\n## Section ##\n";
+        let headings = heading_lines(content);
+        assert_eq!(headings, vec![
+            HeadingLine { line: 3, level: 1, text: "Title".to_string() },
+            HeadingLine { line: 8, level: 2, text: "Section".to_string() },
+        ]);
+    }
+
     #[test]
     fn test_extract_frontmatter_prefix() {
         let content = "---\nPREFIX: task-\n---\n\nContent";
         assert_eq!(extract_frontmatter_prefix(content), Some("task-".to_string()));
     }
 
+    #[test]
+    fn test_link_spans() {
+        let content = "# Links\n\nSee [a](note/a.md#intro) and ![img](<pics/b c.png>).\n\n`[code](skip.md)`\n\n[ref]: ../c.md \"Title\"\n";
+        let spans = link_spans(content);
+        let dests: Vec<&str> = spans.iter().map(|s| s.dest.as_str()).collect();
+        assert_eq!(dests, vec!["note/a.md#intro", "pics/b c.png", "../c.md"]);
+        for span in &spans {
+            assert_eq!(&content[span.range.clone()], span.dest);
+        }
+        assert_eq!(spans[0].line, 3);
+        assert!(spans[2].is_definition);
+        assert_eq!(spans[2].line, 7);
+    }
+
+    #[test]
+    fn test_link_spans_skip_fenced_definitions() {
+        let content = "```\n[x]: in-code.md\n```\n[TASK-01]: my-note\n";
+        let spans = link_spans(content);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].dest, "my-note");
+    }
+
+    #[test]
+    fn test_link_spans_html() {
+        let content = "<p align=\"center\">\n  <img src='pics/logo.png' width=80>\n</p>\n\nSee <a class=x HREF=note/a.md>a</a>, <a href=\"?a=1&amp;b=2\">q</a>.\n\n`<a href=\"code.md\">`\n";
+        let spans = link_spans(content);
+        let dests: Vec<&str> = spans.iter().map(|s| s.dest.as_str()).collect();
+        assert_eq!(dests, vec!["pics/logo.png", "note/a.md"]);
+        for span in &spans {
+            assert_eq!(&content[span.range.clone()], span.dest);
+        }
+        assert_eq!(spans[1].line, 5);
+    }
+
     #[test]
     fn test_extract_frontmatter_prefix_none() {
         let content = "# No frontmatter\n\nContent";
         assert_eq!(extract_frontmatter_prefix(content), None);
     }
+
+    #[test]
+    fn test_inline_tags() {
+        let content = "---\ntags: [front]\n# yaml #comment\n---\n# Title #heading\n\n\
+            Working on #rust and #cli-tools, (#标签) *#emphasis* #rust again.\n\
+            Not tags: issue #42, a#b, https://example.com/page#anchor, site/#route, &#38;, `#code`.\n\
+            [#linked](note.md) and <span>#html</span> \\#escaped\n\
+            See [[#Local]], [[note#Heading]] and ![[#Part Two|#alias]].\n\n\
+            ```\n#fenced\n```\n\n\
+            - list item #todo\n";
+        assert_eq!(inline_tags(content), vec!["rust", "cli-tools", "标签", "emphasis", "html", "todo"]);
+    }
 }
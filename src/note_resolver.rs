@@ -5,13 +5,29 @@
 //! Resolution rules (in order):
 //! 1. Full timestamp (YYYYMMDDHHmmSS) → #daily/YYYYMMDD/HHmmSS*.md
 //! 2. Time only (HHmmSS) → #daily/{current_date}/HHmmSS*.md
-//! 3. Title slug → prefix match in #daily/{current_date}/, then note/, then search index files
+//! 3. Title slug → exact stem or filename match in #daily/{current_date}/, then note/; then
+//!    exact title match in the same directories; then prefix match, then search index files,
+//!    then token match on filename/title (CJK bigrams, stemmed words)
+//!
+//! An exact match always wins over prefix matches, so `idea` resolves to `idea.md` even
+//! when `idea-2.md` exists.
+//!
+//! `#daily/` stands for the capsa's `daily_dir` setting.
+//!
+//! Title slugs follow the capsa's slug style (see `config.rs`).
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use chrono::{Local, NaiveDateTime, TimeZone};
 
+use crate::config::CapsaConfig;
+use crate::link_cache::LinkCache;
+use crate::tokenizer::tokenize;
+
 /// Get current date, allowing override via EMX_TASK_TIMESTAMP for testing
 fn get_current_date() -> String {
     if let Ok(ts) = std::env::var("EMX_TASK_TIMESTAMP") {
@@ -34,6 +50,62 @@ pub enum ResolvedNote {
     NotFound,
 }
 
+/// Area of the capsa a note lives in, derived from its relative path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteArea {
+    /// `#daily/YYYYMMDD/*.md` (the `daily_dir` setting)
+    Daily,
+    /// `note/*.md`
+    Permanent,
+    /// `note/{hash}/*.md` (notes with a source)
+    Literature,
+}
+
+impl NoteArea {
+    /// Classify a capsa-relative path (forward slashes), with daily notes
+    /// under `daily_dir`.
+    /// Returns None for files outside the note areas (tag indexes, TASK.md, ...)
+    pub fn from_relative(relative: &str, daily_dir: &str) -> Option<Self> {
+        let parts: Vec<&str> = relative.split('/').collect();
+        let file_name = parts.last()?;
+        if file_name.starts_with('#') || file_name.starts_with('.') {
+            return None;
+        }
+
+        match parts.as_slice() {
+            [dir, date, _] if *dir == daily_dir && validate_date(date) => {
+                Some(NoteArea::Daily)
+            }
+            [dir, _] if *dir == crate::constants::NOTE_SUBDIR => Some(NoteArea::Permanent),
+            [dir, _, _] if *dir == crate::constants::NOTE_SUBDIR => Some(NoteArea::Literature),
+            _ => None,
+        }
+    }
+
+    /// Name used in CLI filters and JSON output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoteArea::Daily => "daily",
+            NoteArea::Permanent => "permanent",
+            NoteArea::Literature => "literature",
+        }
+    }
+}
+
+impl std::str::FromStr for NoteArea {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "daily" => Ok(NoteArea::Daily),
+            "permanent" => Ok(NoteArea::Permanent),
+            "literature" => Ok(NoteArea::Literature),
+            _ => Err(format!("Unknown area '{}' (expected daily, permanent or literature)", s)),
+        }
+    }
+}
+
 /// Resolve a note and return the path if found, or an error with helpful message
 /// This helper centralizes the error handling logic used across multiple commands
 pub fn resolve_note_or_error(
@@ -121,164 +193,278 @@ pub fn resolve_note_with_force(
     }
 }
 
+/// Resolve the target of a wiki link (`[[target]]`)
+///
+/// A target that looks like a path (`note/idea`, `idea.md`, `diagram.png`) is
+/// first looked up relative to the capsa root, with or without a note
+/// extension. Anything else, or a path that does not exist, follows the
+/// `resolve_note` rules.
+pub fn resolve_link_target(
+    capsa_path: &Path,
+    target: &str,
+    extensions: &[&str],
+) -> io::Result<ResolvedNote> {
+    NoteResolver::new(capsa_path, extensions)?.resolve_link_target(target)
+}
+
 /// Resolve a note reference to a file path
 pub fn resolve_note(
     capsa_path: &Path,
     reference: &str,
     extensions: &[&str],
 ) -> io::Result<ResolvedNote> {
-    // Normalize path separators: backslashes to forward slashes (cross-platform)
-    let reference = reference.trim().replace('\\', "/");
-
-    // Rule 0: Path format YYYYMMDD/prefix (date + prefix match in that date's directory)
-    if reference.contains('/') {
-        let parts: Vec<&str> = reference.splitn(2, '/').collect();
-        if parts.len() == 2 {
-            let date = parts[0].trim();
-            let prefix = parts[1].trim();
-            // Date must be 8 digits
-            if date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()) {
-                if validate_date(date) {
-                    // Slugify the prefix for matching
-                    let slug = crate::slugify(prefix);
-                    return resolve_in_date_dir(capsa_path, date, &slug, extensions);
-                }
-            }
+    NoteResolver::new(capsa_path, extensions)?.resolve_note(reference)
+}
+
+/// Resolves note references and link targets of one capsa
+///
+/// The capsa settings are loaded once, and directory listings, name and
+/// title maps and title tokens are kept between lookups: commands resolving
+/// every link of a capsa (link check, orphans, gc) build one resolver per run
+/// instead of rescanning the capsa for each link.
+pub struct NoteResolver {
+    capsa_path: PathBuf,
+    config: CapsaConfig,
+    extensions: Vec<String>,
+    today: String,
+    /// Files directly in a directory
+    listings: RefCell<HashMap<PathBuf, Rc<Vec<PathBuf>>>>,
+    /// Notes of a directory by file stem (and stem without HHmmSS- prefix)
+    stems: RefCell<HashMap<(PathBuf, bool), Rc<NameMap>>>,
+    /// Notes of a directory by lowercased title
+    title_maps: RefCell<HashMap<PathBuf, Rc<NameMap>>>,
+    titles: RefCell<HashMap<PathBuf, String>>,
+    tokens: RefCell<HashMap<PathBuf, Rc<HashSet<String>>>>,
+    /// Content of index files
+    contents: RefCell<HashMap<PathBuf, Rc<String>>>,
+}
+
+type NameMap = HashMap<String, Vec<PathBuf>>;
+
+impl NoteResolver {
+    /// Resolver trying `extensions` for note files
+    pub fn new(capsa_path: &Path, extensions: &[&str]) -> io::Result<Self> {
+        let config = CapsaConfig::load(capsa_path)?;
+        let extensions = extensions.iter().map(|ext| ext.to_string()).collect();
+        Ok(Self::with_config(capsa_path, config, extensions))
+    }
+
+    /// Resolver with the extensions of the capsa settings
+    pub fn open(capsa_path: &Path) -> io::Result<Self> {
+        let config = CapsaConfig::load(capsa_path)?;
+        let extensions = config.extensions().into_iter().map(str::to_string).collect();
+        Ok(Self::with_config(capsa_path, config, extensions))
+    }
+
+    /// Resolver with the extensions of the capsa settings, taking note titles
+    /// from the link cache instead of reading the notes
+    pub fn from_cache(capsa_path: &Path, cache: &LinkCache) -> io::Result<Self> {
+        let resolver = Self::open(capsa_path)?;
+        resolver.titles.borrow_mut().extend(
+            cache.files().map(|(relative, cached)| (capsa_path.join(relative), cached.title.clone())),
+        );
+        Ok(resolver)
+    }
+
+    fn with_config(capsa_path: &Path, config: CapsaConfig, extensions: Vec<String>) -> Self {
+        NoteResolver {
+            capsa_path: capsa_path.to_path_buf(),
+            config,
+            extensions,
+            today: get_current_date(),
+            listings: RefCell::default(),
+            stems: RefCell::default(),
+            title_maps: RefCell::default(),
+            titles: RefCell::default(),
+            tokens: RefCell::default(),
+            contents: RefCell::default(),
         }
     }
 
-    // Rule 1: Full timestamp YYYYMMDDHHmmSS (14 digits)
-    if let Some((date, time)) = parse_full_timestamp(&reference) {
-        return resolve_by_timestamp(capsa_path, &date, &time, extensions);
+    /// Root of the capsa
+    pub fn capsa_path(&self) -> &Path {
+        &self.capsa_path
     }
 
-    // Rule 2: Time prefix HHmmSS... (starts with 6 digits, or fewer digits)
-    // Check if it starts with digits (time prefix)
-    if let Some(time_prefix) = extract_time_prefix(&reference) {
-        let today = get_current_date();
-        return resolve_in_date_dir(capsa_path, &today, &time_prefix, extensions);
+    /// Settings of the capsa
+    pub fn config(&self) -> &CapsaConfig {
+        &self.config
     }
 
-    // Rule 3: Title slug (prefix match)
-    let slug = crate::slugify(&reference);
-    resolve_by_title(capsa_path, &slug, extensions)
-}
+    /// Extensions tried for note files
+    pub fn extensions(&self) -> Vec<&str> {
+        self.extensions.iter().map(String::as_str).collect()
+    }
 
-/// Extract time prefix from input (HH, HHmm, or HHmmSS format)
-/// Returns None if input doesn't start with digits
-fn extract_time_prefix(s: &str) -> Option<String> {
-    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
-    if !digits.is_empty() && digits.len() <= 6 {
-        Some(digits)
-    } else {
-        None
+    /// Resolve the target of a wiki link, see `resolve_link_target`
+    pub fn resolve_link_target(&self, target: &str) -> io::Result<ResolvedNote> {
+        let target = target.trim().replace('\\', "/");
+        let relative = target.trim_start_matches('/');
+
+        let escapes = relative.split('/').any(|part| part == "..");
+        if !escapes && (relative.contains('/') || relative.contains('.')) {
+            let candidates = std::iter::once(relative.to_string())
+                .chain(self.extensions.iter().map(|ext| format!("{}{}", relative, ext)));
+            for candidate in candidates {
+                let path = self.capsa_path.join(&candidate);
+                if path.is_file() {
+                    return Ok(ResolvedNote::Found(path));
+                }
+            }
+        }
+
+        self.resolve_note(&target)
     }
-}
 
-/// Parse full timestamp YYYYMMDDHHmmSS
-fn parse_full_timestamp(s: &str) -> Option<(String, String)> {
-    if s.len() >= 14 && s.chars().all(|c| c.is_ascii_digit()) {
-        let date = s[0..8].to_string();
-        let time = s[8..14].to_string();
-        // Validate date/time ranges
-        if validate_date(&date) && validate_time(&time) {
-            return Some((date, time));
+    /// Resolve a note reference to a file path, see `resolve_note`
+    pub fn resolve_note(&self, reference: &str) -> io::Result<ResolvedNote> {
+        // Normalize path separators: backslashes to forward slashes (cross-platform)
+        let reference = reference.trim().replace('\\', "/");
+
+        // Rule 0: Path format YYYYMMDD/prefix (date + prefix match in that date's directory)
+        if reference.contains('/') {
+            let parts: Vec<&str> = reference.splitn(2, '/').collect();
+            if parts.len() == 2 {
+                let date = parts[0].trim();
+                let prefix = parts[1].trim();
+                // Date must be 8 digits
+                if date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()) {
+                    if validate_date(date) {
+                        // Slugify the prefix for matching
+                        let slug = self.config.slugify(prefix);
+                        let daily_dir = self.capsa_path.join(&self.config.daily_dir).join(date);
+                        let exact = self.find_exact(&daily_dir, &[slug.as_str(), prefix], true)?;
+                        if !matches!(exact, ResolvedNote::NotFound) {
+                            return Ok(exact);
+                        }
+                        return self.resolve_in_date_dir(date, &slug);
+                    }
+                }
+            }
+        }
+
+        // Rule 1: Full timestamp YYYYMMDDHHmmSS (14 digits)
+        if let Some((date, time)) = parse_full_timestamp(&reference) {
+            return self.resolve_by_timestamp(&date, &time);
         }
+
+        // Rule 2: Time prefix HHmmSS... (starts with 6 digits, or fewer digits)
+        // Check if it starts with digits (time prefix)
+        if let Some(time_prefix) = extract_time_prefix(&reference) {
+            return self.resolve_in_date_dir(&self.today, &time_prefix);
+        }
+
+        // Rule 3: Title slug (exact stem, filename or title, then prefix match), then title tokens
+        let slug = self.config.slugify(&reference);
+        let file_stem = self.note_stem(&reference).unwrap_or("");
+        let result = self.resolve_exact(&[slug.as_str(), reference.as_str(), file_stem])?;
+        if !matches!(result, ResolvedNote::NotFound) {
+            return Ok(result);
+        }
+        let result = self.resolve_exact_title(&reference)?;
+        if !matches!(result, ResolvedNote::NotFound) {
+            return Ok(result);
+        }
+        let result = self.resolve_by_title(&slug)?;
+        if !matches!(result, ResolvedNote::NotFound) {
+            return Ok(result);
+        }
+
+        // Rule 3d: Every query token appears in the note's filename or title
+        // (CJK substrings and inflected English words match via the tokenizer)
+        self.resolve_by_tokens(&reference)
     }
-    None
-}
 
-/// Validate date string YYYYMMDD
-fn validate_date(s: &str) -> bool {
-    if s.len() != 8 {
-        return false;
+    /// Today's daily directory, then note/, with whether names may carry a
+    /// HHmmSS- prefix
+    fn title_dirs(&self) -> [(PathBuf, bool); 2] {
+        [
+            (self.capsa_path.join(&self.config.daily_dir).join(&self.today), true),
+            (self.capsa_path.join("note"), false),
+        ]
     }
-    let year: u32 = s[0..4].parse().unwrap_or(0);
-    let month: u32 = s[4..6].parse().unwrap_or(0);
-    let day: u32 = s[6..8].parse().unwrap_or(0);
 
-    year >= 1900 && year <= 2100 && month >= 1 && month <= 12 && day >= 1 && day <= 31
-}
+    /// Files directly in `dir` (empty if it doesn't exist), listed once
+    fn files(&self, dir: &Path) -> io::Result<Rc<Vec<PathBuf>>> {
+        if let Some(files) = self.listings.borrow().get(dir) {
+            return Ok(Rc::clone(files));
+        }
 
-/// Validate time string HHmmSS
-fn validate_time(s: &str) -> bool {
-    if s.len() != 6 {
-        return false;
+        let mut files = Vec::new();
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        }
+        let files = Rc::new(files);
+        self.listings.borrow_mut().insert(dir.to_path_buf(), Rc::clone(&files));
+        Ok(files)
     }
-    let hour: u32 = s[0..2].parse().unwrap_or(0);
-    let minute: u32 = s[2..4].parse().unwrap_or(0);
-    let second: u32 = s[4..6].parse().unwrap_or(0);
 
-    hour <= 23 && minute <= 59 && second <= 59
-}
+    /// `name` without its note extension, if it has one
+    fn note_stem<'a>(&self, name: &'a str) -> Option<&'a str> {
+        self.extensions.iter().find_map(|ext| name.strip_suffix(ext.as_str()))
+    }
 
-/// Parse and validate a time string (HHmmSS format)
-#[cfg(test)]
-fn parse_time_only(s: &str) -> Option<String> {
-    if validate_time(s) {
-        Some(s.to_string())
-    } else {
-        None
+    /// Title of a note: its first H1 heading, or its file stem
+    fn title(&self, path: &Path) -> String {
+        if let Some(title) = self.titles.borrow().get(path) {
+            return title.clone();
+        }
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let title = crate::util::extract_note_title(path, &content);
+        self.titles.borrow_mut().insert(path.to_path_buf(), title.clone());
+        title
     }
-}
 
-/// Resolve by timestamp (date + time)
-fn resolve_by_timestamp(
-    capsa_path: &Path,
-    date: &str,
-    time: &str,
-    extensions: &[&str],
-) -> io::Result<ResolvedNote> {
-    // First try #daily/YYYYMMDD/ directory
-    let result = resolve_in_date_dir(capsa_path, date, time, extensions)?;
-    if !matches!(result, ResolvedNote::NotFound) {
-        return Ok(result);
+    /// Tokens of a note's file stem and title
+    fn tokens(&self, path: &Path) -> Rc<HashSet<String>> {
+        if let Some(tokens) = self.tokens.borrow().get(path) {
+            return Rc::clone(tokens);
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let mut tokens: HashSet<String> = tokenize(&stem).into_iter().collect();
+        tokens.extend(tokenize(&self.title(path)));
+        let tokens = Rc::new(tokens);
+        self.tokens.borrow_mut().insert(path.to_path_buf(), Rc::clone(&tokens));
+        tokens
     }
 
-    // Also try note/ directory for permanent notes with timestamp filenames
-    let note_dir = capsa_path.join("note");
-    if note_dir.exists() {
-        let result = find_by_prefix(&note_dir, &format!("{}{}", date, time), extensions, false)?;
+    /// Resolve by timestamp (date + time)
+    fn resolve_by_timestamp(&self, date: &str, time: &str) -> io::Result<ResolvedNote> {
+        // First try #daily/YYYYMMDD/ directory
+        let result = self.resolve_in_date_dir(date, time)?;
         if !matches!(result, ResolvedNote::NotFound) {
             return Ok(result);
         }
-    }
 
-    Ok(ResolvedNote::NotFound)
-}
-
-/// Resolve by date and prefix in the date's daily directory
-/// Prefix can be a time prefix (HH, HHmm, HHmmSS) or a title slug
-fn resolve_in_date_dir(
-    capsa_path: &Path,
-    date: &str,
-    prefix: &str,
-    extensions: &[&str],
-) -> io::Result<ResolvedNote> {
-    let daily_dir = capsa_path.join("#daily").join(date);
-
-    if !daily_dir.exists() {
-        return Ok(ResolvedNote::NotFound);
+        // Also try note/ directory for permanent notes with timestamp filenames
+        let note_dir = self.capsa_path.join("note");
+        self.find_by_prefix(&note_dir, &format!("{}{}", date, time), false)
     }
 
-    // Find files matching prefix
-    let mut candidates = Vec::new();
+    /// Resolve by date and prefix in the date's daily directory
+    /// Prefix can be a time prefix (HH, HHmm, HHmmSS) or a title slug
+    fn resolve_in_date_dir(&self, date: &str, prefix: &str) -> io::Result<ResolvedNote> {
+        let daily_dir = self.capsa_path.join(&self.config.daily_dir).join(date);
 
-    for entry in fs::read_dir(&daily_dir)? {
-        let entry = entry?;
-        let path = entry.path();
+        // Find files matching prefix
+        let mut candidates = Vec::new();
 
-        if path.is_file() {
+        for path in self.files(&daily_dir)?.iter() {
             if let Some(name) = path.file_name() {
                 let name_str = name.to_string_lossy();
 
                 // Check if starts with prefix and has valid extension
-                for ext in extensions {
-                    if !name_str.ends_with(ext) {
+                for ext in &self.extensions {
+                    if !name_str.ends_with(ext.as_str()) {
                         continue;
                     }
 
-                    let stem = name_str.strip_suffix(ext).unwrap_or(&name_str);
+                    let stem = name_str.strip_suffix(ext.as_str()).unwrap_or(&name_str);
 
                     // Match 1: Direct prefix match or hybrid time+title match
                     // Cases:
@@ -342,185 +528,292 @@ fn resolve_in_date_dir(
                 }
             }
         }
+
+        Ok(from_candidates(candidates))
     }
 
-    match candidates.len() {
-        0 => Ok(ResolvedNote::NotFound),
-        1 => Ok(ResolvedNote::Found(candidates.into_iter().next().unwrap())),
-        _ => Ok(ResolvedNote::Ambiguous(candidates)),
+    /// Resolve by exact file stem in #daily/{current_date}/, then note/
+    fn resolve_exact(&self, names: &[&str]) -> io::Result<ResolvedNote> {
+        for (dir, allow_timestamp_prefix) in &self.title_dirs() {
+            let result = self.find_exact(dir, names, *allow_timestamp_prefix)?;
+            if !matches!(result, ResolvedNote::NotFound) {
+                return Ok(result);
+            }
+        }
+
+        Ok(ResolvedNote::NotFound)
     }
-}
 
-/// Resolve by title slug
-fn resolve_by_title(
-    capsa_path: &Path,
-    slug: &str,
-    extensions: &[&str],
-) -> io::Result<ResolvedNote> {
-    // Rule 3a: Try #daily/{current_date}/ first (with timestamp prefix handling)
-    let today = get_current_date();
-    let daily_dir = capsa_path.join("#daily").join(&today);
+    /// Resolve by exact title (case-insensitive) in #daily/{current_date}/, then note/
+    fn resolve_exact_title(&self, reference: &str) -> io::Result<ResolvedNote> {
+        let wanted = reference.to_lowercase();
 
-    if daily_dir.exists() {
-        let result = find_by_prefix(&daily_dir, slug, extensions, true)?;
-        if !matches!(result, ResolvedNote::NotFound) {
-            return Ok(result);
+        for (dir, _) in &self.title_dirs() {
+            if let Some(paths) = self.title_map(dir)?.get(&wanted) {
+                return Ok(from_candidates(paths.clone()));
+            }
         }
+
+        Ok(ResolvedNote::NotFound)
     }
 
-    // Rule 3b: Try note/ directory (without timestamp prefix handling)
-    let note_dir = capsa_path.join("note");
-    if note_dir.exists() {
-        let result = find_by_prefix(&note_dir, slug, extensions, false)?;
-        if !matches!(result, ResolvedNote::NotFound) {
-            return Ok(result);
+    /// Notes of `dir` by lowercased title, mapped once
+    fn title_map(&self, dir: &Path) -> io::Result<Rc<NameMap>> {
+        if let Some(map) = self.title_maps.borrow().get(dir) {
+            return Ok(Rc::clone(map));
         }
+
+        let mut map = NameMap::new();
+        for path in self.files(dir)?.iter().filter(|path| self.is_note(path)) {
+            map.entry(self.title(path).trim().to_lowercase()).or_default().push(path.clone());
+        }
+        let map = Rc::new(map);
+        self.title_maps.borrow_mut().insert(dir.to_path_buf(), Rc::clone(&map));
+        Ok(map)
     }
 
-    // Rule 3c: Search index files (#*.md in root directory)
-    search_in_index_files(capsa_path, slug, extensions)
-}
+    /// Notes of `dir` by file stem, mapped once
+    /// If allow_timestamp_prefix is true, HHmmSS-name.md is also mapped as `name`
+    fn stem_map(&self, dir: &Path, allow_timestamp_prefix: bool) -> io::Result<Rc<NameMap>> {
+        let key = (dir.to_path_buf(), allow_timestamp_prefix);
+        if let Some(map) = self.stems.borrow().get(&key) {
+            return Ok(Rc::clone(map));
+        }
 
-/// Find files by prefix in a directory
-/// If allow_timestamp_prefix is true, also matches files like HHmmSS-slug.md
-fn find_by_prefix(
-    dir: &Path,
-    prefix: &str,
-    extensions: &[&str],
-    allow_timestamp_prefix: bool,
-) -> io::Result<ResolvedNote> {
-    let mut candidates = Vec::new();
+        let mut map = NameMap::new();
+        for path in self.files(dir)?.iter() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let Some(stem) = self.note_stem(&name) else {
+                continue;
+            };
+
+            map.entry(stem.to_string()).or_default().push(path.clone());
+            match stem.split_once('-') {
+                Some((time, rest)) if allow_timestamp_prefix && time.len() == 6 && time.bytes().all(|b| b.is_ascii_digit()) => {
+                    map.entry(rest.to_string()).or_default().push(path.clone());
+                }
+                _ => {}
+            }
+        }
+        let map = Rc::new(map);
+        self.stems.borrow_mut().insert(key, Rc::clone(&map));
+        Ok(map)
+    }
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+    /// Find files whose stem is one of `names` in a directory
+    /// If allow_timestamp_prefix is true, also matches files like HHmmSS-name.md
+    fn find_exact(&self, dir: &Path, names: &[&str], allow_timestamp_prefix: bool) -> io::Result<ResolvedNote> {
+        let map = self.stem_map(dir, allow_timestamp_prefix)?;
+        let mut candidates: Vec<PathBuf> = Vec::new();
 
-        if path.is_file() {
-            if let Some(name) = path.file_name() {
-                let name_str = name.to_string_lossy();
-                let name_bytes = name_str.as_bytes();
-
-                // Check if matches prefix with any extension
-                let matches = if allow_timestamp_prefix {
-                    // For daily notes: try matching after HHmmSS- prefix
-                    // Format: HHmmSS[-title].md
-                    if name_bytes.len() >= 7 {
-                        // Check if starts with 6 digits (HHmmSS)
-                        let has_time_prefix = name_bytes[0..6].iter().all(|b| b.is_ascii_digit());
-                        let has_separator = name_bytes.len() > 6 && (name_bytes[6] == b'-');
-
-                        if has_time_prefix {
-                            // Try matching after the timestamp
-                            let rest_start = if has_separator { 7 } else { 6 };
-                            let rest = &name_str[rest_start..];
-
-                            // Check if rest starts with prefix
-                            if rest.starts_with(prefix) {
-                                // Then check extension
-                                for ext in extensions {
-                                    if name_str.ends_with(ext) {
-                                        candidates.push(path.clone());
-                                        break;
-                                    }
-                                }
-                                continue;
-                            }
-                        }
-                    }
-                    // Fall through to regular prefix check
-                    name_str.starts_with(prefix)
-                } else {
-                    // Regular prefix check
-                    name_str.starts_with(prefix)
-                };
-
-                if matches {
-                    // Check if it has one of the supported extensions
-                    for ext in extensions {
-                        if name_str.ends_with(ext) {
+        for path in names.iter().filter_map(|name| map.get(*name)).flatten() {
+            if !candidates.contains(path) {
+                candidates.push(path.clone());
+            }
+        }
+
+        Ok(from_candidates(candidates))
+    }
+
+    /// Resolve by title slug
+    fn resolve_by_title(&self, slug: &str) -> io::Result<ResolvedNote> {
+        // Rule 3a: Try #daily/{current_date}/ first (with timestamp prefix handling)
+        // Rule 3b: Try note/ directory (without timestamp prefix handling)
+        for (dir, allow_timestamp_prefix) in &self.title_dirs() {
+            let result = self.find_by_prefix(dir, slug, *allow_timestamp_prefix)?;
+            if !matches!(result, ResolvedNote::NotFound) {
+                return Ok(result);
+            }
+        }
+
+        // Rule 3c: Search index files (#*.md in root directory)
+        self.search_in_index_files(slug)
+    }
+
+    /// Resolve by title tokens in today's daily directory, then note/
+    fn resolve_by_tokens(&self, reference: &str) -> io::Result<ResolvedNote> {
+        let query: HashSet<String> = tokenize(reference).into_iter().collect();
+        if query.is_empty() {
+            return Ok(ResolvedNote::NotFound);
+        }
+
+        for (dir, _) in &self.title_dirs() {
+            let candidates: Vec<PathBuf> = self.files(dir)?.iter()
+                .filter(|path| self.is_note(path))
+                .filter(|path| query.is_subset(&self.tokens(path)))
+                .cloned()
+                .collect();
+            if !candidates.is_empty() {
+                return Ok(from_candidates(candidates));
+            }
+        }
+
+        Ok(ResolvedNote::NotFound)
+    }
+
+    /// Whether the file name has a note extension
+    fn is_note(&self, path: &Path) -> bool {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.note_stem(&name).is_some()
+    }
+
+    /// Find files by prefix in a directory
+    /// If allow_timestamp_prefix is true, also matches files like HHmmSS-slug.md
+    fn find_by_prefix(&self, dir: &Path, prefix: &str, allow_timestamp_prefix: bool) -> io::Result<ResolvedNote> {
+        let mut candidates = Vec::new();
+
+        for path in self.files(dir)?.iter() {
+            let name_str = path.file_name().unwrap_or_default().to_string_lossy();
+            let name_bytes = name_str.as_bytes();
+
+            // For daily notes: try matching after HHmmSS- prefix
+            // Format: HHmmSS[-title].md
+            if allow_timestamp_prefix && name_bytes.len() >= 7 {
+                // Check if starts with 6 digits (HHmmSS)
+                let has_time_prefix = name_bytes[0..6].iter().all(|b| b.is_ascii_digit());
+                let has_separator = name_bytes[6] == b'-';
+
+                if has_time_prefix {
+                    let rest_start = if has_separator { 7 } else { 6 };
+                    if name_str[rest_start..].starts_with(prefix) {
+                        if self.is_note(path) {
                             candidates.push(path.clone());
-                            break;
                         }
+                        continue;
                     }
                 }
             }
+
+            // Regular prefix check, with one of the supported extensions
+            if name_str.starts_with(prefix) && self.is_note(path) {
+                candidates.push(path.clone());
+            }
         }
-    }
 
-    match candidates.len() {
-        0 => Ok(ResolvedNote::NotFound),
-        1 => Ok(ResolvedNote::Found(candidates.into_iter().next().unwrap())),
-        _ => Ok(ResolvedNote::Ambiguous(candidates)),
+        Ok(from_candidates(candidates))
     }
-}
-
-/// Search for slug in index files (root directory #*.md)
-fn search_in_index_files(
-    capsa_path: &Path,
-    slug: &str,
-    extensions: &[&str],
-) -> io::Result<ResolvedNote> {
-    // Find all #*.md files in root directory
-    let mut candidates = Vec::new();
 
-    for entry in fs::read_dir(capsa_path)? {
-        let entry = entry?;
-        let path = entry.path();
+    /// Search for slug in index files (root directory #*.md)
+    fn search_in_index_files(&self, slug: &str) -> io::Result<ResolvedNote> {
+        let mut candidates = Vec::new();
+
+        for path in self.files(&self.capsa_path)?.iter() {
+            // Check if it's an index file (#*.md, #*.mx, #*.emx)
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name.starts_with('#') && self.is_note(path) {
+                // Search this index file for the slug
+                if let Some(found) = self.find_in_index_file(path, slug)? {
+                    candidates.push(found);
+                }
+            }
+        }
 
-        if path.is_file() {
-            if let Some(name) = path.file_name() {
-                let name_str = name.to_string_lossy();
+        Ok(from_candidates(candidates))
+    }
 
-                // Check if it's an index file (#*.md, #*.mx, #*.emx)
-                if name_str.starts_with('#') {
-                    for ext in extensions {
-                        if name_str.ends_with(ext) {
-                            // Search this index file for the slug
-                            if let Some(found) = find_in_index_file(&path, slug)? {
-                                candidates.push(found);
-                            }
-                            break;
+    /// Search for a link target in an index file
+    fn find_in_index_file(&self, index_path: &Path, slug: &str) -> io::Result<Option<PathBuf>> {
+        let cached = self.contents.borrow().get(index_path).cloned();
+        let content = match cached {
+            Some(content) => content,
+            None => {
+                let content = Rc::new(fs::read_to_string(index_path)?);
+                self.contents.borrow_mut().insert(index_path.to_path_buf(), Rc::clone(&content));
+                content
+            }
+        };
+
+        // Get the directory containing the index file
+        let base_dir = index_path.parent().unwrap_or_else(|| Path::new("."));
+
+        for line in content.lines() {
+            // Look for markdown links: [text](path)
+            if let Some(start) = line.find("](") {
+                if let Some(end) = line[start..].find(')') {
+                    let link_target = &line[start + 2..start + end];
+
+                    // Check if the link text or target contains our slug
+                    let link_text_before = &line[..start];
+                    if link_text_before.contains(slug) || link_target.contains(slug) {
+                        // Resolve the link target relative to the index file
+                        let full_path = base_dir.join(link_target);
+                        if full_path.exists() {
+                            return Ok(Some(full_path));
                         }
                     }
                 }
             }
         }
+
+        Ok(None)
     }
+}
 
+/// Resolution from the matching files
+fn from_candidates(mut candidates: Vec<PathBuf>) -> ResolvedNote {
     match candidates.len() {
-        0 => Ok(ResolvedNote::NotFound),
-        1 => Ok(ResolvedNote::Found(candidates.into_iter().next().unwrap())),
-        _ => Ok(ResolvedNote::Ambiguous(candidates)),
+        0 => ResolvedNote::NotFound,
+        1 => ResolvedNote::Found(candidates.remove(0)),
+        _ => ResolvedNote::Ambiguous(candidates),
     }
 }
 
-/// Search for a link target in an index file
-fn find_in_index_file(index_path: &Path, slug: &str) -> io::Result<Option<PathBuf>> {
-    let content = fs::read_to_string(index_path)?;
-
-    // Get the directory containing the index file
-    let base_dir = index_path.parent().unwrap_or_else(|| Path::new("."));
-
-    for line in content.lines() {
-        // Look for markdown links: [text](path)
-        if let Some(start) = line.find("](") {
-            if let Some(end) = line[start..].find(')') {
-                let link_target = &line[start + 2..start + end];
-
-                // Check if the link text or target contains our slug
-                let link_text_before = &line[..start];
-                if link_text_before.contains(slug) || link_target.contains(slug) {
-                    // Resolve the link target relative to the index file
-                    let full_path = base_dir.join(link_target);
-                    if full_path.exists() {
-                        return Ok(Some(full_path));
-                    }
-                }
-            }
+/// Extract time prefix from input (HH, HHmm, or HHmmSS format)
+/// Returns None if input doesn't start with digits
+fn extract_time_prefix(s: &str) -> Option<String> {
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if !digits.is_empty() && digits.len() <= 6 {
+        Some(digits)
+    } else {
+        None
+    }
+}
+
+/// Parse full timestamp YYYYMMDDHHmmSS
+fn parse_full_timestamp(s: &str) -> Option<(String, String)> {
+    if s.len() >= 14 && s.chars().all(|c| c.is_ascii_digit()) {
+        let date = s[0..8].to_string();
+        let time = s[8..14].to_string();
+        // Validate date/time ranges
+        if validate_date(&date) && validate_time(&time) {
+            return Some((date, time));
         }
     }
+    None
+}
+
+/// Validate date string YYYYMMDD
+fn validate_date(s: &str) -> bool {
+    if s.len() != 8 {
+        return false;
+    }
+    let year: u32 = s[0..4].parse().unwrap_or(0);
+    let month: u32 = s[4..6].parse().unwrap_or(0);
+    let day: u32 = s[6..8].parse().unwrap_or(0);
+
+    year >= 1900 && year <= 2100 && month >= 1 && month <= 12 && day >= 1 && day <= 31
+}
+
+/// Validate time string HHmmSS
+fn validate_time(s: &str) -> bool {
+    if s.len() != 6 {
+        return false;
+    }
+    let hour: u32 = s[0..2].parse().unwrap_or(0);
+    let minute: u32 = s[2..4].parse().unwrap_or(0);
+    let second: u32 = s[4..6].parse().unwrap_or(0);
 
-    Ok(None)
+    hour <= 23 && minute <= 59 && second <= 59
+}
+
+/// Parse and validate a time string (HHmmSS format)
+#[cfg(test)]
+fn parse_time_only(s: &str) -> Option<String> {
+    if validate_time(s) {
+        Some(s.to_string())
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -554,6 +847,126 @@ mod tests {
         assert!(!validate_date("20260132")); // Invalid day (32)
     }
 
+    #[test]
+    fn test_note_area_from_relative() {
+        let area = |relative: &str| NoteArea::from_relative(relative, "#daily");
+        assert_eq!(area("#daily/20260212/143022-standup.md"), Some(NoteArea::Daily));
+        assert_eq!(area("note/my-idea.md"), Some(NoteArea::Permanent));
+        assert_eq!(area("note/a1b2c3d4e5f6/book.md"), Some(NoteArea::Literature));
+        assert_eq!(area("note/#daily.md"), None);
+        assert_eq!(area("#rust.md"), None);
+        assert_eq!(area("TASK.md"), None);
+        assert_eq!(area("journal/20260212/143022-standup.md"), None);
+        assert_eq!(NoteArea::from_relative("journal/20260212/143022-standup.md", "journal"), Some(NoteArea::Daily));
+    }
+
+    #[test]
+    fn test_resolve_by_tokens() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let note_dir = temp_dir.path().join("note");
+        fs::create_dir_all(&note_dir).unwrap();
+        fs::write(note_dir.join("项目会议记录.md"), "# 项目会议记录\n").unwrap();
+        fs::write(note_dir.join("handling-errors.md"), "# Handling Errors\n").unwrap();
+        fs::write(note_dir.join("xiang-mu-ji-hua.md"), "# 项目计划\n").unwrap();
+
+        let resolve = |r: &str| resolve_note(temp_dir.path(), r, &[".md"]).unwrap();
+
+        // CJK substring (not a prefix)
+        match resolve("会议") {
+            ResolvedNote::Found(p) => assert!(p.ends_with("项目会议记录.md")),
+            other => panic!("unexpected: {:?}", other),
+        }
+        // Stemmed words in any order
+        match resolve("error handled") {
+            ResolvedNote::Found(p) => assert!(p.ends_with("handling-errors.md")),
+            other => panic!("unexpected: {:?}", other),
+        }
+        // Title from the H1 heading (pinyin filename)
+        match resolve("计划") {
+            ResolvedNote::Found(p) => assert!(p.ends_with("xiang-mu-ji-hua.md")),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(matches!(resolve("missing"), ResolvedNote::NotFound));
+    }
+
+    #[test]
+    fn test_resolve_link_target() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let note_dir = temp_dir.path().join("note");
+        fs::create_dir_all(note_dir.join("archive")).unwrap();
+        fs::write(note_dir.join("idea.md"), "# Idea\n").unwrap();
+        fs::write(note_dir.join("archive/old.md"), "# Old\n").unwrap();
+        fs::write(temp_dir.path().join("diagram.png"), "").unwrap();
+
+        let resolve = |r: &str| resolve_link_target(temp_dir.path(), r, &[".md"]).unwrap();
+        assert!(matches!(resolve("note/archive/old"), ResolvedNote::Found(p) if p.ends_with("archive/old.md")));
+        assert!(matches!(resolve("note/idea.md"), ResolvedNote::Found(p) if p.ends_with("note/idea.md")));
+        assert!(matches!(resolve("diagram.png"), ResolvedNote::Found(p) if p.ends_with("diagram.png")));
+        // Titles follow the resolve_note rules
+        assert!(matches!(resolve("Idea"), ResolvedNote::Found(p) if p.ends_with("note/idea.md")));
+        assert!(matches!(resolve("../outside"), ResolvedNote::NotFound));
+    }
+
+    #[test]
+    fn test_resolve_link_target_exact_before_prefix() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let note_dir = temp_dir.path().join("note");
+        fs::create_dir_all(&note_dir).unwrap();
+        fs::write(note_dir.join("rust.md"), "# Rust\n").unwrap();
+        fs::write(note_dir.join("rust-async.md"), "# Rust Async\n").unwrap();
+        fs::write(note_dir.join("n1.md"), "# N1\n").unwrap();
+        fs::write(note_dir.join("n10.md"), "# N10\n").unwrap();
+        fs::write(note_dir.join("borrowing.md"), "# Ownership\n").unwrap();
+        fs::write(note_dir.join("ownership-rules.md"), "# Ownership Rules\n").unwrap();
+
+        let resolve = |r: &str| resolve_link_target(temp_dir.path(), r, &[".md"]).unwrap();
+        // Stem that is a prefix of another stem
+        assert!(matches!(resolve("rust"), ResolvedNote::Found(p) if p.ends_with("note/rust.md")));
+        assert!(matches!(resolve("n1"), ResolvedNote::Found(p) if p.ends_with("note/n1.md")));
+        // Filename
+        assert!(matches!(resolve("rust.md"), ResolvedNote::Found(p) if p.ends_with("note/rust.md")));
+        // Title, before the prefix match on `ownership`
+        assert!(matches!(resolve("Ownership"), ResolvedNote::Found(p) if p.ends_with("note/borrowing.md")));
+        assert!(matches!(resolve("rust async"), ResolvedNote::Found(p) if p.ends_with("note/rust-async.md")));
+    }
+
+    #[test]
+    fn test_resolve_exact_stem_wins() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let note_dir = temp_dir.path().join("note");
+        fs::create_dir_all(&note_dir).unwrap();
+        fs::write(note_dir.join("idea.md"), "# Idea\n").unwrap();
+        fs::write(note_dir.join("idea-2.md"), "# Idea\n").unwrap();
+        fs::write(note_dir.join("draft-1.md"), "# Draft\n").unwrap();
+        fs::write(note_dir.join("draft-2.md"), "# Draft\n").unwrap();
+
+        let resolve = |r: &str| resolve_note(temp_dir.path(), r, &[".md"]).unwrap();
+        assert!(matches!(resolve("Idea"), ResolvedNote::Found(p) if p.ends_with("note/idea.md")));
+        assert!(matches!(resolve("idea-2"), ResolvedNote::Found(p) if p.ends_with("note/idea-2.md")));
+        // Without an exact match, prefix matches stay ambiguous
+        assert!(matches!(resolve("draft"), ResolvedNote::Ambiguous(c) if c.len() == 2));
+    }
+
+    #[test]
+    fn test_note_resolver_from_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let note_dir = temp_dir.path().join("note");
+        fs::create_dir_all(&note_dir).unwrap();
+        fs::write(note_dir.join("rust.md"), "# Rust\n").unwrap();
+        fs::write(note_dir.join("rust-async.md"), "# Async Rust\n").unwrap();
+
+        let cache = LinkCache::open(temp_dir.path()).unwrap();
+        let resolver = NoteResolver::from_cache(temp_dir.path(), &cache).unwrap();
+        assert_eq!(resolver.extensions(), crate::DEFAULT_EXTENSIONS);
+        assert!(matches!(resolver.resolve_link_target("rust").unwrap(), ResolvedNote::Found(p) if p.ends_with("note/rust.md")));
+        assert!(matches!(resolver.resolve_link_target("Async Rust").unwrap(), ResolvedNote::Found(p) if p.ends_with("note/rust-async.md")));
+
+        // Listings are kept: a note created afterwards is not seen by this resolver
+        fs::write(note_dir.join("later.md"), "# Later\n").unwrap();
+        assert!(matches!(resolver.resolve_note("later").unwrap(), ResolvedNote::NotFound));
+        assert!(matches!(resolve_note(temp_dir.path(), "later", &[".md"]).unwrap(), ResolvedNote::Found(_)));
+    }
+
     #[test]
     fn test_validate_time() {
         assert!(validate_time("143022"));
@@ -0,0 +1,504 @@
+//! Unified diff patches
+//!
+//! Applies a unified diff (as produced by `diff -u`, `git diff` or an LLM
+//! agent) to a single note. Hunks are located like `patch` does: first at
+//! the line given in the hunk header, shifted by the offset of the previous
+//! hunk, then at the nearest line where they match. With fuzz `n`, up to `n`
+//! context lines at each end of a hunk may be ignored.
+//!
+//! Application is all-or-nothing: if any hunk is rejected, nothing is
+//! changed and every rejected hunk is reported as a [`PatchError`].
+//!
+//! The line counts of a hunk header tell body lines from file headers: while
+//! a hunk still expects lines, `--- `/`+++ ` lines are removed and added lines;
+//! once its counts are used up, they start a new file. Other lines are read
+//! into the hunk up to the next `@@` line either way. A hand-written diff with
+//! wrong counts is therefore misparsed only around `--- `/`+++ ` lines: too
+//! high and the next file header is read into the hunk, which then fails to
+//! match and is rejected; too low and a removed `-- x` line followed by an
+//! added `++ y` line is taken for a file header, so the patch is refused as
+//! touching several files, or the two lines are dropped if it had no header.
+
+use std::fmt;
+use regex::Regex;
+use serde::Serialize;
+
+/// One line of a hunk
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    /// Unchanged line (` `)
+    Context(String),
+    /// Removed line (`-`)
+    Remove(String),
+    /// Added line (`+`)
+    Add(String),
+}
+
+/// A hunk of a unified diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    /// First line of the old side (1-indexed; 0 for an insertion at the start)
+    pub old_start: usize,
+    /// First line of the new side (1-indexed)
+    pub new_start: usize,
+    /// Hunk body
+    pub lines: Vec<HunkLine>,
+    /// The old side has no newline at end of file (`\ No newline at end of file`)
+    pub old_no_newline: bool,
+    /// The new side has no newline at end of file
+    pub new_no_newline: bool,
+}
+
+impl Hunk {
+    /// Lines the hunk expects in the file (context and removed lines)
+    pub fn old_lines(&self) -> Vec<&str> {
+        self.lines.iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// Lines the hunk leaves in the file (context and added lines)
+    pub fn new_lines(&self) -> Vec<&str> {
+        self.lines.iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+
+    /// Leading and trailing context line counts
+    fn context_counts(&self) -> (usize, usize) {
+        let is_context = |line: &&HunkLine| matches!(line, HunkLine::Context(_));
+        let leading = self.lines.iter().take_while(is_context).count();
+        let trailing = if leading == self.lines.len() {
+            0
+        } else {
+            self.lines.iter().rev().take_while(is_context).count()
+        };
+        (leading, trailing)
+    }
+}
+
+/// Where a hunk was applied
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AppliedHunk {
+    /// Hunk number (1-indexed)
+    pub hunk: usize,
+    /// Line of the original note where the hunk's old side starts (1-indexed)
+    pub line: usize,
+    /// Distance from the line given in the hunk header
+    pub offset: isize,
+    /// Context lines ignored at each end to make the hunk match
+    pub fuzz: usize,
+}
+
+/// A hunk that does not match the note
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RejectedHunk {
+    /// Hunk number (1-indexed)
+    pub hunk: usize,
+    /// Old-side start line from the hunk header
+    pub old_start: usize,
+    /// Old-side lines that could not be found
+    pub expected: Vec<String>,
+}
+
+/// Error parsing or applying a patch
+///
+/// Serializes as `{"kind": "Rejected", "hunks": [...]}`, like `ValidationError`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum PatchError {
+    /// Diff text could not be parsed
+    Malformed { line: usize, message: String },
+    /// Diff contains no hunks
+    NoHunks,
+    /// Diff changes more than one file
+    MultipleFiles { count: usize },
+    /// Hunks that do not match the note
+    Rejected { hunks: Vec<RejectedHunk> },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Malformed { line, message } => {
+                write!(f, "Malformed patch at line {}: {}", line, message)
+            }
+            PatchError::NoHunks => write!(f, "Patch contains no hunks"),
+            PatchError::MultipleFiles { count } => {
+                write!(f, "Patch changes {} files (expected 1)", count)
+            }
+            PatchError::Rejected { hunks } => {
+                let numbers: Vec<String> = hunks.iter()
+                    .map(|h| format!("#{} at {}", h.hunk, h.old_start))
+                    .collect();
+                write!(f, "{} hunk(s) rejected: {}", hunks.len(), numbers.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// A parsed unified diff for a single file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub hunks: Vec<Hunk>,
+}
+
+/// Result of applying a patch
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchResult {
+    /// Patched content
+    pub content: String,
+    /// Where each hunk was applied
+    pub hunks: Vec<AppliedHunk>,
+}
+
+impl Patch {
+    /// Parse unified diff text
+    ///
+    /// File headers (`diff`, `index`, `---`, `+++`) are optional; a diff with
+    /// more than one `+++` header is refused. Within the line counts of its
+    /// `@@` header, a hunk's `--- `/`+++ ` lines are removed and added lines,
+    /// not file headers.
+    pub fn parse(text: &str) -> Result<Patch, PatchError> {
+        let header_re = Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@").expect("valid regex");
+        let lines: Vec<&str> = text.lines().collect();
+
+        let mut hunks: Vec<Hunk> = Vec::new();
+        let mut files = 0;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let Some(caps) = header_re.captures(line) else {
+                // Headers and any text before the first hunk
+                let is_header = is_file_header(&lines, i);
+                if is_header && line.starts_with("+++ ") {
+                    files += 1;
+                }
+                if !hunks.is_empty() && !is_header {
+                    return Err(PatchError::Malformed {
+                        line: i + 1,
+                        message: format!("unexpected line {:?}", line),
+                    });
+                }
+                i += 1;
+                continue;
+            };
+
+            let count = |group: usize| caps.get(group).map_or(1, |m| m.as_str().parse().unwrap_or(0));
+            // Old and new lines the header says are left in the hunk
+            let (mut old_left, mut new_left): (usize, usize) = (count(2), count(4));
+            let mut hunk = Hunk {
+                old_start: caps[1].parse().unwrap_or(0),
+                new_start: caps[3].parse().unwrap_or(0),
+                lines: Vec::new(),
+                old_no_newline: false,
+                new_no_newline: false,
+            };
+            i += 1;
+
+            while i < lines.len()
+                && !lines[i].starts_with("@@")
+                && (old_left + new_left > 0 || !is_file_header(&lines, i))
+            {
+                let body = lines[i];
+                match body.chars().next() {
+                    Some(' ') => {
+                        hunk.lines.push(HunkLine::Context(body[1..].to_string()));
+                        (old_left, new_left) = (old_left.saturating_sub(1), new_left.saturating_sub(1));
+                    }
+                    Some('-') => {
+                        hunk.lines.push(HunkLine::Remove(body[1..].to_string()));
+                        old_left = old_left.saturating_sub(1);
+                    }
+                    Some('+') => {
+                        hunk.lines.push(HunkLine::Add(body[1..].to_string()));
+                        new_left = new_left.saturating_sub(1);
+                    }
+                    // Editors often strip the space of empty context lines
+                    None => {
+                        hunk.lines.push(HunkLine::Context(String::new()));
+                        (old_left, new_left) = (old_left.saturating_sub(1), new_left.saturating_sub(1));
+                    }
+                    Some('\\') => match hunk.lines.last() {
+                        Some(HunkLine::Remove(_)) => hunk.old_no_newline = true,
+                        Some(HunkLine::Add(_)) => hunk.new_no_newline = true,
+                        _ => {
+                            hunk.old_no_newline = true;
+                            hunk.new_no_newline = true;
+                        }
+                    },
+                    Some(_) => {
+                        return Err(PatchError::Malformed {
+                            line: i + 1,
+                            message: format!("unexpected line in hunk {:?}", body),
+                        })
+                    }
+                }
+                i += 1;
+            }
+
+            // Blank lines after the last hunk are not part of it
+            while matches!(hunk.lines.last(), Some(HunkLine::Context(s)) if s.is_empty())
+                && i == lines.len()
+            {
+                hunk.lines.pop();
+            }
+            hunks.push(hunk);
+        }
+
+        if files > 1 {
+            return Err(PatchError::MultipleFiles { count: files });
+        }
+        if hunks.is_empty() {
+            return Err(PatchError::NoHunks);
+        }
+        Ok(Patch { hunks })
+    }
+
+    /// Apply the patch to content, ignoring up to `fuzz` context lines at each
+    /// end of a hunk if it does not match as is
+    pub fn apply(&self, content: &str, fuzz: usize) -> Result<PatchResult, PatchError> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut trailing_newline = content.ends_with('\n') || content.is_empty();
+
+        // Locate every hunk in the original, in order and without overlaps
+        let mut placements = Vec::new();
+        let mut applied = Vec::new();
+        let mut rejected = Vec::new();
+        let mut min_pos = 0;
+        let mut last_offset: isize = 0;
+
+        for (index, hunk) in self.hunks.iter().enumerate() {
+            let expected = hunk.old_start.saturating_sub(1) as isize + last_offset;
+            match locate(hunk, &lines, expected, min_pos, fuzz) {
+                Some((pos, skip_start, skip_end)) => {
+                    let old_len = hunk.old_lines().len() - skip_start - skip_end;
+                    let header_pos = hunk.old_start.saturating_sub(1) as isize;
+                    // Line the header refers to: the first old line, or the
+                    // line an insertion follows (`-8,0` inserts after line 8)
+                    let anchor = if old_len == 0 && hunk.old_start > 0 { pos.saturating_sub(1) } else { pos - skip_start };
+
+                    last_offset = anchor as isize - header_pos;
+                    min_pos = pos + old_len;
+                    placements.push((pos, old_len, hunk, skip_start, skip_end));
+                    applied.push(AppliedHunk {
+                        hunk: index + 1,
+                        line: anchor + 1,
+                        offset: last_offset,
+                        fuzz: skip_start.max(skip_end),
+                    });
+
+                    if pos + old_len == lines.len() {
+                        if hunk.new_no_newline {
+                            trailing_newline = false;
+                        } else if hunk.old_no_newline {
+                            trailing_newline = true;
+                        }
+                    }
+                }
+                None => rejected.push(RejectedHunk {
+                    hunk: index + 1,
+                    old_start: hunk.old_start,
+                    expected: hunk.old_lines().iter().map(|s| s.to_string()).collect(),
+                }),
+            }
+        }
+
+        if !rejected.is_empty() {
+            return Err(PatchError::Rejected { hunks: rejected });
+        }
+
+        let mut result: Vec<&str> = Vec::with_capacity(lines.len());
+        let mut cursor = 0;
+        for (pos, old_len, hunk, skip_start, skip_end) in placements {
+            result.extend_from_slice(&lines[cursor..pos]);
+            let new_lines = hunk.new_lines();
+            result.extend_from_slice(&new_lines[skip_start..new_lines.len() - skip_end]);
+            cursor = pos + old_len;
+        }
+        result.extend_from_slice(&lines[cursor..]);
+
+        let mut patched = result.join("\n");
+        if trailing_newline && !result.is_empty() {
+            patched.push('\n');
+        }
+        Ok(PatchResult { content: patched, hunks: applied })
+    }
+}
+
+/// Whether line `i` starts a file header (`diff ...`, or `--- ` followed by `+++ `)
+fn is_file_header(lines: &[&str], i: usize) -> bool {
+    lines[i].starts_with("diff ")
+        || lines[i].starts_with("index ")
+        || (lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")))
+        || lines[i].starts_with("+++ ") && i > 0 && lines[i - 1].starts_with("--- ")
+}
+
+/// Find where a hunk's old side matches, trying fuzz 0..=max_fuzz and, for
+/// each, the positions nearest to `expected` first.
+/// Returns (position of the first matched line, context lines skipped at the
+/// start, at the end).
+fn locate(hunk: &Hunk, lines: &[&str], expected: isize, min_pos: usize, max_fuzz: usize) -> Option<(usize, usize, usize)> {
+    let old = hunk.old_lines();
+    let (leading, trailing) = hunk.context_counts();
+
+    if old.is_empty() {
+        // Pure insertion: goes after line `old_start`
+        let pos = (expected + 1).clamp(min_pos as isize, lines.len() as isize) as usize;
+        return Some((if hunk.old_start == 0 { min_pos } else { pos }, 0, 0));
+    }
+
+    for fuzz in 0..=max_fuzz {
+        let skip_start = fuzz.min(leading);
+        let skip_end = fuzz.min(trailing);
+        if fuzz > 0 && skip_start + skip_end == 0 {
+            break;
+        }
+        if skip_start + skip_end >= old.len() {
+            break;
+        }
+        let needle = &old[skip_start..old.len() - skip_end];
+        let last_start = match lines.len().checked_sub(needle.len()) {
+            Some(n) => n,
+            None => continue,
+        };
+        if min_pos > last_start {
+            continue;
+        }
+
+        let matches_at = |pos: usize| lines[pos..pos + needle.len()] == *needle;
+        let center = (expected + skip_start as isize).clamp(min_pos as isize, last_start as isize) as usize;
+        let max_distance = (center - min_pos).max(last_start - center);
+        for distance in 0..=max_distance {
+            if center >= min_pos + distance && matches_at(center - distance) {
+                return Some((center - distance, skip_start, skip_end));
+            }
+            if distance > 0 && center + distance <= last_start && matches_at(center + distance) {
+                return Some((center + distance, skip_start, skip_end));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "# Plan\n\nShip on Monday.\n\n## Todo\n- a\n- b\n- c\n";
+
+    #[test]
+    fn test_parse() {
+        let patch = Patch::parse(
+            "--- a/plan.md\n+++ b/plan.md\n@@ -3,1 +3,1 @@\n-Ship on Monday.\n+Ship on Friday.\n",
+        ).unwrap();
+        assert_eq!(patch.hunks.len(), 1);
+        assert_eq!(patch.hunks[0].old_start, 3);
+        assert_eq!(patch.hunks[0].old_lines(), vec!["Ship on Monday."]);
+        assert_eq!(patch.hunks[0].new_lines(), vec!["Ship on Friday."]);
+
+        assert!(matches!(Patch::parse("no hunks here\n"), Err(PatchError::NoHunks)));
+        assert!(matches!(
+            Patch::parse("@@ -1 +1 @@\n-a\n*b\n"),
+            Err(PatchError::Malformed { line: 3, .. })
+        ));
+        assert!(matches!(
+            Patch::parse("--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n--- a/y\n+++ b/y\n@@ -1 +1 @@\n-a\n+b\n"),
+            Err(PatchError::MultipleFiles { count: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_parse_dash_lines_within_hunk_counts() {
+        // Removing `-- x` and adding `++ y` gives lines that look like file headers
+        let patch = Patch::parse(
+            "--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n--- x\n+++ y\n keep\n@@ -5 +5 @@\n-a\n+b\n",
+        ).unwrap();
+        assert_eq!(patch.hunks.len(), 2);
+        assert_eq!(patch.hunks[0].old_lines(), vec!["-- x", "keep"]);
+        assert_eq!(patch.hunks[0].new_lines(), vec!["++ y", "keep"]);
+        assert_eq!(patch.apply("-- x\nkeep\n\n\na\n", 0).unwrap().content, "++ y\nkeep\n\n\nb\n");
+    }
+
+    #[test]
+    fn test_apply_exact_and_offset() {
+        let patch = Patch::parse(
+            "@@ -3 +3 @@\n-Ship on Monday.\n+Ship on Friday.\n@@ -6,2 +6,2 @@\n - a\n-- b\n+- B\n",
+        ).unwrap();
+        let result = patch.apply(NOTE, 0).unwrap();
+        assert_eq!(result.content, "# Plan\n\nShip on Friday.\n\n## Todo\n- a\n- B\n- c\n");
+        assert_eq!(result.hunks[0], AppliedHunk { hunk: 1, line: 3, offset: 0, fuzz: 0 });
+
+        // The note gained two lines at the top since the diff was made
+        let shifted = format!("---\ntags: []\n---\n{}", NOTE);
+        let result = patch.apply(&shifted, 0).unwrap();
+        assert_eq!(result.hunks[0].offset, 3);
+        assert_eq!(result.hunks[1].line, 9);
+        assert!(result.content.contains("- B\n"));
+    }
+
+    #[test]
+    fn test_apply_fuzz() {
+        // First context line is stale
+        let patch = Patch::parse("@@ -5,3 +5,3 @@\n ## Tasks\n - a\n-- b\n+- B\n").unwrap();
+        assert!(matches!(patch.apply(NOTE, 0), Err(PatchError::Rejected { .. })));
+
+        let result = patch.apply(NOTE, 1).unwrap();
+        assert_eq!(result.hunks[0].fuzz, 1);
+        assert_eq!(result.content, "# Plan\n\nShip on Monday.\n\n## Todo\n- a\n- B\n- c\n");
+    }
+
+    #[test]
+    fn test_apply_rejected_is_all_or_nothing() {
+        let patch = Patch::parse("@@ -3 +3 @@\n-Ship on Monday.\n+Ship on Friday.\n@@ -7 +7 @@\n-- x\n+- y\n").unwrap();
+        match patch.apply(NOTE, 2) {
+            Err(PatchError::Rejected { hunks }) => {
+                assert_eq!(hunks.len(), 1);
+                assert_eq!(hunks[0].hunk, 2);
+                assert_eq!(hunks[0].expected, vec!["- x"]);
+            }
+            other => panic!("expected rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_apply_insertions_and_newline_at_eof() {
+        let patch = Patch::parse("@@ -0,0 +1 @@\n+---\n@@ -8,0 +9 @@\n+- d\n").unwrap();
+        let result = patch.apply(NOTE, 0).unwrap();
+        assert!(result.content.starts_with("---\n# Plan\n"));
+        assert!(result.content.ends_with("- c\n- d\n"));
+
+        let patch = Patch::parse("@@ -1 +1 @@\n-a\n+b\n\\ No newline at end of file\n").unwrap();
+        assert_eq!(patch.apply("a\n", 0).unwrap().content, "b");
+    }
+
+    #[test]
+    fn test_parse_wrong_counts() {
+        // Too high: the second file header is read into the first hunk
+        let patch = Patch::parse("--- a/x\n+++ b/x\n@@ -1,3 +1,3 @@\n-a\n+b\n--- a/y\n+++ b/y\n@@ -2 +2 @@\n-c\n+d\n").unwrap();
+        assert!(matches!(patch.apply("a\nc\n", 0), Err(PatchError::Rejected { .. })));
+
+        // Too low: `-- x`/`++ y` past the counts are a file header
+        assert!(matches!(
+            Patch::parse("--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n--- x\n+++ y\n"),
+            Err(PatchError::MultipleFiles { count: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_apply_insertion_to_empty_note() {
+        let patch = Patch::parse("@@ -1,0 +1 @@\n+x\n").unwrap();
+        let result = patch.apply("", 0).unwrap();
+        assert_eq!(result.content, "x\n");
+        assert_eq!(result.hunks[0], AppliedHunk { hunk: 1, line: 1, offset: 0, fuzz: 0 });
+    }
+}
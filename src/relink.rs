@@ -0,0 +1,446 @@
+//! Link rewriting for moved notes
+//!
+//! When a note moves, every link pointing at it is rewritten:
+//! - inline links and reference definitions in any markdown file of the capsa,
+//!   which covers tag indexes (`#tag.md`) and `note/#daily.md`
+//! - task references in the task file (`[TASK-01]: node_ref`), which are
+//!   note references rather than paths and run to the end of the line
+//! - wiki links (`[[Old Title]]`, `[[old-stem]]`, `[[note/old]]`) that
+//!   resolve to the note, keeping `#heading`, `|alias` and the form of the
+//!   target (title, name or path)
+//! - links inside the moved note itself that are relative to its directory
+//!
+//! A link destination is matched relative to the linking file and relative to
+//! the capsa root (the form used by tag indexes and `note/#daily.md`). The
+//! rewritten link keeps the form it was written in, and any `#anchor`.
+
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use crate::constants as C;
+use crate::markdown::{link_spans, task_references, wiki_links};
+use crate::note_resolver::{NoteResolver, ResolvedNote};
+use crate::util;
+
+/// A file whose content changes because of a move
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    /// Where the file is before the move
+    pub source: PathBuf,
+    /// Where the file is written (differs from `source` for the moved note)
+    pub path: PathBuf,
+    /// Current content
+    pub old_content: String,
+    /// Content with links rewritten
+    pub new_content: String,
+}
+
+impl FileChange {
+    /// Changed lines as (line number, old line, new line)
+    ///
+    /// Link rewrites never add or remove lines, so lines are compared pairwise.
+    pub fn changed_lines(&self) -> Vec<(usize, &str, &str)> {
+        self.old_content.lines()
+            .zip(self.new_content.lines())
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(i, (old, new))| (i + 1, old, new))
+            .collect()
+    }
+
+    /// Unified-style diff, with paths shown relative to `root`
+    pub fn diff(&self, root: &Path) -> String {
+        let mut out = format!(
+            "--- a/{}\n+++ b/{}\n",
+            util::relative_path(root, &self.source),
+            util::relative_path(root, &self.path)
+        );
+        for (line, old, new) in self.changed_lines() {
+            out.push_str(&format!("@@ -{} +{} @@\n-{}\n+{}\n", line, line, old, new));
+        }
+        out
+    }
+}
+
+/// How a link destination was written
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LinkBase {
+    /// Relative to the linking file's directory
+    File,
+    /// Relative to the capsa root (`/`-prefixed if `absolute`)
+    Root { absolute: bool },
+}
+
+/// Compute the content changes needed to move `from` to `to` within a capsa
+///
+/// `title` is the new title of a note renamed by title; wiki links written
+/// with a title get it. Without it, they get the note's own title.
+///
+/// Nothing is written; the returned changes are applied by `CapsaEngine::move_note`
+/// or shown as a diff for a dry run.
+pub fn plan_move(capsa_path: &Path, from: &Path, to: &Path, title: Option<&str>) -> io::Result<Vec<FileChange>> {
+    let root = normalize(capsa_path);
+    let from = normalize(from);
+    let to = normalize(to);
+    let resolver = NoteResolver::open(&root)?;
+    let title = match title {
+        Some(title) => title.to_string(),
+        None => util::extract_note_title(&to, &fs::read_to_string(&from).unwrap_or_default()),
+    };
+    let task_file = normalize(&root.join(&resolver.config().task_file));
+
+    let mut files = Vec::new();
+    collect_markdown_files(&root, &resolver.extensions(), &mut files)?;
+    files.sort();
+
+    let mut changes = Vec::new();
+    for file in files {
+        let file = normalize(&file);
+        let content = match fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let is_moved = file == from;
+        let old_dir = file.parent().unwrap_or(&root).to_path_buf();
+        let new_dir = if is_moved { to.parent().unwrap_or(&root).to_path_buf() } else { old_dir.clone() };
+
+        let task_refs = if file == task_file { task_references(&content) } else { Vec::new() };
+        let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+        for span in link_spans(&content) {
+            if task_refs.iter().any(|reference| reference.range.contains(&span.range.start)) {
+                continue;
+            }
+            if let Some(new_dest) = rewrite_dest(&span.dest, &root, &old_dir, &new_dir, &from, &to, is_moved) {
+                replacements.push((span.range, new_dest));
+            }
+        }
+        // The whole note reference after `]: `, which may contain spaces
+        for reference in task_refs {
+            let rewritten = rewrite_dest(&reference.node_ref, &root, &old_dir, &new_dir, &from, &to, is_moved);
+            if let Some(new_dest) = rewritten {
+                replacements.push((reference.range, new_dest));
+            } else if resolves_to(&resolver, &reference.node_ref, &from, false) {
+                replacements.push((reference.range, task_ref(&root, &resolver.config().daily_dir, &to)));
+            }
+        }
+        for link in wiki_links(&content) {
+            if link.target.is_empty() || !resolves_to(&resolver, &link.target, &from, true) {
+                continue;
+            }
+            let target = wiki_target(&root, &resolver, &link.target, &from, &to, &title);
+            if target != link.target {
+                // Replace only the target, keeping `#heading`, `|alias` and spacing
+                let inner = link.range.start + if link.embed { 3 } else { 2 };
+                let start = inner + content[inner..link.range.end].find(&link.target).unwrap_or(0);
+                replacements.push((start..start + link.target.len(), target));
+            }
+        }
+        replacements.sort_by_key(|(range, _)| range.start);
+
+        if replacements.is_empty() {
+            continue;
+        }
+
+        let mut new_content = content.clone();
+        for (range, replacement) in replacements.into_iter().rev() {
+            new_content.replace_range(range, &replacement);
+        }
+
+        changes.push(FileChange {
+            source: file.clone(),
+            path: if is_moved { to.clone() } else { file },
+            old_content: content,
+            new_content,
+        });
+    }
+
+    Ok(changes)
+}
+
+/// New destination for a link, if the move affects it
+fn rewrite_dest(
+    dest: &str,
+    root: &Path,
+    old_dir: &Path,
+    new_dir: &Path,
+    from: &Path,
+    to: &Path,
+    is_moved: bool,
+) -> Option<String> {
+    let (path, fragment) = split_dest(dest)?;
+
+    let (target, base) = if let Some(rooted) = path.strip_prefix('/') {
+        (normalize(&root.join(rooted)), LinkBase::Root { absolute: true })
+    } else {
+        let file_relative = normalize(&old_dir.join(path));
+        let root_relative = normalize(&root.join(path));
+        if file_relative == from || (is_moved && file_relative.exists()) {
+            (file_relative, LinkBase::File)
+        } else if root_relative == from {
+            (root_relative, LinkBase::Root { absolute: false })
+        } else {
+            return None;
+        }
+    };
+
+    let target = if target == from { to.to_path_buf() } else { target };
+    let new_path = match base {
+        LinkBase::File => {
+            let relative = relative_path(new_dir, &target);
+            // Keep a leading `./` (`[TASK-01]: ./note/idea.md`)
+            if path.starts_with("./") && !relative.starts_with("../") {
+                format!("./{}", relative)
+            } else {
+                relative
+            }
+        }
+        LinkBase::Root { absolute } => {
+            let relative = relative_path(root, &target);
+            if absolute { format!("/{}", relative) } else { relative }
+        }
+    };
+
+    (new_path != path).then(|| format!("{}{}", new_path, fragment))
+}
+
+/// Split a local link destination into path and `#fragment`
+/// (None for external URLs and pure anchors)
+pub(crate) fn split_dest(dest: &str) -> Option<(&str, &str)> {
+    if dest.contains("://") || dest.starts_with("mailto:") {
+        return None;
+    }
+    // A leading '#' is part of the path for `#daily/...` and `#tag.md`
+    let split = dest.char_indices().skip(1).find(|(_, c)| *c == '#').map(|(i, _)| i);
+    let (path, fragment) = match split {
+        Some(i) => (&dest[..i], &dest[i..]),
+        None => (dest, ""),
+    };
+    if path.is_empty() || (path.starts_with('#') && !path.contains('/') && !path.contains('.')) {
+        return None;
+    }
+    Some((path, fragment))
+}
+
+/// Whether a note reference (or a wiki link target, if `wiki`) currently resolves to `target`
+fn resolves_to(resolver: &NoteResolver, note_ref: &str, target: &Path, wiki: bool) -> bool {
+    let resolved = if wiki { resolver.resolve_link_target(note_ref) } else { resolver.resolve_note(note_ref) };
+    matches!(resolved, Ok(ResolvedNote::Found(path)) if normalize(&path) == target)
+}
+
+/// New target for a wiki link `old` to `from` once the note is at `to`
+///
+/// A capsa path stays a capsa path (with the extension if it had one). Where
+/// the resolver finds notes by name, a note name becomes the new stem
+/// (`YYYYMMDD/stem` for daily notes) and a title becomes `title` in `note/`;
+/// elsewhere both become a capsa path without extension.
+fn wiki_target(root: &Path, resolver: &NoteResolver, old: &str, from: &Path, to: &Path, title: &str) -> String {
+    let rooted = old.trim_start_matches('/');
+    let prefix = &old[..old.len() - rooted.len()];
+    if normalize(&root.join(rooted)) == from {
+        return format!("{}{}", prefix, relative_path(root, to));
+    }
+    let path = relative_path(root, &to.with_extension(""));
+    let extensions = resolver.extensions();
+    if extensions.iter().any(|ext| normalize(&root.join(format!("{}{}", rooted, ext))) == from) {
+        return format!("{}{}", prefix, path);
+    }
+
+    let daily_dir = &resolver.config().daily_dir;
+    let dir = to.parent().unwrap_or(root);
+    let stem = from.file_stem().unwrap_or_default().to_string_lossy();
+    let by_name = old == stem || old.contains('/');
+    if dir == root.join(C::NOTE_SUBDIR) && !by_name && !title.contains(['#', '|', '[', ']']) {
+        title.to_string()
+    } else if dir == root.join(C::NOTE_SUBDIR) || dir.parent() == Some(&root.join(daily_dir)) {
+        task_ref(root, daily_dir, to)
+    } else {
+        path
+    }
+}
+
+/// Note reference for a note at `path`: `YYYYMMDD/stem` for daily notes
+/// (under `daily_dir`), the stem otherwise
+pub fn task_ref(root: &Path, daily_dir: &str, path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let daily_dir = root.join(daily_dir);
+    match path.parent().and_then(|dir| dir.strip_prefix(&daily_dir).ok()) {
+        Some(date) if !date.as_os_str().is_empty() => {
+            format!("{}/{}", date.to_string_lossy().replace('\\', "/"), stem)
+        }
+        _ => stem,
+    }
+}
+
+/// Collect markdown files (with one of `extensions`) under `dir`, skipping
+/// hidden directories
+pub(crate) fn collect_markdown_files(dir: &Path, extensions: &[&str], files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if path.is_dir() {
+            if !name.starts_with('.') {
+                collect_markdown_files(&path, extensions, files)?;
+            }
+        } else if extensions.iter().any(|ext| name.ends_with(ext)) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Lexically normalize a path (resolve `.` and `..` without touching the filesystem)
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+/// Relative link path from directory `from_dir` to `target`, with `/` separators
+pub(crate) fn relative_path(from_dir: &Path, target: &Path) -> String {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = target.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(to[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()));
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write;
+    use tempfile::TempDir;
+
+    fn change_for<'a>(changes: &'a [FileChange], root: &Path, relative: &str) -> &'a FileChange {
+        changes.iter().find(|c| c.source == root.join(relative)).unwrap()
+    }
+
+    #[test]
+    fn test_split_dest() {
+        assert_eq!(split_dest("note/a.md#intro"), Some(("note/a.md", "#intro")));
+        assert_eq!(split_dest("#daily/20250115/100000.md"), Some(("#daily/20250115/100000.md", "")));
+        assert_eq!(split_dest("#rust.md"), Some(("#rust.md", "")));
+        assert_eq!(split_dest("#section"), None);
+        assert_eq!(split_dest("https://example.com/a.md"), None);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(relative_path(Path::new("/c/note"), Path::new("/c/note/a.md")), "a.md");
+        assert_eq!(relative_path(Path::new("/c/note/x"), Path::new("/c/note/a.md")), "../a.md");
+        assert_eq!(relative_path(Path::new("/c"), Path::new("/c/#daily/d/a.md")), "#daily/d/a.md");
+    }
+
+    #[test]
+    fn test_plan_move_rewrites_backlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Idea\n\nSee [other](other.md) and [self](#top).\n");
+        write(root, "note/other.md", "# Other\n\n[idea](idea.md#part) and [ext](https://x.org/idea.md)\n\n[r]: ./idea.md\n");
+        write(root, "#rust.md", "# rust\n\n## 2025-01-15\n- [Idea](note/idea.md)\n");
+        write(root, "TASK.md", "---\nPREFIX: TASK-\n---\n\n---\n\n[TASK-01]: idea\n[TASK-02]: other\n");
+
+        let from = root.join("note/idea.md");
+        let to = root.join("note/archive/better-idea.md");
+        let changes = plan_move(root, &from, &to, None).unwrap();
+        assert_eq!(changes.len(), 4);
+
+        let other = change_for(&changes, root, "note/other.md");
+        assert!(other.new_content.contains("[idea](archive/better-idea.md#part)"));
+        assert!(other.new_content.contains("[r]: ./archive/better-idea.md"));
+        assert!(other.new_content.contains("https://x.org/idea.md"));
+
+        let tag = change_for(&changes, root, "#rust.md");
+        assert!(tag.new_content.contains("- [Idea](note/archive/better-idea.md)"));
+
+        let task = change_for(&changes, root, "TASK.md");
+        assert!(task.new_content.contains("[TASK-01]: better-idea\n"));
+        assert!(task.new_content.contains("[TASK-02]: other\n"));
+
+        // The moved note's own relative links follow it
+        let moved = change_for(&changes, root, "note/idea.md");
+        assert_eq!(moved.path, to);
+        assert!(moved.new_content.contains("[other](../other.md)"));
+        assert!(moved.new_content.contains("[self](#top)"));
+
+        assert_eq!(task.changed_lines(), vec![(7, "[TASK-01]: idea", "[TASK-01]: better-idea")]);
+    }
+
+    #[test]
+    fn test_plan_move_rewrites_whole_task_refs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/weekly-sync.md", "# Weekly Sync\n");
+        write(root, "TASK.md", "---\nPREFIX: TASK-\n---\n\n---\n\n[TASK-01]: Weekly Sync\n[TASK-02]: ./note/weekly-sync.md\n");
+
+        let from = root.join("note/weekly-sync.md");
+        let changes = plan_move(root, &from, &root.join("note/team-sync.md"), Some("Team Sync")).unwrap();
+        let task = change_for(&changes, root, "TASK.md");
+        assert!(task.new_content.ends_with("[TASK-01]: team-sync\n[TASK-02]: ./note/team-sync.md\n"));
+    }
+
+    #[test]
+    fn test_plan_move_daily_index() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "#daily/20250115/100000-standup.md", "# Standup\n");
+        write(root, "note/#daily.md", "# Daily Notes\n\n- [Standup](#daily/20250115/100000-standup.md)\n");
+
+        let from = root.join("#daily/20250115/100000-standup.md");
+        let to = root.join("#daily/20250115/100000-sync.md");
+        let changes = plan_move(root, &from, &to, None).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].new_content.contains("(#daily/20250115/100000-sync.md)"));
+        assert_eq!(task_ref(root, "#daily", &to), "20250115/100000-sync");
+
+        let diff = changes[0].diff(root);
+        assert!(diff.starts_with("--- a/note/#daily.md\n+++ b/note/#daily.md\n@@ -3 +3 @@\n"));
+    }
+
+    #[test]
+    fn test_plan_move_rewrites_wiki_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "note/idea.md", "# Old Title\n");
+        write(root, "note/idea-2.md", "# Idea 2\n");
+        write(
+            root,
+            "note/other.md",
+            "[[Old Title]], [[idea#Part|my idea]], ![[ idea ]], [[note/idea]], [[note/idea.md]], [[idea-2]]\n\n`[[idea]]`\n",
+        );
+
+        let from = root.join("note/idea.md");
+        let changes = plan_move(root, &from, &root.join("note/better-idea.md"), None).unwrap();
+        let other = change_for(&changes, root, "note/other.md");
+        assert_eq!(
+            other.new_content,
+            "[[Old Title]], [[better-idea#Part|my idea]], ![[ better-idea ]], [[note/better-idea]], [[note/better-idea.md]], [[idea-2]]\n\n`[[idea]]`\n"
+        );
+
+        // Renamed by title: links by title get the new one, links by name the new stem
+        let changes = plan_move(root, &from, &root.join("note/better-idea.md"), Some("Better Idea")).unwrap();
+        let other = change_for(&changes, root, "note/other.md");
+        assert!(other.new_content.starts_with("[[Better Idea]], [[better-idea#Part|my idea]], ![[ better-idea ]],"));
+
+        // Outside note/, names are not found: link by path
+        let changes = plan_move(root, &from, &root.join("archive/idea.md"), None).unwrap();
+        let other = change_for(&changes, root, "note/other.md");
+        assert!(other.new_content.starts_with("[[archive/idea]], [[archive/idea#Part|my idea]], ![[ archive/idea ]],"));
+        let changes = plan_move(root, &from, &root.join("archive/idea.md"), Some("Archived")).unwrap();
+        assert!(change_for(&changes, root, "note/other.md").new_content.starts_with("[[archive/idea]],"));
+    }
+}
@@ -91,6 +91,9 @@ impl ResolveContext {
             .ok()
             .filter(|s| !s.is_empty());
 
+        // The home-level settings file gives the defaults of every capsa
+        crate::CapsaConfig::set_home(&home);
+
         Self {
             home,
             global,
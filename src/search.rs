@@ -0,0 +1,616 @@
+//! Full-text search module
+//!
+//! Maintains a persistent inverted index per capsa under `.index/search.json`
+//! and ranks matches with BM25.
+//!
+//! The index is updated incrementally: files are re-tokenized only when their
+//! modification time or size changed since the last update.
+//!
+//! Text is split with the CJK-aware tokenizer (`tokenizer.rs`), so Chinese
+//! and English notes are searchable alike.
+//!
+//! Query syntax:
+//! - `rust async` → documents containing any of the terms, ranked by BM25
+//! - `"error handling"` → documents containing the exact phrase
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::config::CapsaConfig;
+use crate::constants as C;
+use crate::lock::CapsaLock;
+use crate::note_resolver::NoteArea;
+use crate::tokenizer::tokenize;
+use crate::util;
+
+/// A document stored in the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedDoc {
+    /// Path relative to the capsa root (forward slashes)
+    pub path: String,
+    /// Note title (first H1 heading or filename)
+    pub title: String,
+    /// Area of the capsa the note belongs to
+    pub area: NoteArea,
+    /// Note date (YYYYMMDD): daily directory date, or modification date otherwise
+    pub date: String,
+    /// Modification time (nanoseconds since epoch) at indexing time
+    mtime: u64,
+    /// File size at indexing time
+    size: u64,
+    /// Number of tokens in the document
+    length: u32,
+}
+
+/// Persistent inverted index for a capsa
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    next_id: u32,
+    /// Document id -> document
+    docs: BTreeMap<u32, IndexedDoc>,
+    /// Term -> document id -> token positions
+    postings: BTreeMap<String, BTreeMap<u32, Vec<u32>>>,
+}
+
+/// Parsed search query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// Free terms (any may match)
+    pub terms: Vec<String>,
+    /// Phrases (all must match, tokens must be adjacent)
+    pub phrases: Vec<Vec<String>>,
+}
+
+/// Result filters
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    /// Only notes in this area
+    pub area: Option<NoteArea>,
+    /// Only notes listed in these relative paths (e.g. members of a tag)
+    pub paths: Option<HashSet<String>>,
+    /// Only notes dated on or after this date (YYYYMMDD)
+    pub from: Option<String>,
+    /// Only notes dated on or before this date (YYYYMMDD)
+    pub to: Option<String>,
+}
+
+/// A ranked search hit
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub path: String,
+    pub title: String,
+    pub area: NoteArea,
+    pub date: String,
+    pub score: f64,
+}
+
+impl SearchIndex {
+    /// Path of the index file for a capsa
+    pub fn file(capsa_path: &Path) -> PathBuf {
+        capsa_path.join(C::INDEX_DIR).join(C::SEARCH_INDEX_FILENAME)
+    }
+
+    /// Load the index from disk, or start an empty one if missing/outdated
+    pub fn load(capsa_path: &Path) -> io::Result<Self> {
+        let path = Self::file(capsa_path);
+        if !path.exists() {
+            return Ok(Self::empty());
+        }
+
+        let content = fs::read_to_string(&path)?;
+        match serde_json::from_str::<SearchIndex>(&content) {
+            Ok(index) if index.version == C::SEARCH_INDEX_VERSION => Ok(index),
+            // Outdated or corrupt index: rebuild from scratch
+            _ => Ok(Self::empty()),
+        }
+    }
+
+    /// Create an empty index
+    pub fn empty() -> Self {
+        SearchIndex {
+            version: C::SEARCH_INDEX_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Save the index to disk
+    pub fn save(&self, capsa_path: &Path) -> io::Result<()> {
+        let path = Self::file(capsa_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        util::atomic_write(&path, &content)
+    }
+
+    /// Load the index, bring it up to date with the capsa and save it if anything changed
+    pub fn open(capsa_path: &Path) -> io::Result<Self> {
+        let mut index = Self::load(capsa_path)?;
+        if index.update(capsa_path)? {
+            let _lock = CapsaLock::acquire(capsa_path)?;
+            index.save(capsa_path)?;
+        }
+        Ok(index)
+    }
+
+    /// Number of indexed documents
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    /// Whether the index holds no documents
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Re-index added/changed notes and drop deleted ones.
+    /// Returns true if the index changed.
+    pub fn update(&mut self, capsa_path: &Path) -> io::Result<bool> {
+        let config = CapsaConfig::load(capsa_path)?;
+        let mut on_disk: HashMap<String, (PathBuf, u64, u64)> = HashMap::new();
+        collect_notes(capsa_path, capsa_path, &config, &mut on_disk)?;
+
+        let mut changed = false;
+
+        // Drop documents that were deleted or modified
+        let stale: Vec<u32> = self.docs.iter()
+            .filter(|(_, doc)| match on_disk.get(&doc.path) {
+                Some((_, mtime, size)) => *mtime != doc.mtime || *size != doc.size,
+                None => true,
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in stale {
+            self.remove_doc(id);
+            changed = true;
+        }
+
+        // Add documents that are new (or were just dropped as modified)
+        let indexed: HashSet<String> = self.docs.values().map(|d| d.path.clone()).collect();
+        let mut pending: Vec<_> = on_disk.into_iter()
+            .filter(|(relative, _)| !indexed.contains(relative))
+            .collect();
+        pending.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (relative, (path, mtime, size)) in pending {
+            let content = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(_) => continue, // Unreadable (e.g. not UTF-8): skip
+            };
+            self.add_doc(&config, &relative, &path, &content, mtime, size);
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
+    /// Add a document to the index
+    fn add_doc(&mut self, config: &CapsaConfig, relative: &str, path: &Path, content: &str, mtime: u64, size: u64) {
+        let area = match NoteArea::from_relative(relative, &config.daily_dir) {
+            Some(a) => a,
+            None => return,
+        };
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let tokens = tokenize(content);
+        for (pos, token) in tokens.iter().enumerate() {
+            self.postings
+                .entry(token.clone())
+                .or_default()
+                .entry(id)
+                .or_default()
+                .push(pos as u32);
+        }
+
+        self.docs.insert(id, IndexedDoc {
+            path: relative.to_string(),
+            title: util::extract_note_title(path, content),
+            area,
+            date: note_date(relative, &config.daily_dir, mtime),
+            mtime,
+            size,
+            length: tokens.len() as u32,
+        });
+    }
+
+    /// Remove a document and its postings
+    fn remove_doc(&mut self, id: u32) {
+        if self.docs.remove(&id).is_none() {
+            return;
+        }
+        self.postings.retain(|_, docs| {
+            docs.remove(&id);
+            !docs.is_empty()
+        });
+    }
+
+    /// Run a query, returning hits ranked by BM25 score (best first)
+    pub fn search(&self, query: &Query, filter: &SearchFilter, limit: usize) -> Vec<SearchHit> {
+        let total_docs = self.docs.len() as f64;
+        if total_docs == 0.0 {
+            return Vec::new();
+        }
+        let avg_len = self.docs.values().map(|d| d.length as f64).sum::<f64>() / total_docs;
+
+        // Candidate documents: must match every phrase, and at least one term
+        // (or only the phrases when the query has no free terms)
+        let mut candidates: Option<HashSet<u32>> = None;
+        for phrase in &query.phrases {
+            let matching = self.phrase_docs(phrase);
+            candidates = Some(match candidates {
+                Some(c) => c.intersection(&matching).copied().collect(),
+                None => matching,
+            });
+        }
+        if !query.terms.is_empty() {
+            let matching: HashSet<u32> = query.terms.iter()
+                .filter_map(|t| self.postings.get(t))
+                .flat_map(|docs| docs.keys().copied())
+                .collect();
+            candidates = Some(match candidates {
+                Some(c) => c.intersection(&matching).copied().collect(),
+                None => matching,
+            });
+        }
+
+        // All query tokens contribute to the score
+        let mut scoring_terms: Vec<&String> = query.terms.iter()
+            .chain(query.phrases.iter().flatten())
+            .collect();
+        scoring_terms.sort();
+        scoring_terms.dedup();
+
+        let mut hits: Vec<SearchHit> = candidates.unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| self.docs.get(&id).map(|doc| (id, doc)))
+            .filter(|(_, doc)| filter.matches(doc))
+            .map(|(id, doc)| {
+                let score = scoring_terms.iter()
+                    .map(|term| self.bm25(term, id, doc.length as f64, avg_len, total_docs))
+                    .sum();
+                SearchHit {
+                    path: doc.path.clone(),
+                    title: doc.title.clone(),
+                    area: doc.area,
+                    date: doc.date.clone(),
+                    score,
+                }
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score.partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        hits.truncate(limit);
+        hits
+    }
+
+    /// BM25 contribution of one term to one document
+    fn bm25(&self, term: &str, id: u32, doc_len: f64, avg_len: f64, total_docs: f64) -> f64 {
+        let docs = match self.postings.get(term) {
+            Some(d) => d,
+            None => return 0.0,
+        };
+        let tf = match docs.get(&id) {
+            Some(positions) => positions.len() as f64,
+            None => return 0.0,
+        };
+        let df = docs.len() as f64;
+        let idf = ((total_docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+        let norm = 1.0 - C::BM25_B + C::BM25_B * doc_len / avg_len.max(1.0);
+        idf * tf * (C::BM25_K1 + 1.0) / (tf + C::BM25_K1 * norm)
+    }
+
+    /// Documents containing the phrase (tokens at consecutive positions)
+    fn phrase_docs(&self, phrase: &[String]) -> HashSet<u32> {
+        let mut result = HashSet::new();
+        let first = match phrase.first().and_then(|t| self.postings.get(t)) {
+            Some(docs) => docs,
+            None => return result,
+        };
+
+        'docs: for (id, starts) in first {
+            let mut rest = Vec::with_capacity(phrase.len() - 1);
+            for term in &phrase[1..] {
+                match self.postings.get(term).and_then(|docs| docs.get(id)) {
+                    Some(positions) => rest.push(positions),
+                    None => continue 'docs,
+                }
+            }
+
+            let found = starts.iter().any(|&start| {
+                rest.iter().enumerate().all(|(offset, positions)| {
+                    positions.binary_search(&(start + offset as u32 + 1)).is_ok()
+                })
+            });
+            if found {
+                result.insert(*id);
+            }
+        }
+
+        result
+    }
+}
+
+impl SearchFilter {
+    /// Check whether a document passes all filters
+    fn matches(&self, doc: &IndexedDoc) -> bool {
+        if let Some(area) = self.area {
+            if doc.area != area {
+                return false;
+            }
+        }
+        if let Some(ref paths) = self.paths {
+            if !paths.contains(&doc.path) {
+                return false;
+            }
+        }
+        if let Some(ref from) = self.from {
+            if doc.date.as_str() < from.as_str() {
+                return false;
+            }
+        }
+        if let Some(ref to) = self.to {
+            if doc.date.as_str() > to.as_str() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl Query {
+    /// Parse a query string: quoted parts are phrases, the rest are free terms
+    pub fn parse(input: &str) -> Self {
+        let mut query = Query::default();
+
+        for (i, part) in input.split('"').enumerate() {
+            let tokens = tokenize(part);
+            if i % 2 == 1 {
+                // Inside quotes
+                match tokens.len() {
+                    0 => {}
+                    1 => query.terms.extend(tokens),
+                    _ => query.phrases.push(tokens),
+                }
+            } else {
+                query.terms.extend(tokens);
+            }
+        }
+
+        query
+    }
+
+    /// Whether the query has nothing to search for
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.phrases.is_empty()
+    }
+}
+
+/// Normalize a date filter (YYYY-MM-DD or YYYYMMDD) to YYYYMMDD
+pub fn parse_date_filter(s: &str) -> Option<String> {
+    let digits: String = s.chars().filter(|c| *c != '-').collect();
+    if digits.len() == 8 && digits.chars().all(|c| c.is_ascii_digit()) {
+        Some(digits)
+    } else {
+        None
+    }
+}
+
+/// Date of a note: the daily directory for daily notes, modification date otherwise
+fn note_date(relative: &str, daily_dir: &str, mtime: u64) -> String {
+    if let Some(rest) = relative.strip_prefix(&format!("{}/", daily_dir)) {
+        if let Some(date) = rest.split('/').next() {
+            return date.to_string();
+        }
+    }
+    let modified: DateTime<Local> = (UNIX_EPOCH + std::time::Duration::from_nanos(mtime)).into();
+    modified.format(C::DAILY_DATE_FORMAT).to_string()
+}
+
+/// Recursively collect indexable notes: relative path -> (full path, mtime, size)
+fn collect_notes(
+    base: &Path,
+    current: &Path,
+    config: &CapsaConfig,
+    notes: &mut HashMap<String, (PathBuf, u64, u64)>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(current)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+
+        // Skip hidden files and directories (.index, .template, ...)
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_notes(base, &path, config, notes)?;
+            continue;
+        }
+
+        let name_str = name.to_string_lossy();
+        if !config.extensions.iter().any(|ext| name_str.ends_with(ext.as_str())) {
+            continue;
+        }
+
+        let relative = path.strip_prefix(base)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if NoteArea::from_relative(&relative, &config.daily_dir).is_none() {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        let mtime = metadata.modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        notes.insert(relative, (path, mtime, metadata.len()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::stem;
+    use tempfile::TempDir;
+
+    fn setup() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("note/a1b2c3d4e5f6")).unwrap();
+        fs::create_dir_all(root.join("#daily/20250115")).unwrap();
+        fs::write(root.join("note/rust.md"), "# Rust\n\nError handling in Rust uses Result.").unwrap();
+        fs::write(root.join("note/python.md"), "# Python\n\nPython handles errors with exceptions.").unwrap();
+        fs::write(root.join("note/a1b2c3d4e5f6/book.md"), "# Book\n\nA book about handling errors.").unwrap();
+        fs::write(root.join("#daily/20250115/100000-standup.md"), "# Standup\n\nRust rust rust.").unwrap();
+        fs::write(root.join("note/#daily.md"), "# Daily Notes\n\nrust").unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_query_parse() {
+        let q = Query::parse("rust \"error handling\" Async");
+        assert_eq!(q.terms, vec!["rust", "async"]);
+        assert_eq!(q.phrases, vec![vec!["error".to_string(), stem("handling")]]);
+    }
+
+    #[test]
+    fn test_index_skips_index_files() {
+        let temp_dir = setup();
+        let index = SearchIndex::open(temp_dir.path()).unwrap();
+        assert_eq!(index.len(), 4);
+        assert!(SearchIndex::file(temp_dir.path()).exists());
+    }
+
+    #[test]
+    fn test_bm25_ranking() {
+        let temp_dir = setup();
+        let index = SearchIndex::open(temp_dir.path()).unwrap();
+        let hits = index.search(&Query::parse("rust"), &SearchFilter::default(), 10);
+        assert_eq!(hits.len(), 2);
+        // Higher term frequency in a short document ranks first
+        assert_eq!(hits[0].path, "#daily/20250115/100000-standup.md");
+        assert_eq!(hits[1].path, "note/rust.md");
+    }
+
+    #[test]
+    fn test_phrase_query() {
+        let temp_dir = setup();
+        let index = SearchIndex::open(temp_dir.path()).unwrap();
+        let hits = index.search(&Query::parse("\"handling errors\""), &SearchFilter::default(), 10);
+        // "handles errors" is the same phrase once stemmed; "Error handling" is not
+        let mut paths: Vec<&str> = hits.iter().map(|h| h.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["note/a1b2c3d4e5f6/book.md", "note/python.md"]);
+    }
+
+    #[test]
+    fn test_stemmed_search() {
+        let temp_dir = setup();
+        let index = SearchIndex::open(temp_dir.path()).unwrap();
+
+        let hits = index.search(&Query::parse("handled"), &SearchFilter::default(), 10);
+        assert_eq!(hits.len(), 3);
+        let hits = index.search(&Query::parse("exception"), &SearchFilter::default(), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "note/python.md");
+    }
+
+    #[test]
+    fn test_cjk_search() {
+        let temp_dir = setup();
+        fs::write(temp_dir.path().join("note/meeting.md"), "# 会议\n\n项目会议记录：讨论发布计划").unwrap();
+        let index = SearchIndex::open(temp_dir.path()).unwrap();
+
+        let hits = index.search(&Query::parse("会议记录"), &SearchFilter::default(), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "note/meeting.md");
+
+        let hits = index.search(&Query::parse("\"发布计划\""), &SearchFilter::default(), 10);
+        assert_eq!(hits.len(), 1);
+        assert!(index.search(&Query::parse("\"计划发布\""), &SearchFilter::default(), 10).is_empty());
+    }
+
+    #[test]
+    fn test_filters() {
+        let temp_dir = setup();
+        let index = SearchIndex::open(temp_dir.path()).unwrap();
+        let query = Query::parse("rust");
+
+        let filter = SearchFilter { area: Some(NoteArea::Permanent), ..Default::default() };
+        let hits = index.search(&query, &filter, 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, "note/rust.md");
+
+        let filter = SearchFilter {
+            area: Some(NoteArea::Daily),
+            from: Some("20250101".to_string()),
+            to: Some("20250131".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(index.search(&query, &filter, 10).len(), 1);
+
+        let filter = SearchFilter { to: Some("20240101".to_string()), ..Default::default() };
+        assert!(index.search(&query, &filter, 10).is_empty());
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let temp_dir = setup();
+        let mut index = SearchIndex::open(temp_dir.path()).unwrap();
+        assert!(!index.update(temp_dir.path()).unwrap());
+
+        fs::remove_file(temp_dir.path().join("note/python.md")).unwrap();
+        fs::write(temp_dir.path().join("note/go.md"), "# Go\n\nGoroutines").unwrap();
+        assert!(index.update(temp_dir.path()).unwrap());
+
+        assert_eq!(index.len(), 4);
+        assert!(index.search(&Query::parse("exceptions"), &SearchFilter::default(), 10).is_empty());
+        assert_eq!(index.search(&Query::parse("goroutines"), &SearchFilter::default(), 10).len(), 1);
+    }
+
+    #[test]
+    fn test_same_size_edit_within_a_second() {
+        let temp_dir = setup();
+        let path = temp_dir.path().join("note/go.md");
+        let set_mtime = |millis: u64| {
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(UNIX_EPOCH + std::time::Duration::from_millis(millis)).unwrap();
+        };
+
+        fs::write(&path, "# Go\n\nGoroutines").unwrap();
+        set_mtime(1_000_100);
+        let mut index = SearchIndex::open(temp_dir.path()).unwrap();
+
+        fs::write(&path, "# Go\n\nGoroutinez").unwrap();
+        set_mtime(1_000_200);
+        assert!(index.update(temp_dir.path()).unwrap());
+        assert_eq!(index.search(&Query::parse("goroutinez"), &SearchFilter::default(), 10).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_date_filter() {
+        assert_eq!(parse_date_filter("2025-01-15"), Some("20250115".to_string()));
+        assert_eq!(parse_date_filter("20250115"), Some("20250115".to_string()));
+        assert_eq!(parse_date_filter("2025-1-15"), None);
+    }
+}
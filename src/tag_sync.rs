@@ -0,0 +1,397 @@
+//! Tag index synchronisation
+//!
+//! The `tags` of a note's frontmatter are the source of truth, along with its
+//! inline `#hashtags` when the `inline_tags` setting is on; the `#tag.md`
+//! files in the capsa root index them, one link per note under the date it
+//! was tagged (`## YYYY-MM-DD`). The two drift apart when notes are edited by
+//! hand or by other tools. `TagIndex` reads both sides and reports the
+//! differences: `tag sync` adds and removes only the entries that differ,
+//! `tag rebuild` regenerates every tag file, keeping the date of the notes
+//! that were already indexed.
+//!
+//! Tag files written before tags were kept in the frontmatter list notes
+//! that carry no `tags`. Their entries are reported as stale but never
+//! removed unless asked (`--prune`); `--from-index` first copies them into
+//! the notes' frontmatter.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::config::CapsaConfig;
+use crate::constants as C;
+use crate::link_cache::LinkCache;
+use crate::{frontmatter, markdown};
+
+/// How a tag file differs from the frontmatter tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftKind {
+    /// Tagged in the note (frontmatter or inline), missing from the tag file
+    Missing,
+    /// In the tag file, but the note is not tagged (or no longer exists)
+    Stale,
+}
+
+/// What `tag sync` / `tag rebuild` may change
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncOptions {
+    /// Write the changes (otherwise only report them)
+    pub apply: bool,
+    /// Remove stale entries from the tag files
+    pub prune: bool,
+    /// First tag the notes listed in tag files in their frontmatter
+    pub from_index: bool,
+}
+
+/// One difference between the frontmatter tags and the tag files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagDrift {
+    pub tag: String,
+    /// Note path relative to the capsa root
+    pub path: String,
+    pub kind: DriftKind,
+}
+
+/// Result of `tag sync` / `tag rebuild`
+#[derive(Debug, Default)]
+pub struct TagSync {
+    /// Differences left after the backfill
+    pub drift: Vec<TagDrift>,
+    /// Stale entries copied into the notes' frontmatter (`--from-index`)
+    pub backfilled: Vec<TagDrift>,
+    /// Notes whose frontmatter changed (or would change with `--dry-run`)
+    pub notes: Vec<PathBuf>,
+    /// Tag files changed (or that would change with `--dry-run`)
+    pub files: Vec<PathBuf>,
+}
+
+/// Entry of a tag file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagEntry {
+    /// Date heading the entry is filed under
+    pub date: Option<String>,
+    /// Note path relative to the capsa root
+    pub path: String,
+    /// Line of the tag file
+    pub line: String,
+}
+
+/// Frontmatter tags and tag files of a capsa
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    /// Tag -> notes tagged in their frontmatter or inline (relative path -> title)
+    tagged: BTreeMap<String, BTreeMap<String, String>>,
+    /// Tag -> entries of its tag file
+    indexed: BTreeMap<String, Vec<TagEntry>>,
+    /// Every note, tagged or not (relative paths)
+    notes: BTreeSet<String>,
+}
+
+impl TagIndex {
+    /// Read the tags of every note (through the link cache) and the tag
+    /// files of the capsa root
+    pub fn load(capsa_path: &Path, config: &CapsaConfig) -> io::Result<Self> {
+        let mut index = TagIndex::default();
+
+        let cache = LinkCache::open(capsa_path)?;
+        for (relative, file) in cache.files() {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            if name.starts_with(C::TAG_PREFIX) || *relative == config.task_file {
+                continue;
+            }
+            index.notes.insert(relative.clone());
+            for tag in &file.note_tags(config.inline_tags) {
+                index.tagged.entry(tag.clone())
+                    .or_default()
+                    .insert(relative.clone(), file.title.clone());
+            }
+        }
+
+        for entry in fs::read_dir(capsa_path)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let tag = match name.strip_prefix(C::TAG_PREFIX).and_then(|n| n.strip_suffix(C::MARKDOWN_EXTENSION)) {
+                Some(tag) if entry.path().is_file() => tag.to_string(),
+                _ => continue,
+            };
+            let content = fs::read_to_string(entry.path())?;
+            index.indexed.insert(tag, parse_entries(&content));
+        }
+
+        Ok(index)
+    }
+
+    /// Notes tagged `tag`, or listed in its tag file
+    pub fn notes(&self, tag: &str) -> BTreeSet<String> {
+        let tagged = self.tagged.get(tag).into_iter().flat_map(|notes| notes.keys().cloned());
+        let indexed = self.indexed.get(tag).into_iter().flatten().map(|entry| entry.path.clone());
+        tagged.chain(indexed).collect()
+    }
+
+    /// Notes tagged `tag` that its tag file does not list
+    pub fn unindexed(&self, tag: &str) -> Vec<String> {
+        let indexed = self.indexed.get(tag).map(Vec::as_slice).unwrap_or_default();
+        self.tagged.get(tag)
+            .into_iter()
+            .flat_map(|notes| notes.keys())
+            .filter(|path| !indexed.iter().any(|entry| entry.path == **path))
+            .cloned()
+            .collect()
+    }
+
+    /// Differences between the notes' tags and the tag files, by tag and path
+    pub fn drift(&self) -> Vec<TagDrift> {
+        let mut drift = Vec::new();
+        for tag in self.tag_names() {
+            let tagged = self.tagged.get(tag);
+            let indexed = self.indexed.get(tag).map(Vec::as_slice).unwrap_or_default();
+
+            for path in self.unindexed(tag) {
+                drift.push(TagDrift { tag: tag.clone(), path, kind: DriftKind::Missing });
+            }
+            for entry in indexed {
+                let is_tagged = tagged.is_some_and(|notes| notes.contains_key(&entry.path));
+                let reported = drift.iter().any(|d| d.tag == *tag && d.path == entry.path);
+                if !is_tagged && !reported {
+                    drift.push(TagDrift { tag: tag.clone(), path: entry.path.clone(), kind: DriftKind::Stale });
+                }
+            }
+        }
+        drift.sort_by(|a, b| (&a.tag, &a.path).cmp(&(&b.tag, &b.path)));
+        drift
+    }
+
+    /// Stale entries of notes that still exist: the notes to tag in their
+    /// frontmatter to keep them in the tag files (`--from-index`)
+    pub fn backfill(&self) -> Vec<TagDrift> {
+        self.drift()
+            .into_iter()
+            .filter(|d| d.kind == DriftKind::Stale && self.notes.contains(&d.path))
+            .collect()
+    }
+
+    /// Tag files regenerated from the frontmatter tags, by tag: `None` for a
+    /// tag file left without entries. Notes already indexed keep their date,
+    /// the others are filed under `today`. Stale entries are kept as they
+    /// are, unless `prune`.
+    pub fn rebuild(&self, config: &CapsaConfig, today: &str, prune: bool) -> BTreeMap<String, Option<String>> {
+        let no_notes = BTreeMap::new();
+        let mut files = BTreeMap::new();
+        for tag in self.tag_names() {
+            let notes = self.tagged.get(tag).unwrap_or(&no_notes);
+
+            // Date -> lines, indexed notes first in their tag file order
+            let mut sections: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+            let mut filed: Vec<&str> = Vec::new();
+            let indexed = self.indexed.get(tag).map(Vec::as_slice).unwrap_or_default();
+            for entry in indexed {
+                if filed.contains(&entry.path.as_str()) {
+                    continue;
+                }
+                let line = match notes.get(&entry.path) {
+                    Some(title) => config.tag_link(title, &entry.path),
+                    None if prune => continue,
+                    None => entry.line.clone(),
+                };
+                sections.entry(entry.date.as_deref().unwrap_or(today)).or_default().push(line);
+                filed.push(&entry.path);
+            }
+            for (path, title) in notes {
+                if !filed.contains(&path.as_str()) {
+                    sections.entry(today).or_default().push(config.tag_link(title, path));
+                }
+            }
+
+            if sections.is_empty() {
+                files.insert(tag.clone(), None);
+                continue;
+            }
+            let mut content = format!("# {}\n", tag);
+            for (date, links) in sections {
+                content.push_str(&format!("\n## {}\n{}\n", date, links.join("\n")));
+            }
+            files.insert(tag.clone(), Some(content));
+        }
+        files
+    }
+
+    /// Every tag, tagged in a frontmatter or with a tag file
+    fn tag_names(&self) -> Vec<&String> {
+        let mut tags: Vec<&String> = self.tagged.keys().chain(self.indexed.keys()).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+/// Tags of a note: its frontmatter `tags`, then with `inline` its inline hashtags
+pub fn note_tags(content: &str, inline: bool) -> Vec<String> {
+    let mut tags = frontmatter::tags(content);
+    if inline {
+        for tag in markdown::inline_tags(content) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+/// Entries of a tag file: the first link of each line, filed under the last
+/// date before it (a `## YYYY-MM-DD` heading or a bare date line)
+pub fn parse_entries(content: &str) -> Vec<TagEntry> {
+    let mut entries = Vec::new();
+    let mut date: Option<String> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if let Some(heading) = date_heading(trimmed) {
+            date = Some(heading.to_string());
+            continue;
+        }
+
+        let target = trimmed.find("](").and_then(|start| {
+            let rest = &trimmed[start + 2..];
+            rest.find(')').map(|end| &rest[..end])
+        });
+        if let Some(path) = target.filter(|path| !path.is_empty()) {
+            entries.push(TagEntry { date: date.clone(), path: path.to_string(), line: line.to_string() });
+        }
+    }
+
+    entries
+}
+
+/// Date of a tag file section heading: `## YYYY-MM-DD`, or a bare date line
+/// as in tag files written before date headings
+pub fn date_heading(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let heading = line.strip_prefix("## ").unwrap_or(line).trim();
+    NaiveDate::parse_from_str(heading, C::DAILY_DATE_DISPLAY_FORMAT).is_ok().then_some(heading)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn index(tagged: &[(&str, &str, &str)], indexed: &[(&str, &str)]) -> TagIndex {
+        let mut index = TagIndex::default();
+        for (tag, path, title) in tagged {
+            index.tagged.entry(tag.to_string()).or_default().insert(path.to_string(), title.to_string());
+        }
+        for (tag, content) in indexed {
+            index.indexed.insert(tag.to_string(), parse_entries(content));
+        }
+        index
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let content = "# rust\n\n2026-01-02\n- [Old](note/old.md)\n\n## 2026-03-04\n- [Idea](note/idea.md)\n- [Daily](#daily/2026-03-04/note.md)\n";
+        let entry = |date: &str, path: &str, line: &str| TagEntry {
+            date: Some(date.to_string()),
+            path: path.to_string(),
+            line: line.to_string(),
+        };
+        assert_eq!(parse_entries(content), vec![
+            entry("2026-01-02", "note/old.md", "- [Old](note/old.md)"),
+            entry("2026-03-04", "note/idea.md", "- [Idea](note/idea.md)"),
+            entry("2026-03-04", "#daily/2026-03-04/note.md", "- [Daily](#daily/2026-03-04/note.md)"),
+        ]);
+
+        let entries = parse_entries("# rust\n\n- [Undated](a.md)\n");
+        assert_eq!(entries[0].date, None);
+    }
+
+    #[test]
+    fn test_drift() {
+        let index = index(
+            &[("rust", "note/idea.md", "Idea"), ("cli", "note/idea.md", "Idea")],
+            &[("rust", "# rust\n\n## 2026-01-02\n- [Old](note/old.md)\n")],
+        );
+
+        assert_eq!(index.drift(), vec![
+            TagDrift { tag: "cli".to_string(), path: "note/idea.md".to_string(), kind: DriftKind::Missing },
+            TagDrift { tag: "rust".to_string(), path: "note/idea.md".to_string(), kind: DriftKind::Missing },
+            TagDrift { tag: "rust".to_string(), path: "note/old.md".to_string(), kind: DriftKind::Stale },
+        ]);
+    }
+
+    #[test]
+    fn test_rebuild_keeps_dates() {
+        let index = index(
+            &[("rust", "note/idea.md", "Idea"), ("rust", "note/new.md", "New")],
+            &[
+                ("rust", "# rust\n\n2026-01-02\n- [Idea (old title)](note/idea.md)\n- [Old](note/old.md)\n"),
+                ("gone", "# gone\n\n## 2026-01-02\n- [Old](note/old.md)\n"),
+            ],
+        );
+
+        let files = index.rebuild(&CapsaConfig::default(), "2026-05-06", true);
+        assert_eq!(files["gone"], None);
+        assert_eq!(
+            files["rust"].as_deref(),
+            Some("# rust\n\n## 2026-01-02\n- [Idea](note/idea.md)\n\n## 2026-05-06\n- [New](note/new.md)\n")
+        );
+
+        // Without prune, stale entries stay as written
+        let files = index.rebuild(&CapsaConfig::default(), "2026-05-06", false);
+        assert_eq!(files["gone"].as_deref(), Some("# gone\n\n## 2026-01-02\n- [Old](note/old.md)\n"));
+        assert!(files["rust"].as_deref().unwrap().contains("## 2026-01-02\n- [Idea](note/idea.md)\n- [Old](note/old.md)\n"));
+    }
+
+    #[test]
+    fn test_backfill() {
+        let mut index = index(
+            &[("rust", "note/idea.md", "Idea")],
+            &[("rust", "# rust\n\n2025-01-10\n- [Idea](note/idea.md)\n- [Legacy](note/legacy.md)\n- [Gone](note/gone.md)")],
+        );
+        index.notes.extend(["note/idea.md".to_string(), "note/legacy.md".to_string()]);
+
+        assert_eq!(index.backfill(), vec![
+            TagDrift { tag: "rust".to_string(), path: "note/legacy.md".to_string(), kind: DriftKind::Stale },
+        ]);
+    }
+
+    #[test]
+    fn test_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("note")).unwrap();
+        fs::write(root.join("note/idea.md"), "---\ntags: [rust]\n---\n# Idea\n").unwrap();
+        fs::write(root.join("#rust.md"), "# rust\n\n## 2026-01-02\n- [Idea](note/idea.md)\n").unwrap();
+        fs::write(root.join("TASK.md"), "---\ntags: [rust]\n---\n# Tasks\n").unwrap();
+
+        let index = TagIndex::load(root, &CapsaConfig::default()).unwrap();
+        assert!(index.drift().is_empty());
+        assert_eq!(index.tagged["rust"].len(), 1);
+    }
+
+    #[test]
+    fn test_load_inline_tags() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("note")).unwrap();
+        fs::write(root.join("note/idea.md"), "---\ntags: [rust]\n---\n# Idea\n\nAbout #cli and #rust.\n").unwrap();
+
+        let index = TagIndex::load(root, &CapsaConfig::default()).unwrap();
+        assert!(index.notes("cli").is_empty());
+
+        let config = CapsaConfig { inline_tags: true, ..CapsaConfig::default() };
+        let index = TagIndex::load(root, &config).unwrap();
+        assert_eq!(index.unindexed("cli"), vec!["note/idea.md".to_string()]);
+        assert_eq!(index.drift().len(), 2);
+    }
+
+    #[test]
+    fn test_note_tags() {
+        let content = "---\ntags: [rust]\n---\n# Idea\n\n#cli and #rust\n";
+        assert_eq!(note_tags(content, false), vec!["rust"]);
+        assert_eq!(note_tags(content, true), vec!["rust", "cli"]);
+    }
+}
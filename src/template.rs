@@ -0,0 +1,337 @@
+//! Note templates
+//!
+//! Templates are markdown files in the capsa's `.template/` directory: the
+//! template `meeting` is `.template/meeting.md`. `note` and `daily` render one
+//! with `--template`, or the capsa's `note_template` / `daily_template` setting.
+//!
+//! ```markdown
+//! ---
+//! title: {{title}}
+//! date: {{date}}
+//! tags: [meeting]
+//! ---
+//! # {{title}}
+//!
+//! {{content}}
+//! ```
+//!
+//! `{{name}}` placeholders are replaced by the variables of the new note;
+//! unknown placeholders are kept as written. A placeholder that is the whole
+//! value of a frontmatter key is quoted as YAML needs it.
+//!
+//! The template's frontmatter is the default frontmatter of the note: keys of
+//! a frontmatter block at the start of the stdin content override it. The rest
+//! of the content goes at `{{content}}`, or at the end without that marker.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_yaml::{Mapping, Value};
+
+use crate::constants as C;
+use crate::engine::CapsaEngine;
+use crate::frontmatter;
+
+/// `{{name}}` placeholder, with optional spaces inside the braces
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{\{\s*([A-Za-z_]+)\s*\}\}").expect("valid placeholder regex")
+});
+
+/// `key: {{name}}` frontmatter line whose whole value is a placeholder
+static YAML_PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\s*[^\s:#][^:]*:\s+)\{\{\s*([A-Za-z_]+)\s*\}\}\s*$").expect("valid placeholder regex")
+});
+
+/// Variables available to a template
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemplateVars {
+    /// Title of the note
+    pub title: String,
+    /// Creation date (`YYYY-MM-DD`)
+    pub date: String,
+    /// Creation time (`HH:MM`)
+    pub time: String,
+    /// Agent creating the note (EMX_AGENT_NAME, empty if unset)
+    pub agent: String,
+    /// Name of the capsa
+    pub capsa: String,
+    /// Source of the note (empty without `--source`)
+    pub source: String,
+    /// Content read from stdin
+    pub content: String,
+}
+
+impl TemplateVars {
+    /// Variables for a note created now
+    pub fn now(title: &str, capsa: &str, source: Option<&str>, agent: Option<&str>, content: &str) -> Self {
+        let now = CapsaEngine::get_timestamp();
+        TemplateVars {
+            title: title.to_string(),
+            date: now.format(C::DAILY_DATE_DISPLAY_FORMAT).to_string(),
+            time: now.format(C::TEMPLATE_TIME_FORMAT).to_string(),
+            agent: agent.unwrap_or_default().to_string(),
+            capsa: capsa.to_string(),
+            source: source.unwrap_or_default().to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    /// Value of a variable by placeholder name
+    fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "title" => Some(&self.title),
+            "date" => Some(&self.date),
+            "time" => Some(&self.time),
+            "agent" => Some(&self.agent),
+            "capsa" => Some(&self.capsa),
+            "source" => Some(&self.source),
+            "content" => Some(&self.content),
+            _ => None,
+        }
+    }
+}
+
+/// A template loaded from `.template/`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    /// Template name (filename without `.md`)
+    pub name: String,
+    /// Raw template text
+    pub text: String,
+}
+
+impl Template {
+    /// Template directory of a capsa
+    pub fn dir(capsa_path: &Path) -> PathBuf {
+        capsa_path.join(C::TEMPLATE_DIR)
+    }
+
+    /// Path of the template `name` (`meeting` or `meeting.md`)
+    pub fn path(capsa_path: &Path, name: &str) -> io::Result<PathBuf> {
+        check_name(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let stem = name.strip_suffix(C::MARKDOWN_EXTENSION).unwrap_or(name);
+        Ok(Self::dir(capsa_path).join(format!("{}{}", stem, C::MARKDOWN_EXTENSION)))
+    }
+
+    /// Load the template `name`
+    pub fn load(capsa_path: &Path, name: &str) -> io::Result<Self> {
+        let path = Self::path(capsa_path, name)?;
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok(Template {
+                name: name.strip_suffix(C::MARKDOWN_EXTENSION).unwrap_or(name).to_string(),
+                text,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let available = Self::list(capsa_path)?;
+                let hint = if available.is_empty() {
+                    format!("no templates in {}/", C::TEMPLATE_DIR)
+                } else {
+                    format!("available: {}", available.join(", "))
+                };
+                Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Template '{}' not found ({})", name, hint),
+                ))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Names of the templates of a capsa, sorted
+    pub fn list(capsa_path: &Path) -> io::Result<Vec<String>> {
+        let dir = Self::dir(capsa_path);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut names: Vec<String> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.strip_suffix(C::MARKDOWN_EXTENSION).map(str::to_string)
+            })
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Render the template with `vars`
+    pub fn render(&self, vars: &TemplateVars) -> String {
+        let (content_yaml, content) = match frontmatter::split(&vars.content) {
+            Some((yaml, body)) => (Some(yaml), body),
+            None => (None, vars.content.as_str()),
+        };
+        let (template_yaml, body) = match frontmatter::split(&self.text) {
+            Some((yaml, body)) => (Some(render_yaml(yaml, vars)), body),
+            None => (None, self.text.as_str()),
+        };
+
+        let content = content.trim_end_matches('\n');
+        let mut has_marker = false;
+        let mut body = PLACEHOLDER.replace_all(body, |caps: &Captures| {
+            match &caps[1] {
+                "content" => {
+                    has_marker = true;
+                    content.to_string()
+                }
+                name => vars.get(name).unwrap_or(&caps[0]).to_string(),
+            }
+        }).into_owned();
+        if !has_marker && !content.is_empty() {
+            if !body.is_empty() && !body.ends_with("\n\n") {
+                body.push_str(if body.ends_with('\n') { "\n" } else { "\n\n" });
+            }
+            body.push_str(content);
+            body.push('\n');
+        }
+
+        let yaml = match (template_yaml, content_yaml) {
+            (Some(defaults), Some(overrides)) => Some(merge_yaml(&defaults, overrides)),
+            (defaults, overrides) => defaults.or(overrides.map(str::to_string)),
+        };
+        match yaml {
+            Some(yaml) if yaml.is_empty() => format!("---\n---\n{}", body),
+            Some(yaml) => format!("---\n{}\n---\n{}", yaml.trim_end_matches('\n'), body),
+            None => body,
+        }
+    }
+}
+
+/// Check a template name: a plain filename, not hidden
+pub(crate) fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(format!("Invalid template name '{}' (expected a file name in {}/)", name, C::TEMPLATE_DIR));
+    }
+    Ok(())
+}
+
+/// Replace the placeholders of the template's frontmatter, quoting values
+/// that stand alone as a key's value
+fn render_yaml(yaml: &str, vars: &TemplateVars) -> String {
+    yaml.lines()
+        .map(|line| {
+            if let Some(caps) = YAML_PLACEHOLDER.captures(line) {
+                if let Some(value) = vars.get(&caps[2]) {
+                    return format!("{}{}", &caps[1], yaml_scalar(value));
+                }
+            }
+            PLACEHOLDER.replace_all(line, |caps: &Captures| {
+                match &caps[1] {
+                    "content" => caps[0].to_string(),
+                    name => vars.get(name).unwrap_or(&caps[0]).to_string(),
+                }
+            }).into_owned()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A string as a single-line YAML scalar, quoted if needed
+fn yaml_scalar(value: &str) -> String {
+    serde_yaml::to_string(&Value::String(value.to_string()))
+        .map(|s| s.trim_end().to_string())
+        .unwrap_or_else(|_| format!("{:?}", value))
+}
+
+/// Template frontmatter with the keys of the content's frontmatter replaced
+/// or added; the template's text is kept if either block is not a mapping
+fn merge_yaml(defaults: &str, overrides: &str) -> String {
+    let parse = |yaml: &str| match serde_yaml::from_str::<Value>(yaml) {
+        Ok(Value::Mapping(map)) => Some(map),
+        Ok(Value::Null) => Some(Mapping::new()),
+        _ => None,
+    };
+    match (parse(defaults), parse(overrides)) {
+        (Some(mut merged), Some(overrides)) => {
+            for (key, value) in overrides {
+                merged.insert(key, value);
+            }
+            serde_yaml::to_string(&merged).unwrap_or_else(|_| defaults.to_string())
+        }
+        _ => defaults.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn vars(content: &str) -> TemplateVars {
+        TemplateVars {
+            title: "Weekly: Sync".to_string(),
+            date: "2026-02-14".to_string(),
+            time: "10:00".to_string(),
+            agent: "alice".to_string(),
+            capsa: "work".to_string(),
+            source: String::new(),
+            content: content.to_string(),
+        }
+    }
+
+    fn template(text: &str) -> Template {
+        Template { name: "t".to_string(), text: text.to_string() }
+    }
+
+    #[test]
+    fn test_render_variables_and_content() {
+        let t = template("# {{title}}\n\n{{ date }} {{time}} by {{agent}} in {{capsa}} {{unknown}}\n\n{{content}}\n\n## Notes\n");
+        assert_eq!(
+            t.render(&vars("Agenda\n")),
+            "# Weekly: Sync\n\n2026-02-14 10:00 by alice in work {{unknown}}\n\nAgenda\n\n## Notes\n"
+        );
+        // Placeholders in the content are not expanded
+        assert_eq!(template("{{content}}\n").render(&vars("{{title}}\n")), "{{title}}\n");
+    }
+
+    #[test]
+    fn test_render_appends_content_without_marker() {
+        assert_eq!(template("# {{title}}\n").render(&vars("Body\n")), "# Weekly: Sync\n\nBody\n");
+        assert_eq!(template("# {{title}}\n").render(&vars("")), "# Weekly: Sync\n");
+    }
+
+    #[test]
+    fn test_render_frontmatter() {
+        let t = template("---\ntitle: {{title}}\ndate: {{date}}\nsource: {{source}}\ntags: [meeting]\n---\n# {{title}}\n");
+        assert_eq!(
+            t.render(&vars("")),
+            "---\ntitle: 'Weekly: Sync'\ndate: 2026-02-14\nsource: ''\ntags: [meeting]\n---\n# Weekly: Sync\n"
+        );
+
+        // Content frontmatter overrides the template's keys
+        let rendered = t.render(&vars("---\ntags: [retro]\nstatus: draft\n---\nBody\n"));
+        let (yaml, body) = frontmatter::split(&rendered).unwrap();
+        let map: Mapping = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(map.get("title"), Some(&Value::String("Weekly: Sync".into())));
+        assert_eq!(map.get("tags"), Some(&Value::Sequence(vec![Value::String("retro".into())])));
+        assert_eq!(map.get("status"), Some(&Value::String("draft".into())));
+        assert_eq!(body, "# Weekly: Sync\n\nBody\n");
+
+        // Content frontmatter moves to the top of a template without one
+        assert_eq!(
+            template("# {{title}}\n\n{{content}}\n").render(&vars("---\nstatus: draft\n---\nBody\n")),
+            "---\nstatus: draft\n---\n# Weekly: Sync\n\nBody\n"
+        );
+    }
+
+    #[test]
+    fn test_load_and_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = Template::dir(temp_dir.path());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("meeting.md"), "# {{title}}\n").unwrap();
+        fs::write(dir.join("daily.md"), "## {{date}}\n").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        assert_eq!(Template::list(temp_dir.path()).unwrap(), vec!["daily", "meeting"]);
+        assert_eq!(Template::load(temp_dir.path(), "meeting.md").unwrap().name, "meeting");
+        let err = Template::load(temp_dir.path(), "retro").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("available: daily, meeting"));
+        assert!(Template::load(temp_dir.path(), "../TASK").is_err());
+        assert!(Template::load(temp_dir.path(), ".hidden").is_err());
+    }
+}
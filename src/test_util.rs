@@ -0,0 +1,11 @@
+//! Helpers shared by unit tests
+
+use std::fs;
+use std::path::Path;
+
+/// Write `content` to `relative` under `root`, creating parent directories
+pub(crate) fn write(root: &Path, relative: &str, content: &str) {
+    let path = root.join(relative);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
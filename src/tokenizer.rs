@@ -0,0 +1,113 @@
+//! Tokenizer module for mixed CJK / Latin text
+//!
+//! Splits text into index terms:
+//! - Latin words are lowercased and stemmed (English Snowball stemmer),
+//!   so "Meetings" and "meeting" produce the same term
+//! - CJK runs are segmented into overlapping bigrams ("会议记录" → 会议, 议记, 记录),
+//!   a single CJK character becomes a unigram
+//!
+//! Bigram segmentation needs no dictionary and still gives good recall: any
+//! substring of two or more characters in the query matches the same bigrams
+//! in the document, and phrase queries keep them adjacent.
+
+use once_cell::sync::Lazy;
+use rust_stemmers::{Algorithm, Stemmer};
+
+static STEMMER: Lazy<Stemmer> = Lazy::new(|| Stemmer::create(Algorithm::English));
+
+/// Check if a character belongs to a CJK script (Han, Kana, Hangul)
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Unified Ideographs Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul Syllables
+        | 0xF900..=0xFAFF   // CJK Compatibility Ideographs
+        | 0x20000..=0x2FA1F // CJK Extensions B-F, Compatibility Supplement
+    )
+}
+
+/// Split text into normalized terms (stemmed Latin words and CJK bigrams)
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    for c in text.chars() {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.push(c);
+        } else {
+            flush_word(&mut word, &mut tokens);
+            flush_cjk(&mut cjk_run, &mut tokens);
+        }
+    }
+    flush_word(&mut word, &mut tokens);
+    flush_cjk(&mut cjk_run, &mut tokens);
+
+    tokens
+}
+
+/// Stem a single (already lowercased) Latin word
+pub fn stem(word: &str) -> String {
+    STEMMER.stem(word).into_owned()
+}
+
+/// Emit the pending Latin word as a stemmed term
+fn flush_word(word: &mut String, tokens: &mut Vec<String>) {
+    if !word.is_empty() {
+        tokens.push(stem(&word.to_lowercase()));
+        word.clear();
+    }
+}
+
+/// Emit the pending CJK run as bigrams (or a unigram for a single character)
+fn flush_cjk(run: &mut Vec<char>, tokens: &mut Vec<String>) {
+    match run.len() {
+        0 => {}
+        1 => tokens.push(run[0].to_string()),
+        _ => {
+            for pair in run.windows(2) {
+                tokens.push(pair.iter().collect());
+            }
+        }
+    }
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin_stemming() {
+        assert_eq!(tokenize("Meetings about Handling errors"), vec!["meet", "about", "handl", "error"]);
+        assert_eq!(tokenize("meeting"), tokenize("meetings"));
+    }
+
+    #[test]
+    fn test_cjk_bigrams() {
+        assert_eq!(tokenize("会议记录"), vec!["会议", "议记", "记录"]);
+        assert_eq!(tokenize("会"), vec!["会"]);
+    }
+
+    #[test]
+    fn test_mixed_text() {
+        assert_eq!(
+            tokenize("Rust项目会议，notes"),
+            vec!["rust", "项目", "目会", "会议", "note"]
+        );
+    }
+
+    #[test]
+    fn test_is_cjk() {
+        assert!(is_cjk('中'));
+        assert!(is_cjk('カ'));
+        assert!(is_cjk('한'));
+        assert!(!is_cjk('a'));
+        assert!(!is_cjk('，'));
+    }
+}
@@ -0,0 +1,270 @@
+//! Capsa trash bin
+//!
+//! Deleted notes are moved to `.trash/{id}/{relative path}` in the capsa
+//! instead of being unlinked. The id is the deletion time (`YYYYMMDDHHMMSS`,
+//! with a numeric suffix if several deletions share a second).
+//!
+//! Each entry has a sidecar record `.trash/{id}.yaml` with the original path,
+//! deletion time, deleting agent and the indexes the note was removed from,
+//! so that restoring it can put everything back:
+//!
+//! ```yaml
+//! id: '20260214100000'
+//! path: note/idea.md
+//! deleted: 2026-02-14 10:00:00
+//! agent: alice
+//! tags:
+//! - rust
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::constants as C;
+use crate::engine::CapsaEngine;
+use crate::{util, CapsaRef};
+
+/// Sidecar record of a trashed note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Entry id (name of the entry directory)
+    pub id: String,
+    /// Original path relative to the capsa root
+    pub path: String,
+    /// Deletion time (`YYYY-MM-DD HH:MM:SS`, local time)
+    pub deleted: String,
+    /// Agent that deleted the note (EMX_AGENT_NAME)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Tags the note was removed from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Title of the note's entry in `note/#daily.md`, if it had one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_title: Option<String>,
+}
+
+impl TrashEntry {
+    /// Time since deletion (zero if the time cannot be parsed)
+    pub fn age(&self) -> Duration {
+        NaiveDateTime::parse_from_str(&self.deleted, C::TRASH_TIME_FORMAT).ok()
+            .and_then(|naive| Local.from_local_datetime(&naive).single())
+            .and_then(|deleted| (Local::now() - deleted).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Trash bin of a capsa
+pub struct Trash<'a> {
+    capsa: &'a CapsaRef,
+}
+
+impl<'a> Trash<'a> {
+    /// Get the trash bin of a capsa
+    pub fn new(capsa: &'a CapsaRef) -> Self {
+        Self { capsa }
+    }
+
+    /// Trash directory path
+    pub fn dir(&self) -> PathBuf {
+        self.capsa.path.join(C::TRASH_DIR)
+    }
+
+    /// Path of the trashed file of an entry
+    pub fn file(&self, entry: &TrashEntry) -> PathBuf {
+        self.dir().join(&entry.id).join(&entry.path)
+    }
+
+    /// Move a file of the capsa into the trash, keeping its relative path,
+    /// and write the entry's sidecar record
+    pub fn put(&self, path: &Path) -> io::Result<TrashEntry> {
+        let relative = path.strip_prefix(&self.capsa.path).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Not inside the capsa: {}", path.display()),
+            )
+        })?;
+
+        let now = CapsaEngine::get_timestamp();
+        let entry_dir = self.create_entry_dir(&now.format(C::CONFLICT_TIMESTAMP_FORMAT).to_string())?;
+        let entry = TrashEntry {
+            id: entry_dir.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            path: relative.to_string_lossy().replace('\\', "/"),
+            deleted: now.format(C::TRASH_TIME_FORMAT).to_string(),
+            agent: std::env::var("EMX_AGENT_NAME").ok().filter(|s| !s.is_empty()),
+            tags: Vec::new(),
+            daily_title: None,
+        };
+
+        let trashed = self.file(&entry);
+        if let Some(parent) = trashed.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(path, &trashed)?;
+        self.save(&entry)?;
+        Ok(entry)
+    }
+
+    /// Write an entry's sidecar record
+    pub fn save(&self, entry: &TrashEntry) -> io::Result<()> {
+        let content = serde_yaml::to_string(entry)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        util::atomic_write(&self.record_file(&entry.id), &content)
+    }
+
+    /// Get an entry by id
+    pub fn get(&self, id: &str) -> io::Result<TrashEntry> {
+        let record = self.record_file(id);
+        if id.is_empty() || id.contains(['/', '\\']) || !record.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Trash entry '{}' not found", id),
+            ));
+        }
+
+        let content = fs::read_to_string(&record)?;
+        let mut entry: TrashEntry = serde_yaml::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid trash record {}: {}", util::display_path(&record), e),
+            )
+        })?;
+        // The file name is authoritative
+        entry.id = id.to_string();
+        Ok(entry)
+    }
+
+    /// List all entries, oldest first
+    pub fn list(&self) -> io::Result<Vec<TrashEntry>> {
+        let dir = self.dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(id) = name.strip_suffix(C::TRASH_RECORD_EXTENSION) {
+                ids.push(id.to_string());
+            }
+        }
+        ids.sort();
+
+        ids.iter().map(|id| self.get(id)).collect()
+    }
+
+    /// Delete an entry (trashed file and record) for good
+    pub fn remove(&self, id: &str) -> io::Result<()> {
+        self.get(id)?;
+        let entry_dir = self.dir().join(id);
+        if entry_dir.exists() {
+            fs::remove_dir_all(&entry_dir)?;
+        }
+        fs::remove_file(self.record_file(id))
+    }
+
+    /// Delete entries older than `older_than` (all entries if None).
+    /// Returns the removed entries.
+    pub fn empty(&self, older_than: Option<Duration>) -> io::Result<Vec<TrashEntry>> {
+        let mut removed = Vec::new();
+        for entry in self.list()? {
+            if older_than.is_none_or(|min_age| entry.age() >= min_age) {
+                self.remove(&entry.id)?;
+                removed.push(entry);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Sidecar record path of an entry
+    fn record_file(&self, id: &str) -> PathBuf {
+        self.dir().join(format!("{}{}", id, C::TRASH_RECORD_EXTENSION))
+    }
+
+    /// Create a new, unique entry directory named after the deletion time
+    fn create_entry_dir(&self, id: &str) -> io::Result<PathBuf> {
+        let trash_dir = self.dir();
+        fs::create_dir_all(&trash_dir)?;
+
+        for n in 1..=C::MAX_CONFLICT_SUFFIX {
+            let name = if n == 1 { id.to_string() } else { format!("{}-{}", id, n) };
+            let dir = trash_dir.join(name);
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(dir),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("No free trash entry for {}", id),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn capsa(temp_dir: &TempDir) -> CapsaRef {
+        CapsaRef {
+            name: "test".to_string(),
+            path: temp_dir.path().to_path_buf(),
+            is_link: false,
+            is_default: false,
+        }
+    }
+
+    #[test]
+    fn test_put_get_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        let capsa = capsa(&temp_dir);
+        let trash = Trash::new(&capsa);
+        let note = temp_dir.path().join("note/idea.md");
+        fs::create_dir_all(note.parent().unwrap()).unwrap();
+        fs::write(&note, "# Idea\n").unwrap();
+
+        let mut entry = trash.put(&note).unwrap();
+        assert!(!note.exists());
+        assert_eq!(entry.path, "note/idea.md");
+        assert_eq!(fs::read_to_string(trash.file(&entry)).unwrap(), "# Idea\n");
+
+        entry.tags = vec!["rust".to_string()];
+        trash.save(&entry).unwrap();
+        assert_eq!(trash.get(&entry.id).unwrap(), entry);
+        assert_eq!(trash.list().unwrap(), vec![entry.clone()]);
+
+        trash.remove(&entry.id).unwrap();
+        assert!(trash.list().unwrap().is_empty());
+        assert_eq!(trash.get(&entry.id).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_empty_older_than() {
+        let temp_dir = TempDir::new().unwrap();
+        let capsa = capsa(&temp_dir);
+        let trash = Trash::new(&capsa);
+        for name in ["a.md", "b.md"] {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, "x").unwrap();
+            trash.put(&path).unwrap();
+        }
+        let mut old = trash.list().unwrap().remove(0);
+        old.deleted = "2000-01-01 00:00:00".to_string();
+        trash.save(&old).unwrap();
+
+        let removed = trash.empty(Some(Duration::from_secs(86400))).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, old.id);
+        assert_eq!(trash.list().unwrap().len(), 1);
+
+        trash.empty(None).unwrap();
+        assert!(trash.list().unwrap().is_empty());
+    }
+}
@@ -1,7 +1,9 @@
 //! Utility functions for secure path handling and common operations
 
-use std::io::{self, Read};
+use std::fs;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use sha2::{Sha256, Digest};
 use crate::constants as C;
 
@@ -215,6 +217,98 @@ pub fn display_path(path: &Path) -> String {
     path.to_string_lossy().replace('\\', "/")
 }
 
+/// Path relative to `root` with forward slashes, or `path` itself if it is
+/// not under `root`
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    display_path(path.strip_prefix(root).unwrap_or(path))
+}
+
+/// Write a file atomically.
+///
+/// The content goes to a temporary file in the same directory, which is
+/// fsynced and then renamed over the target, so readers (and a crash or
+/// Ctrl-C mid-write) see either the old or the new content, never a truncated file.
+pub fn atomic_write(path: &Path, content: &str) -> io::Result<()> {
+    let temp = write_temp_sibling(path, content)?;
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Keep the current version of a file as `{name}.bak` (no-op if it does not exist)
+pub fn backup_file(path: &Path) -> io::Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let backup = path.with_file_name(format!("{}{}", name, C::BACKUP_EXTENSION));
+    let content = fs::read_to_string(path)?;
+    atomic_write(&backup, &content)
+}
+
+/// Write a new file without ever replacing an existing one.
+///
+/// The content is written to a temporary sibling first and then hard-linked
+/// into place, so the file appears complete or not at all. Fails with
+/// `AlreadyExists` if `path` exists (including when another process wins the race).
+pub fn write_new_file(path: &Path, content: &str) -> io::Result<()> {
+    let temp = write_temp_sibling(path, content)?;
+    let linked = fs::hard_link(&temp, path);
+    let _ = fs::remove_file(&temp);
+
+    match linked {
+        Ok(()) => {
+            sync_parent_dir(path);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+        Err(_) => {
+            // Filesystem without hard links: fall back to exclusive create
+            let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        }
+    }
+}
+
+/// Write content to a hidden temporary file next to `path` and fsync it
+///
+/// The temporary name holds the pid and a per-process counter, so concurrent
+/// writers (processes or threads) never share a temporary file.
+fn write_temp_sibling(path: &Path, content: &str) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp = dir.join(format!(".{}.{}.{}.tmp", name, std::process::id(), count));
+
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    Ok(temp)
+}
+
+/// Persist a rename/link in the parent directory (best effort, Unix only)
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
 /// Read content from stdin, returns empty string if no data
 pub fn read_stdin_content() -> io::Result<String> {
     let mut buffer = String::new();
@@ -230,6 +324,47 @@ pub fn read_stdin_content() -> io::Result<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_write_new_file_never_overwrites() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("note.md");
+
+        write_new_file(&path, "first").unwrap();
+        let err = write_new_file(&path, "second").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_atomic_write_replaces_content() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("TASK.md");
+
+        atomic_write(&path, "first").unwrap();
+        atomic_write(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_backup_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("#rust.md");
+
+        // Missing file: nothing to back up
+        backup_file(&path).unwrap();
+        assert!(!temp_dir.path().join("#rust.md.bak").exists());
+
+        fs::write(&path, "old").unwrap();
+        backup_file(&path).unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join("#rust.md.bak")).unwrap(), "old");
+    }
+
     #[test]
     fn test_secure_path_normal() {
         let base = PathBuf::from("/home/user/notes");